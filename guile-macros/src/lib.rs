@@ -0,0 +1,384 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+//! Procedural macros for the `guile` crate, which re-exports them.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Attribute, Data, DataStruct, DeriveInput, Error, Expr, ExprLit, Fields,
+    FnArg, ItemFn, Lit, LitStr, Meta, MetaNameValue, Type,
+};
+
+/// Guile's limit on the number of parameters of a primitive.
+const MAX_PARAMS: usize = 10;
+
+/// Where a parameter falls in Guile's required/optional/rest model, in
+/// the order they have to come in.
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Kind {
+    Required,
+    Optional,
+    Rest,
+}
+
+/* Decided by name, since a proc macro can't resolve types. */
+fn kind(ty: &Type) -> Kind {
+    if let Type::Path(path) = ty {
+        if let Some(last) = path.path.segments.last() {
+            if last.ident == "Option" {
+                return Kind::Optional;
+            }
+            if last.ident == "Rest" {
+                return Kind::Rest;
+            }
+        }
+    }
+    Kind::Required
+}
+
+/// Expose a Rust function to Scheme.
+///
+/// ```ignore
+/// #[guile_fn]
+/// fn add(a: i64, b: i64) -> i64 {
+///     a + b
+/// }
+///
+/// vm.define_guile_fn::<add>();
+/// ```
+///
+/// Arguments are converted with `FromScm`, failing with `wrong-type-arg`,
+/// and the result with `ToScm`; a function returning
+/// `Result<T, GuileError>` throws its errors. The Scheme name is the
+/// Rust one with `_` turned into `-`, unless given as
+/// `#[guile_fn(name = "add!")]`. The function stays callable from Rust.
+///
+/// Trailing `Option<T>` parameters are optional arguments, `None` when
+/// left out, and a last `Rest` parameter gathers any arguments after
+/// those. The doc comment becomes the docstring.
+#[proc_macro_attribute]
+pub fn guile_fn(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut name = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse::<LitStr>()?.value());
+            Ok(())
+        } else {
+            Err(meta.error("expected `name = \"...\"`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+    let item = parse_macro_input!(item as ItemFn);
+    expand(name, item)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(name: Option<String>, item: ItemFn) -> Result<proc_macro2::TokenStream, Error> {
+    let sig = &item.sig;
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new(
+            sig.generics.span(),
+            "#[guile_fn] functions can't be generic",
+        ));
+    }
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(Error::new(
+            asyncness.span(),
+            "#[guile_fn] functions can't be async",
+        ));
+    }
+    let mut types = Vec::new();
+    let mut kinds: Vec<Kind> = Vec::new();
+    for input in &sig.inputs {
+        let arg = match input {
+            FnArg::Typed(arg) => arg,
+            FnArg::Receiver(receiver) => {
+                return Err(Error::new(
+                    receiver.span(),
+                    "#[guile_fn] functions can't take self",
+                ))
+            }
+        };
+        let kind = kind(&arg.ty);
+        match kinds.last() {
+            Some(&Kind::Rest) => {
+                return Err(Error::new(
+                    arg.span(),
+                    "the `Rest` parameter has to come last",
+                ))
+            }
+            Some(&last) if kind < last => {
+                return Err(Error::new(
+                    arg.span(),
+                    "required parameters have to come before `Option` ones",
+                ))
+            }
+            _ => {}
+        }
+        types.push(&arg.ty);
+        kinds.push(kind);
+    }
+    if types.len() > MAX_PARAMS {
+        return Err(Error::new(
+            sig.inputs.span(),
+            format!("Guile primitives take at most {} parameters", MAX_PARAMS),
+        ));
+    }
+
+    let doc = doc_comment(&item.attrs);
+    let doc = match doc {
+        Some(doc) => quote!(::std::option::Option::Some(#doc)),
+        None => quote!(::std::option::Option::None),
+    };
+    let ident = &sig.ident;
+    let vis = &item.vis;
+    let scheme_name = name.unwrap_or_else(|| ident.to_string().replace('_', "-"));
+    let count = |k| kinds.iter().filter(|&&kind| kind == k).count();
+    let required = count(Kind::Required);
+    let optional = count(Kind::Optional);
+    let rest = count(Kind::Rest) == 1;
+    let params: Vec<_> = (0..types.len())
+        .map(|i| format_ident!("arg{}", i, span = Span::mixed_site()))
+        .collect();
+    let values: Vec<_> = (0..types.len())
+        .map(|i| format_ident!("value{}", i, span = Span::mixed_site()))
+        .collect();
+    let conversions = kinds
+        .iter()
+        .zip(&params)
+        .enumerate()
+        .map(|(i, (kind, param))| {
+            let pos = i + 1;
+            match kind {
+                Kind::Required => quote!(args.get(#pos, #param)?),
+                Kind::Optional => quote!(args.get_opt(#pos, #param)?),
+                Kind::Rest => quote!(args.rest(#param)),
+            }
+        });
+    let scm_types = params.iter().map(|_| quote!(::guile::__private::SCM));
+    let subr_type = quote!(extern "C" fn(#(#scm_types),*) -> ::guile::__private::SCM);
+    Ok(quote! {
+        #item
+
+        #[doc(hidden)]
+        #[allow(non_camel_case_types)]
+        #vis struct #ident {}
+
+        impl ::guile::GuileFn for #ident {
+            const NAME: &'static str = #scheme_name;
+            const DOC: ::std::option::Option<&'static str> = #doc;
+            const REQUIRED: usize = #required;
+            const OPTIONAL: usize = #optional;
+            const REST: bool = #rest;
+            type Subr = #subr_type;
+            const SUBR: Self::Subr = {
+                extern "C" fn subr(#(#params: ::guile::__private::SCM),*) -> ::guile::__private::SCM {
+                    unsafe {
+                        ::guile::subr_body(#scheme_name, |args| {
+                            #(let #values: #types = #conversions;)*
+                            ::guile::SubrReturn::into_subr_result(#ident(#(#values),*), args.vm())
+                        })
+                    }
+                }
+                subr
+            };
+        }
+    })
+}
+
+/* `///` lines arrive as `#[doc = " line"]`; drop the space after the
+ * slashes. */
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(MetaNameValue {
+                value:
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(line),
+                        ..
+                    }),
+                ..
+            }) => Some(line.value()),
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').unwrap_or(&line).to_string())
+        .collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n").trim().to_string())
+    }
+}
+
+/// Derive `FromKeywordArgs`; see that trait for the attributes.
+#[proc_macro_derive(FromKeywordArgs, attributes(guile))]
+pub fn derive_from_keyword_args(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_keyword_args(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// What a field gets when its keyword is left out.
+enum FieldDefault {
+    /// `None` for an `Option`, an error otherwise.
+    Required,
+    /// `#[guile(default)]`
+    Trait,
+    /// `#[guile(default = expr)]`
+    Expr(Expr),
+}
+
+fn expand_keyword_args(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let mut allow_other_keys = false;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("guile"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("allow_other_keys") {
+                allow_other_keys = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `allow_other_keys`"))
+            }
+        })?;
+    }
+    let fields = match &input.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => {
+            return Err(Error::new(
+                input.ident.span(),
+                "FromKeywordArgs can only be derived for structs with named fields",
+            ))
+        }
+    };
+    let generics = &input.generics;
+    let mut lifetimes = generics.lifetimes();
+    let lifetime = lifetimes.next().map(|param| &param.lifetime);
+    if lifetimes.next().is_some() || generics.type_params().next().is_some() {
+        return Err(Error::new(
+            generics.span(),
+            "FromKeywordArgs structs can only be generic over one lifetime",
+        ));
+    }
+    let ident = &input.ident;
+    let (vm, self_ty) = match lifetime {
+        Some(lifetime) => (quote!(#lifetime), quote!(#ident<#lifetime>)),
+        None => (quote!('vm), quote!(#ident)),
+    };
+
+    let mut keywords = Vec::new();
+    let mut values = Vec::new();
+    let field_idents: Vec<_> = fields.iter().map(|field| &field.ident).collect();
+    let slots: Vec<_> = (0..fields.len())
+        .map(|i| format_ident!("slot{}", i, span = Span::mixed_site()))
+        .collect();
+    let found: Vec<_> = (0..fields.len())
+        .map(|i| format_ident!("found{}", i, span = Span::mixed_site()))
+        .collect();
+    let args = format_ident!("args", span = Span::mixed_site());
+    for (field, value) in fields.iter().zip(&found) {
+        let mut keyword = field.ident.as_ref().unwrap().to_string().replace('_', "-");
+        let mut default = FieldDefault::Required;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("guile"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    keyword = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else if meta.path.is_ident("default") {
+                    default = match meta.value() {
+                        Ok(value) => FieldDefault::Expr(value.parse()?),
+                        Err(_) => FieldDefault::Trait,
+                    };
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `name = \"...\"` or `default`"))
+                }
+            })?;
+        }
+        /* Only the slot reads need `unsafe`; the defaults are user code and
+         * run outside it. */
+        values.push(match default {
+            FieldDefault::Required if kind(&field.ty) == Kind::Optional => quote!(#value),
+            FieldDefault::Required => quote! {
+                #value.ok_or_else(|| ::guile::__private::missing_keyword(#keyword))?
+            },
+            FieldDefault::Trait => quote!(#value.unwrap_or_default()),
+            FieldDefault::Expr(expr) => quote!(#value.unwrap_or_else(|| #expr)),
+        });
+        keywords.push(keyword);
+    }
+
+    let subr = format_ident!("subr", span = Span::mixed_site());
+    let rest = format_ident!("rest", span = Span::mixed_site());
+    let flags = format_ident!("flags", span = Span::mixed_site());
+    /* A bare `()` at the end of the block would trip `clippy::unused_unit`. */
+    let read_slots = if fields.is_empty() {
+        quote!()
+    } else {
+        quote!((#(::guile::__private::keyword_value(#args, #keywords, #slots)?,)*))
+    };
+    Ok(quote! {
+        impl<#vm> ::guile::FromKeywordArgs<#vm> for #self_ty {
+            fn from_keyword_args(
+                #subr: &str,
+                #args: ::guile::ScmList<#vm>,
+            ) -> ::std::result::Result<Self, ::guile::GuileError> {
+                #(let mut #slots = ::guile::__private::SCM_UNDEFINED;)*
+                let (#(#found,)*) = unsafe {
+                    ::guile::__private::bind_keyword_arguments(
+                        #subr,
+                        #args,
+                        #allow_other_keys,
+                        |#subr, #rest, #flags| {
+                            ::guile::__private::scm_c_bind_keyword_arguments(
+                                #subr,
+                                #rest,
+                                #flags,
+                                #(
+                                    ::guile::__private::keyword(#keywords),
+                                    &mut #slots as *mut ::guile::__private::SCM,
+                                )*
+                                ::guile::__private::SCM_UNDEFINED
+                            )
+                        },
+                    )?;
+                    #read_slots
+                };
+                ::std::result::Result::Ok(Self {
+                    #(#field_idents: #values,)*
+                })
+            }
+        }
+    })
+}
@@ -0,0 +1,81 @@
+// Definitions that libguile only provides as C macros or `SCM_INLINE`
+// functions, which bindgen does not emit.
+
+use crate::bindings::{scm_t_bits, SCM};
+
+const fn makiflag(n: scm_t_bits) -> SCM {
+    ((n << 8) + 4) as SCM
+}
+
+pub const SCM_BOOL_F: SCM = makiflag(0);
+pub const SCM_ELISP_NIL: SCM = makiflag(1);
+pub const SCM_EOL: SCM = makiflag(3);
+pub const SCM_BOOL_T: SCM = makiflag(4);
+pub const SCM_UNSPECIFIED: SCM = makiflag(8);
+pub const SCM_UNDEFINED: SCM = makiflag(9);
+pub const SCM_EOF_VAL: SCM = makiflag(10);
+pub const SCM_UNBOUND: SCM = makiflag(11);
+
+#[inline]
+pub fn scm_unpack(x: SCM) -> scm_t_bits {
+    x as scm_t_bits
+}
+
+#[inline]
+pub fn scm_is_eq(x: SCM, y: SCM) -> bool {
+    scm_unpack(x) == scm_unpack(y)
+}
+
+/* SCM_MATCHES_BITS_IN_COMMON: #f and #nil are both false */
+#[inline]
+pub fn scm_is_false(x: SCM) -> bool {
+    let (a, b) = (scm_unpack(SCM_ELISP_NIL), scm_unpack(SCM_BOOL_F));
+    scm_unpack(x) & !(a ^ b) == a & b
+}
+
+#[inline]
+pub fn scm_is_true(x: SCM) -> bool {
+    !scm_is_false(x)
+}
+
+#[inline]
+pub fn scm_is_null(x: SCM) -> bool {
+    let (a, b) = (scm_unpack(SCM_ELISP_NIL), scm_unpack(SCM_EOL));
+    scm_unpack(x) & !(a ^ b) == a & b
+}
+
+#[inline]
+pub fn scm_from_bool(x: bool) -> SCM {
+    if x {
+        SCM_BOOL_T
+    } else {
+        SCM_BOOL_F
+    }
+}
+
+/* Characters are immediates tagged with scm_tc8_char. */
+const scm_tc8_char: scm_t_bits = 0x0c;
+
+#[inline]
+pub fn SCM_CHARP(x: SCM) -> bool {
+    scm_unpack(x) & 0xff == scm_tc8_char
+}
+
+#[inline]
+pub fn SCM_CHAR(x: SCM) -> u32 {
+    (scm_unpack(x) >> 8) as u32
+}
+
+#[inline]
+pub fn SCM_MAKE_CHAR(x: u32) -> SCM {
+    (((x as scm_t_bits) << 8) + scm_tc8_char) as SCM
+}
+
+/* These are `SCM_INLINE` in the headers, but libguile also exports
+ * them from inline.c, so we can link against them directly. */
+extern "C" {
+    pub fn scm_is_pair(x: SCM) -> ::std::os::raw::c_int;
+    pub fn scm_cons(x: SCM, y: SCM) -> SCM;
+    pub fn scm_car(x: SCM) -> SCM;
+    pub fn scm_cdr(x: SCM) -> SCM;
+}
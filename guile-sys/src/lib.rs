@@ -6,4 +6,6 @@
 )]
 
 mod bindings;
+mod inline;
 pub use bindings::*;
+pub use inline::*;
@@ -0,0 +1,137 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use guile_sys::SCM;
+
+use crate::convert::to_raw;
+use crate::{FromScm, GuileVM, Scm, ToScm};
+
+/// An association list: a list of `(key . value)` pairs, where earlier
+/// entries shadow later ones with the same key.
+#[derive(Clone, Copy)]
+pub struct ScmAlist<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> ScmAlist<'vm> {
+    pub fn new(vm: &'vm GuileVM) -> ScmAlist<'vm> {
+        ScmAlist { scm: Scm::eol(vm) }
+    }
+
+    /// Returns `None` unless `scm` is a proper list of pairs.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmAlist<'vm>> {
+        let mut entries = crate::ScmList::from_scm(scm)?.iter();
+        if entries.all(|entry| entry.is_pair()) {
+            Some(ScmAlist { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    fn lookup(&self, key: SCM, assoc: unsafe extern "C" fn(SCM, SCM) -> SCM) -> Option<Scm<'vm>> {
+        let entry = unsafe { assoc(key, self.scm.as_raw()) };
+        if guile_sys::scm_is_false(entry) {
+            None
+        } else {
+            Some(self.scm.with_raw(unsafe { guile_sys::scm_cdr(entry) }))
+        }
+    }
+
+    /// The value for `key`, compared with `eq?` (right for symbols).
+    pub fn assq(&self, key: &(impl ToScm + ?Sized)) -> Option<Scm<'vm>> {
+        self.lookup(to_raw(key), guile_sys::scm_assq)
+    }
+
+    /// The value for `key`, compared with `eqv?` (right for numbers and
+    /// characters).
+    pub fn assv(&self, key: &(impl ToScm + ?Sized)) -> Option<Scm<'vm>> {
+        self.lookup(to_raw(key), guile_sys::scm_assv)
+    }
+
+    /// The value for `key`, compared with `equal?` (right for strings).
+    pub fn assoc(&self, key: &(impl ToScm + ?Sized)) -> Option<Scm<'vm>> {
+        self.lookup(to_raw(key), guile_sys::scm_assoc)
+    }
+
+    /// `assoc`, converted to `T`; `None` if the key is missing or the
+    /// value has the wrong type.
+    pub fn get<T: FromScm<'vm>>(&self, key: &(impl ToScm + ?Sized)) -> Option<T> {
+        self.assoc(key).and_then(T::from_scm)
+    }
+
+    /// Add an entry at the front, shadowing any existing one.
+    pub fn acons(&mut self, key: &(impl ToScm + ?Sized), value: &(impl ToScm + ?Sized)) {
+        let key = to_raw(key);
+        let value = to_raw(value);
+        self.scm = self
+            .scm
+            .with_raw(unsafe { guile_sys::scm_acons(key, value, self.scm.as_raw()) });
+    }
+
+    /// Remove every entry whose key is `equal?` to `key`.
+    pub fn remove(&mut self, key: &(impl ToScm + ?Sized)) {
+        let key = to_raw(key);
+        /* `assoc-remove!` only removes the first match. */
+        unsafe {
+            while guile_sys::scm_is_true(guile_sys::scm_assoc(key, self.scm.as_raw())) {
+                self.scm = self
+                    .scm
+                    .with_raw(guile_sys::scm_assoc_remove_x(self.scm.as_raw(), key));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language, ScmSymbol};
+
+    #[test]
+    fn typed_lookup() {
+        init(|vm| {
+            let config = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(list (cons 'port 80) (cons \"name\" \"web\"))",
+                )
+                .unwrap();
+            let mut alist = ScmAlist::from_scm(config).unwrap();
+            let port = ScmSymbol::new(&vm, "port").as_scm();
+            assert_eq!(alist.assq(&port).and_then(|v| v.get::<u16>()), Some(80));
+            assert_eq!(alist.get::<String>("name").as_deref(), Some("web"));
+            assert_eq!(alist.get::<u16>("name"), None);
+            assert!(alist.assq("name").is_none());
+
+            alist.acons(&port, &8080u16);
+            assert_eq!(alist.get::<u16>(&port), Some(8080));
+            alist.remove(&port);
+            assert!(alist.assq(&port).is_none());
+            assert_eq!(format!("{:?}", alist.as_scm()), "((\"name\" . \"web\"))");
+
+            let mut fresh = ScmAlist::new(&vm);
+            fresh.acons(&1i32, &'a');
+            assert_eq!(fresh.assv(&1i32).and_then(|v| v.get::<char>()), Some('a'));
+            assert!(ScmAlist::from_scm(1i32.to_scm(&vm)).is_none());
+            assert!(ScmAlist::from_scm(vec![1i32].to_scm(&vm)).is_none());
+        });
+    }
+}
@@ -0,0 +1,381 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::slice;
+
+use guile_sys::{scm_t_array_dim, scm_t_array_element_type, scm_t_array_handle, SCM};
+
+use crate::error::protect;
+use crate::scm::list_from_raw;
+use crate::{GuileError, Scm, UniformElement};
+
+/// An `scm_t_array_handle` that's released when dropped.
+///
+/// Boxed so it never moves: Guile can point `dims` back into the handle
+/// itself.
+pub(crate) struct ArrayHandle {
+    raw: Box<scm_t_array_handle>,
+}
+
+impl ArrayHandle {
+    /// A handle for Guile to fill in, for the `scm_*_elements` functions
+    /// that acquire it themselves. Must be passed to one of them before
+    /// it's dropped.
+    pub(crate) fn empty() -> ArrayHandle {
+        ArrayHandle {
+            raw: Box::new(unsafe { std::mem::zeroed() }),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `array` must satisfy `scm_is_array`.
+    pub(crate) unsafe fn new(array: SCM) -> ArrayHandle {
+        let mut handle = ArrayHandle::empty();
+        guile_sys::scm_array_get_handle(array, handle.as_mut_ptr());
+        handle
+    }
+
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut scm_t_array_handle {
+        &mut *self.raw
+    }
+
+    pub(crate) fn dims(&self) -> &[scm_t_array_dim] {
+        if self.raw.ndims == 0 {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.raw.dims, self.raw.ndims) }
+        }
+    }
+
+    pub(crate) fn element_type(&self) -> scm_t_array_element_type {
+        self.raw.element_type
+    }
+}
+
+impl Drop for ArrayHandle {
+    fn drop(&mut self) {
+        unsafe { guile_sys::scm_array_handle_release(self.as_mut_ptr()) };
+    }
+}
+
+/// The bounds of one dimension of an array, and how far apart its
+/// elements are in the underlying storage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArrayDim {
+    /// The lowest valid index, usually 0.
+    pub lower: isize,
+    /// The highest valid index, inclusive.
+    pub upper: isize,
+    /// Elements between consecutive indices.
+    pub stride: isize,
+}
+
+impl ArrayDim {
+    pub fn len(&self) -> usize {
+        (self.upper - self.lower + 1).max(0) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn contains(&self, idx: isize) -> bool {
+        self.lower <= idx && idx <= self.upper
+    }
+}
+
+/// A Scheme array of any rank and element type, including vectors,
+/// strings and SRFI-4 vectors as the rank-1 case.
+#[derive(Clone, Copy)]
+pub struct ScmArray<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> ScmArray<'vm> {
+    /// Returns `None` if `scm` is not an array.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmArray<'vm>> {
+        if unsafe { guile_sys::scm_is_array(scm.as_raw()) } != 0 {
+            Some(ScmArray { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    fn handle(&self) -> ArrayHandle {
+        unsafe { ArrayHandle::new(self.scm.as_raw()) }
+    }
+
+    pub fn rank(&self) -> usize {
+        unsafe { guile_sys::scm_c_array_rank(self.scm.as_raw()) }
+    }
+
+    pub fn dims(&self) -> Vec<ArrayDim> {
+        self.handle()
+            .dims()
+            .iter()
+            .map(|dim| ArrayDim {
+                lower: dim.lbnd,
+                upper: dim.ubnd,
+                stride: dim.inc,
+            })
+            .collect()
+    }
+
+    /// The length of each dimension.
+    pub fn shape(&self) -> Vec<usize> {
+        self.dims().iter().map(ArrayDim::len).collect()
+    }
+
+    /// The stride of each dimension, in elements.
+    pub fn strides(&self) -> Vec<isize> {
+        self.dims().iter().map(|dim| dim.stride).collect()
+    }
+
+    unsafe fn index_list(indices: &[isize]) -> SCM {
+        list_from_raw(
+            indices
+                .iter()
+                .map(|&idx| guile_sys::scm_from_int64(idx as i64)),
+        )
+    }
+
+    fn in_bounds(&self, indices: &[isize]) -> bool {
+        let dims = self.dims();
+        dims.len() == indices.len()
+            && dims
+                .iter()
+                .zip(indices)
+                .all(|(dim, &idx)| dim.contains(idx))
+    }
+
+    /// Returns `None` if `indices` are out of bounds, or there are the
+    /// wrong number of them.
+    pub fn get(&self, indices: &[isize]) -> Option<Scm<'vm>> {
+        if !self.in_bounds(indices) {
+            return None;
+        }
+        unsafe {
+            let raw = guile_sys::scm_array_ref(self.scm.as_raw(), ScmArray::index_list(indices));
+            Some(self.scm.with_raw(raw))
+        }
+    }
+
+    /// Fails if `indices` are out of bounds, or `value` can't be stored
+    /// in an array of this element type.
+    pub fn set(&self, indices: &[isize], value: Scm<'vm>) -> Result<(), GuileError> {
+        unsafe {
+            let args = ScmArray::index_list(indices);
+            protect(|| guile_sys::scm_array_set_x(self.scm.as_raw(), value.as_raw(), args))?;
+        }
+        Ok(())
+    }
+
+    /* Row-major with no gaps, so the elements can be viewed as one
+     * slice in index order. */
+    fn is_contiguous(handle: &ArrayHandle) -> bool {
+        let mut expected = 1;
+        for dim in handle.dims().iter().rev() {
+            let len = (dim.ubnd - dim.lbnd + 1).max(0);
+            if len > 1 && dim.inc != expected {
+                return false;
+            }
+            expected *= len;
+        }
+        true
+    }
+
+    fn uniform_handle<T: UniformElement>(&self) -> Option<(ArrayHandle, usize)> {
+        let handle = self.handle();
+        if handle.element_type() == T::ELEMENT_TYPE && ScmArray::is_contiguous(&handle) {
+            let len = self.shape().iter().product();
+            Some((handle, len))
+        } else {
+            None
+        }
+    }
+
+    /// Borrow the elements in place, in row-major order. Returns `None`
+    /// unless the array holds `T`s and is laid out contiguously (a
+    /// transposed or strided view of another array, for example, isn't).
+    pub fn as_slice<T: UniformElement>(&self) -> Option<ArraySlice<'_, T>> {
+        let (handle, len) = self.uniform_handle::<T>()?;
+        Some(unsafe { ArraySlice::new(handle, len) })
+    }
+
+    /// Borrow the elements in place for writing, as
+    /// [`as_slice`](ScmArray::as_slice).
+    ///
+    /// # Safety
+    ///
+    /// Copies of this `ScmArray`, and other arrays sharing its storage,
+    /// aren't visible to the borrow checker. While the guard is alive,
+    /// nothing else may read or write the elements: no slice borrowed
+    /// through a copy or a shared view, no `get` or `set` through one, and
+    /// no Scheme code that touches the array.
+    pub unsafe fn as_mut_slice<T: UniformElement>(&mut self) -> Option<ArraySliceMut<'_, T>> {
+        let (handle, len) = self.uniform_handle::<T>()?;
+        Some(ArraySliceMut::new(handle, len))
+    }
+}
+
+/// The elements of a uniform Scheme array, borrowed in place and
+/// released when dropped.
+pub struct ArraySlice<'a, T> {
+    _handle: ArrayHandle,
+    elements: *const T,
+    len: usize,
+    _array: PhantomData<&'a [T]>,
+}
+
+impl<'a, T> ArraySlice<'a, T> {
+    /// # Safety
+    ///
+    /// `handle` must be for a contiguous array of at least `len`
+    /// elements of type `T`, which outlives `'a`.
+    pub(crate) unsafe fn new(mut handle: ArrayHandle, len: usize) -> ArraySlice<'a, T> {
+        let elements = guile_sys::scm_array_handle_uniform_elements(handle.as_mut_ptr());
+        ArraySlice {
+            _handle: handle,
+            elements: elements as *const T,
+            len,
+            _array: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for ArraySlice<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        if self.len == 0 {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.elements, self.len) }
+        }
+    }
+}
+
+/// The elements of a uniform Scheme array, borrowed in place for
+/// writing and released when dropped.
+pub struct ArraySliceMut<'a, T> {
+    _handle: ArrayHandle,
+    elements: *mut T,
+    len: usize,
+    _array: PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T> ArraySliceMut<'a, T> {
+    /// # Safety
+    ///
+    /// As for [`ArraySlice::new`], and nothing else may access the
+    /// elements during `'a`.
+    pub(crate) unsafe fn new(mut handle: ArrayHandle, len: usize) -> ArraySliceMut<'a, T> {
+        let elements = guile_sys::scm_array_handle_uniform_writable_elements(handle.as_mut_ptr());
+        ArraySliceMut {
+            _handle: handle,
+            elements: elements as *mut T,
+            len,
+            _array: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for ArraySliceMut<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        if self.len == 0 {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.elements, self.len) }
+        }
+    }
+}
+
+impl<T> DerefMut for ArraySliceMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        if self.len == 0 {
+            &mut []
+        } else {
+            unsafe { slice::from_raw_parts_mut(self.elements, self.len) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Language, ToScm};
+
+    #[test]
+    fn matrix() {
+        crate::init(|vm| {
+            let scm = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(list->typed-array 'f64 2 '((1 2 3) (4 5 6)))",
+                )
+                .unwrap();
+            let mut matrix = ScmArray::from_scm(scm).unwrap();
+            assert_eq!(matrix.rank(), 2);
+            assert_eq!(matrix.shape(), vec![2, 3]);
+            assert_eq!(matrix.strides(), vec![3, 1]);
+            assert_eq!(matrix.get(&[1, 2]).and_then(|x| x.get::<f64>()), Some(6.0));
+            assert!(matrix.get(&[2, 0]).is_none());
+            assert!(matrix.get(&[0]).is_none());
+
+            matrix.set(&[0, 0], 10.0f64.to_scm(&vm)).unwrap();
+            assert!(matrix.set(&[0, 0], "x".to_scm(&vm)).is_err());
+            assert!(matrix.set(&[5, 0], 1.0f64.to_scm(&vm)).is_err());
+            assert_eq!(
+                &*matrix.as_slice::<f64>().unwrap(),
+                &[10.0, 2.0, 3.0, 4.0, 5.0, 6.0]
+            );
+            let mut elements = unsafe { matrix.as_mut_slice::<f64>() }.unwrap();
+            elements[5] = 0.0;
+            drop(elements);
+            assert_eq!(matrix.get(&[1, 2]).and_then(|x| x.get::<f64>()), Some(0.0));
+            assert!(matrix.as_slice::<f32>().is_none());
+        });
+    }
+
+    #[test]
+    fn strided_views_have_no_slice() {
+        crate::init(|vm| {
+            let scm = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(transpose-array (list->typed-array 's32 2 '((1 2) (3 4))) 1 0)",
+                )
+                .unwrap();
+            let transposed = ScmArray::from_scm(scm).unwrap();
+            assert_eq!(
+                transposed.get(&[0, 1]).and_then(|x| x.get::<i32>()),
+                Some(3)
+            );
+            assert!(transposed.as_slice::<i32>().is_none());
+            assert!(ScmArray::from_scm(1i32.to_scm(&vm)).is_none());
+        });
+    }
+}
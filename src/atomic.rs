@@ -0,0 +1,144 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use guile_sys::SCM;
+
+use crate::convert::to_raw;
+use crate::error::{protect, GuileError};
+use crate::scm::public_ref;
+use crate::{GuileVM, Scm, ToScm};
+
+unsafe fn atomic(name: &str) -> SCM {
+    public_ref("ice-9 atomic", name)
+}
+
+/// A Guile atomic box, from `(ice-9 atomic)`: a cell that Scheme and
+/// Rust threads can read and update without a mutex. Values are
+/// compared with `eq?`.
+#[derive(Clone, Copy)]
+pub struct ScmAtomicBox<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> ScmAtomicBox<'vm> {
+    /// Fails if `(ice-9 atomic)` isn't available.
+    pub fn new(vm: &'vm GuileVM, init: impl ToScm) -> Result<ScmAtomicBox<'vm>, GuileError> {
+        let init = init.to_scm(vm).as_raw();
+        unsafe {
+            let raw = protect(|| guile_sys::scm_call_1(atomic("make-atomic-box"), init))?;
+            Ok(ScmAtomicBox {
+                scm: Scm::from_raw(vm, raw),
+            })
+        }
+    }
+
+    /// Returns `None` if `scm` is not an atomic box.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmAtomicBox<'vm>> {
+        let raw = scm.as_raw();
+        let is_box = unsafe { protect(|| guile_sys::scm_call_1(atomic("atomic-box?"), raw)) };
+        match is_box {
+            Ok(is_box) if guile_sys::scm_is_true(is_box) => Some(ScmAtomicBox { scm }),
+            _ => None,
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    pub fn get(&self) -> Scm<'vm> {
+        let raw = unsafe { guile_sys::scm_call_1(atomic("atomic-box-ref"), self.scm.as_raw()) };
+        self.scm.with_raw(raw)
+    }
+
+    pub fn set(&self, value: impl ToScm) {
+        unsafe {
+            guile_sys::scm_call_2(atomic("atomic-box-set!"), self.scm.as_raw(), to_raw(&value))
+        };
+    }
+
+    /// Store `value`, returning the previous contents.
+    pub fn swap(&self, value: impl ToScm) -> Scm<'vm> {
+        let raw = unsafe {
+            guile_sys::scm_call_2(
+                atomic("atomic-box-swap!"),
+                self.scm.as_raw(),
+                to_raw(&value),
+            )
+        };
+        self.scm.with_raw(raw)
+    }
+
+    /// Store `desired` if the box holds something `eq?` to `expected`.
+    /// Returns the previous contents either way; the swap happened iff
+    /// they're `eq?` to `expected`.
+    pub fn compare_and_swap(&self, expected: Scm<'vm>, desired: impl ToScm) -> Scm<'vm> {
+        let raw = unsafe {
+            guile_sys::scm_call_3(
+                atomic("atomic-box-compare-and-swap!"),
+                self.scm.as_raw(),
+                expected.as_raw(),
+                to_raw(&desired),
+            )
+        };
+        self.scm.with_raw(raw)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language, ScmProcedure};
+
+    #[test]
+    fn update() {
+        init(|vm| {
+            let cell = ScmAtomicBox::new(&vm, 1i32).unwrap();
+            assert_eq!(cell.get().get::<i32>(), Some(1));
+            cell.set(2i32);
+            assert_eq!(cell.swap(3i32).get::<i32>(), Some(2));
+
+            let current = cell.get();
+            let stale = 7i32.to_scm(&vm);
+            assert_eq!(vm.write(cell.compare_and_swap(stale, 4i32)), "3");
+            assert_eq!(cell.get().get::<i32>(), Some(3));
+            cell.compare_and_swap(current, 4i32);
+            assert_eq!(cell.get().get::<i32>(), Some(4));
+        });
+    }
+
+    #[test]
+    fn shared_with_scheme() {
+        init(|vm| {
+            let bump = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(use-modules (ice-9 atomic))
+                     (lambda (b) (atomic-box-set! b (1+ (atomic-box-ref b))))",
+                )
+                .unwrap();
+            let cell = ScmAtomicBox::new(&vm, 10i32).unwrap();
+            ScmProcedure::from_scm(bump)
+                .unwrap()
+                .call(&[cell.as_scm()])
+                .unwrap();
+            assert_eq!(cell.get().get::<i32>(), Some(11));
+            assert!(ScmAtomicBox::from_scm(cell.as_scm()).is_some());
+            assert!(ScmAtomicBox::from_scm(cell.get()).is_none());
+        });
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use crate::{GuileVM, Scm};
+
+/// A Scheme bitvector.
+#[derive(Clone, Copy)]
+pub struct ScmBitvector<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> ScmBitvector<'vm> {
+    /// Create a bitvector of `len` bits, all set to `fill`.
+    pub fn new(vm: &'vm GuileVM, len: usize, fill: bool) -> ScmBitvector<'vm> {
+        unsafe {
+            let raw = guile_sys::scm_c_make_bitvector(len, guile_sys::scm_from_bool(fill));
+            ScmBitvector {
+                scm: Scm::from_raw(vm, raw),
+            }
+        }
+    }
+
+    pub fn from_bools(vm: &'vm GuileVM, bits: &[bool]) -> ScmBitvector<'vm> {
+        let bv = ScmBitvector::new(vm, bits.len(), false);
+        for (idx, &bit) in bits.iter().enumerate() {
+            if bit {
+                unsafe { guile_sys::scm_c_bitvector_set_bit_x(bv.scm.as_raw(), idx) };
+            }
+        }
+        bv
+    }
+
+    /// Returns `None` if `scm` is not a bitvector.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmBitvector<'vm>> {
+        if unsafe { guile_sys::scm_is_bitvector(scm.as_raw()) } != 0 {
+            Some(ScmBitvector { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    pub fn len(&self) -> usize {
+        unsafe { guile_sys::scm_c_bitvector_length(self.scm.as_raw()) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `None` if `idx` is out of bounds.
+    pub fn get(&self, idx: usize) -> Option<bool> {
+        if idx < self.len() {
+            Some(unsafe { guile_sys::scm_c_bitvector_bit_is_set(self.scm.as_raw(), idx) } != 0)
+        } else {
+            None
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn set(&self, idx: usize, bit: bool) {
+        assert!(
+            idx < self.len(),
+            "bitvector index {} out of bounds (length {})",
+            idx,
+            self.len()
+        );
+        unsafe {
+            if bit {
+                guile_sys::scm_c_bitvector_set_bit_x(self.scm.as_raw(), idx);
+            } else {
+                guile_sys::scm_c_bitvector_clear_bit_x(self.scm.as_raw(), idx);
+            }
+        }
+    }
+
+    /// Set every bit to `bit`.
+    pub fn fill(&self, bit: bool) {
+        unsafe {
+            if bit {
+                guile_sys::scm_c_bitvector_set_all_bits_x(self.scm.as_raw());
+            } else {
+                guile_sys::scm_c_bitvector_clear_all_bits_x(self.scm.as_raw());
+            }
+        }
+    }
+
+    /// Number of bits that are set.
+    pub fn count(&self) -> usize {
+        unsafe { guile_sys::scm_c_bitvector_count(self.scm.as_raw()) }
+    }
+
+    pub fn iter(&self) -> ScmBitvectorIter<'vm> {
+        ScmBitvectorIter {
+            bitvector: *self,
+            idx: 0,
+        }
+    }
+
+    pub fn to_bools(&self) -> Vec<bool> {
+        self.iter().collect()
+    }
+}
+
+impl<'vm> IntoIterator for ScmBitvector<'vm> {
+    type Item = bool;
+    type IntoIter = ScmBitvectorIter<'vm>;
+
+    fn into_iter(self) -> ScmBitvectorIter<'vm> {
+        self.iter()
+    }
+}
+
+impl<'vm> IntoIterator for &ScmBitvector<'vm> {
+    type Item = bool;
+    type IntoIter = ScmBitvectorIter<'vm>;
+
+    fn into_iter(self) -> ScmBitvectorIter<'vm> {
+        self.iter()
+    }
+}
+
+pub struct ScmBitvectorIter<'vm> {
+    bitvector: ScmBitvector<'vm>,
+    idx: usize,
+}
+
+impl Iterator for ScmBitvectorIter<'_> {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<bool> {
+        let bit = self.bitvector.get(self.idx)?;
+        self.idx += 1;
+        Some(bit)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.bitvector.len().saturating_sub(self.idx);
+        (left, Some(left))
+    }
+}
+
+impl<'vm> From<ScmBitvector<'vm>> for Vec<bool> {
+    fn from(bv: ScmBitvector<'vm>) -> Vec<bool> {
+        bv.to_bools()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use guile_sys::SCM_BOOL_F;
+
+    #[test]
+    fn round_trip() {
+        crate::init(|vm| {
+            let bits = vec![true, false, false, true, true];
+            let bv = ScmBitvector::from_bools(&vm, &bits);
+            assert_eq!(bv.len(), 5);
+            assert_eq!(bv.count(), 3);
+            assert_eq!(bv.get(1), Some(false));
+            assert_eq!(bv.get(5), None);
+
+            bv.set(1, true);
+            assert_eq!(bv.count(), 4);
+            assert_eq!(Vec::from(bv), vec![true, true, false, true, true]);
+        });
+    }
+
+    #[test]
+    fn fill_and_iterate() {
+        crate::init(|vm| {
+            let bv = ScmBitvector::new(&vm, 4, false);
+            bv.fill(true);
+            assert_eq!(bv.count(), 4);
+            bv.set(2, false);
+            let set: Vec<usize> = bv
+                .iter()
+                .enumerate()
+                .filter(|&(_, bit)| bit)
+                .map(|(idx, _)| idx)
+                .collect();
+            assert_eq!(set, vec![0, 1, 3]);
+            bv.fill(false);
+            assert!(bv.into_iter().all(|bit| !bit));
+        });
+    }
+
+    #[test]
+    fn not_a_bitvector() {
+        crate::init(|vm| {
+            let bv = ScmBitvector::new(&vm, 3, true);
+            assert!(ScmBitvector::from_scm(bv.as_scm()).is_some());
+            let f = unsafe { Scm::from_raw(&vm, SCM_BOOL_F) };
+            assert!(ScmBitvector::from_scm(f).is_none());
+        });
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::cell::Cell;
+use std::ffi::CString;
+use std::path::PathBuf;
+
+use guile_sys::SCM;
+
+use crate::error::{protect, GuileError};
+use crate::{init, GuileVM, LocaleCategory, ToScm};
+
+/// How to set up the locale before the `init` closure runs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LocaleSetup {
+    /// Install the locale from the environment (`LANG`, `LC_ALL`, ...).
+    Environment,
+    /// Install the named locale, e.g. `"C"` or `"en_US.UTF-8"`.
+    Named(String),
+}
+
+/// Configures guile mode before handing control to a closure, as an
+/// alternative to calling `init` directly.
+#[derive(Clone, Debug, Default)]
+pub struct Builder {
+    program_arguments: Option<Vec<String>>,
+    auto_compile: Option<bool>,
+    load_paths: Vec<PathBuf>,
+    compiled_load_paths: Vec<PathBuf>,
+    locale: Option<LocaleSetup>,
+}
+
+unsafe fn variable(name: &str) -> SCM {
+    let name = CString::new(name).unwrap();
+    guile_sys::scm_c_lookup(name.as_ptr())
+}
+
+impl Builder {
+    pub fn new() -> Builder {
+        Builder::default()
+    }
+
+    /// What `(command-line)` returns, see `GuileVM::set_program_arguments`.
+    pub fn program_arguments<I, S>(mut self, args: I) -> Builder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.program_arguments = Some(args.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Whether loaded files are compiled automatically, overriding
+    /// `GUILE_AUTO_COMPILE`.
+    pub fn auto_compile(mut self, enabled: bool) -> Builder {
+        self.auto_compile = Some(enabled);
+        self
+    }
+
+    /// Add a directory to the front of `%load-path`. Directories added
+    /// later take precedence.
+    pub fn load_path<P: Into<PathBuf>>(mut self, path: P) -> Builder {
+        self.load_paths.push(path.into());
+        self
+    }
+
+    /// Add a directory to the front of `%load-compiled-path`.
+    pub fn compiled_load_path<P: Into<PathBuf>>(mut self, path: P) -> Builder {
+        self.compiled_load_paths.push(path.into());
+        self
+    }
+
+    pub fn locale(mut self, locale: LocaleSetup) -> Builder {
+        self.locale = Some(locale);
+        self
+    }
+
+    /// Enter guile mode, apply the configuration and call `func`. `func`
+    /// isn't called if the configuration can't be applied.
+    pub fn init<F>(self, func: F) -> Result<(), GuileError>
+    where
+        F: Fn(GuileVM),
+    {
+        let error = Cell::new(None);
+        init(|vm| match self.apply(&vm) {
+            Ok(()) => func(vm),
+            Err(e) => error.set(Some(e)),
+        });
+        match error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn apply(&self, vm: &GuileVM) -> Result<(), GuileError> {
+        if let Some(locale) = &self.locale {
+            let name = match locale {
+                LocaleSetup::Environment => "",
+                LocaleSetup::Named(name) => name,
+            };
+            vm.setlocale(LocaleCategory::All, name)?;
+        }
+        if let Some(args) = &self.program_arguments {
+            vm.set_program_arguments(args);
+        }
+        unsafe {
+            if let Some(enabled) = self.auto_compile {
+                protect(|| {
+                    guile_sys::scm_variable_set_x(
+                        variable("%load-should-auto-compile"),
+                        guile_sys::scm_from_bool(enabled),
+                    )
+                })?;
+            }
+            for (name, paths) in [
+                ("%load-path", &self.load_paths),
+                ("%load-compiled-path", &self.compiled_load_paths),
+            ] {
+                for path in paths {
+                    let path = path.to_scm(vm).as_raw();
+                    protect(|| {
+                        let var = variable(name);
+                        let old = guile_sys::scm_variable_ref(var);
+                        guile_sys::scm_variable_set_x(var, guile_sys::scm_cons(path, old))
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Language, ScmString};
+
+    #[test]
+    fn configure() {
+        Builder::new()
+            .program_arguments(["app", "script.scm"])
+            .auto_compile(false)
+            .load_path("/opt/app/scheme")
+            .locale(LocaleSetup::Named("C".to_string()))
+            .init(|vm| {
+                let first = vm
+                    .eval_language(Language::Scheme, "(car %load-path)")
+                    .unwrap();
+                assert_eq!(
+                    ScmString::from_scm(first).unwrap().to_string(),
+                    "/opt/app/scheme"
+                );
+                let args = vm
+                    .eval_language(Language::Scheme, "(cadr (command-line))")
+                    .unwrap();
+                assert_eq!(ScmString::from_scm(args).unwrap().to_string(), "script.scm");
+                let auto = vm
+                    .eval_language(Language::Scheme, "%load-should-auto-compile")
+                    .unwrap();
+                assert!(auto.is_false());
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn bad_locale() {
+        let result = Builder::new()
+            .locale(LocaleSetup::Named("no-such-locale".to_string()))
+            .init(|_| panic!("closure should not run"));
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use crate::array::{ArrayHandle, ArraySlice, ArraySliceMut};
+use crate::{GuileVM, Scm};
+
+/// A Scheme bytevector.
+#[derive(Clone, Copy)]
+pub struct ScmBytevector<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> ScmBytevector<'vm> {
+    /// Create a bytevector of `len` zero bytes.
+    pub fn new(vm: &'vm GuileVM, len: usize) -> ScmBytevector<'vm> {
+        let mut bv = unsafe {
+            ScmBytevector {
+                scm: Scm::from_raw(vm, guile_sys::scm_c_make_bytevector(len)),
+            }
+        };
+        /* Nothing else has seen the new bytevector yet. */
+        unsafe { bv.as_mut_slice() }.fill(0);
+        bv
+    }
+
+    pub fn from_bytes(vm: &'vm GuileVM, bytes: &[u8]) -> ScmBytevector<'vm> {
+        let mut bv = unsafe {
+            ScmBytevector {
+                scm: Scm::from_raw(vm, guile_sys::scm_c_make_bytevector(bytes.len())),
+            }
+        };
+        unsafe { bv.as_mut_slice() }.copy_from_slice(bytes);
+        bv
+    }
+
+    /// Returns `None` if `scm` is not a bytevector.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmBytevector<'vm>> {
+        if unsafe { guile_sys::scm_is_bytevector(scm.as_raw()) } != 0 {
+            Some(ScmBytevector { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    pub fn len(&self) -> usize {
+        unsafe { guile_sys::scm_c_bytevector_length(self.scm.as_raw()) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Borrow the contents in place.
+    pub fn as_slice(&self) -> ArraySlice<'_, u8> {
+        unsafe { ArraySlice::new(ArrayHandle::new(self.scm.as_raw()), self.len()) }
+    }
+
+    /// Borrow the contents in place for writing.
+    ///
+    /// # Safety
+    ///
+    /// Copies of this `ScmBytevector` share its storage, and the borrow
+    /// checker can't see them. While the guard is alive, nothing else may
+    /// read or write the contents: no `as_slice` or `as_mut_slice` borrow
+    /// through a copy, and no Scheme code that touches the bytevector.
+    pub unsafe fn as_mut_slice(&mut self) -> ArraySliceMut<'_, u8> {
+        ArraySliceMut::new(ArrayHandle::new(self.scm.as_raw()), self.len())
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.as_slice().to_vec()
+    }
+}
+
+impl<'vm> From<ScmBytevector<'vm>> for Vec<u8> {
+    fn from(bv: ScmBytevector<'vm>) -> Vec<u8> {
+        bv.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Language, ScmProcedure, ToScm};
+
+    #[test]
+    fn shared_contents() {
+        crate::init(|vm| {
+            let mut bv = ScmBytevector::from_bytes(&vm, b"guile");
+            assert_eq!(bv.len(), 5);
+            assert_eq!(&*bv.as_slice(), b"guile");
+
+            let mut contents = unsafe { bv.as_mut_slice() };
+            contents[0] = b'G';
+            drop(contents);
+            let first = vm
+                .eval_language(Language::Scheme, "(lambda (bv) (bytevector-u8-ref bv 0))")
+                .unwrap();
+            let first = ScmProcedure::from_scm(first).unwrap();
+            assert_eq!(first.call(&[bv.as_scm()]).unwrap().get::<u8>(), Some(b'G'));
+            assert_eq!(Vec::from(bv), b"Guile".to_vec());
+            assert_eq!(&*ScmBytevector::new(&vm, 3).as_slice(), &[0, 0, 0]);
+            assert!(ScmBytevector::new(&vm, 0).as_slice().is_empty());
+        });
+    }
+
+    #[test]
+    fn not_a_bytevector() {
+        crate::init(|vm| {
+            let scm = vm.eval_language(Language::Scheme, "#vu8(1 2 3)").unwrap();
+            assert_eq!(
+                ScmBytevector::from_scm(scm).unwrap().to_vec(),
+                vec![1, 2, 3]
+            );
+            assert!(ScmBytevector::from_scm(vec![1u8].to_scm(&vm)).is_none());
+        });
+    }
+}
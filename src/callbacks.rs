@@ -0,0 +1,267 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::error::Error;
+use std::fmt;
+
+use guile_sys::SCM;
+
+use crate::error::{protect, GuileError};
+use crate::scm::{list_from_raw, read_all, symbol};
+use crate::string;
+use crate::{FromScm, GuileVM, Scm, ScmProcedure, ToScm};
+
+/* Give the module a callback table and export procedures for Scheme
+ * code to fill it. Callback names may be strings or symbols. */
+const SETUP: &str = "
+(lambda (module-name)
+  (let ((module (resolve-module module-name #:ensure #t)))
+    (beautify-user-module! module)
+    (unless (module-local-variable module '%callbacks)
+      (let ((callbacks (make-hash-table)))
+        (define (key name)
+          (if (symbol? name) (symbol->string name) name))
+        (module-define! module '%callbacks callbacks)
+        (module-define! module 'register-callback!
+          (lambda (name proc)
+            (hash-set! callbacks (key name) proc)))
+        (module-define! module 'unregister-callback!
+          (lambda (name)
+            (hash-remove! callbacks (key name))))
+        (module-export! module '(register-callback! unregister-callback!))))))
+";
+
+const LOOKUP: &str = "
+(lambda (module-name name)
+  (hash-ref (module-ref (resolve-module module-name) '%callbacks) name #f))
+";
+
+const NAMES: &str = "
+(lambda (module-name)
+  (hash-map->list (lambda (name proc) name)
+                  (module-ref (resolve-module module-name) '%callbacks)))
+";
+
+unsafe fn eval_lambda(code: &str) -> SCM {
+    guile_sys::scm_primitive_eval(guile_sys::scm_car(read_all(code)))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CallbackError {
+    /// No callback is registered under this name.
+    NotRegistered(String),
+    /// The callback raised an error, or was called with the wrong number
+    /// of arguments.
+    Guile(GuileError),
+    /// The callback's return value can't be converted to the requested
+    /// type.
+    ReturnType { name: String },
+}
+
+impl fmt::Display for CallbackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CallbackError::NotRegistered(name) => write!(f, "no callback registered as {}", name),
+            CallbackError::Guile(err) => err.fmt(f),
+            CallbackError::ReturnType { name } => {
+                write!(f, "callback {} returned a value of the wrong type", name)
+            }
+        }
+    }
+}
+
+impl Error for CallbackError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CallbackError::Guile(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<GuileError> for CallbackError {
+    fn from(err: GuileError) -> CallbackError {
+        CallbackError::Guile(err)
+    }
+}
+
+/// Argument tuples that `CallbackRegistry::call` can pass to a callback.
+pub trait CallbackArgs {
+    /// The number of arguments.
+    const LEN: usize;
+
+    fn apply<'vm>(
+        &self,
+        vm: &'vm GuileVM,
+        procedure: ScmProcedure<'vm>,
+    ) -> Result<Scm<'vm>, GuileError>;
+}
+
+impl CallbackArgs for () {
+    const LEN: usize = 0;
+
+    fn apply<'vm>(
+        &self,
+        _vm: &'vm GuileVM,
+        procedure: ScmProcedure<'vm>,
+    ) -> Result<Scm<'vm>, GuileError> {
+        procedure.call(&[])
+    }
+}
+
+/* The converted arguments are kept in an array on the stack, where the
+ * GC can see them. */
+macro_rules! callback_args {
+    ($($ty:ident $var:ident),+) => {
+        impl<$($ty: ToScm),+> CallbackArgs for ($($ty,)+) {
+            const LEN: usize = [$(stringify!($var)),+].len();
+
+            fn apply<'vm>(
+                &self,
+                vm: &'vm GuileVM,
+                procedure: ScmProcedure<'vm>,
+            ) -> Result<Scm<'vm>, GuileError> {
+                let ($($var,)+) = self;
+                procedure.call(&[$($var.to_scm(vm)),+])
+            }
+        }
+    };
+}
+
+callback_args!(A a);
+callback_args!(A a, B b);
+callback_args!(A a, B b, C c);
+callback_args!(A a, B b, C c, D d);
+callback_args!(A a, B b, C c, D d, E e);
+callback_args!(A a, B b, C c, D d, E e, F f);
+
+/// Named callbacks registered by Scheme code, for Rust to invoke with
+/// typed arguments and results.
+///
+/// The registry lives in a module, which exports
+/// `(register-callback! name proc)` and `(unregister-callback! name)`.
+pub struct CallbackRegistry<'vm> {
+    vm: &'vm GuileVM,
+    module: String,
+}
+
+impl<'vm> CallbackRegistry<'vm> {
+    /// Set up a registry in `module`, given as space-separated components
+    /// like `"app events"`. The module is created if it doesn't exist,
+    /// and an existing registry in it is reused.
+    pub fn new(vm: &'vm GuileVM, module: &str) -> Result<CallbackRegistry<'vm>, GuileError> {
+        let registry = CallbackRegistry {
+            vm,
+            module: module.to_string(),
+        };
+        unsafe {
+            let module_name = registry.module_name();
+            protect(|| guile_sys::scm_call_1(eval_lambda(SETUP), module_name))?;
+        }
+        Ok(registry)
+    }
+
+    unsafe fn module_name(&self) -> SCM {
+        list_from_raw(self.module.split_whitespace().map(|c| symbol(c)))
+    }
+
+    fn lookup(&self, name: &str) -> Result<Option<ScmProcedure<'vm>>, GuileError> {
+        unsafe {
+            let module_name = self.module_name();
+            let key = string::from_str(name);
+            let proc_ = protect(|| guile_sys::scm_call_2(eval_lambda(LOOKUP), module_name, key))?;
+            Ok(ScmProcedure::from_scm(Scm::from_raw(self.vm, proc_)))
+        }
+    }
+
+    pub fn is_registered(&self, name: &str) -> bool {
+        matches!(self.lookup(name), Ok(Some(_)))
+    }
+
+    /// The names of the registered callbacks, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        unsafe {
+            let module_name = self.module_name();
+            if let Ok(mut list) = protect(|| guile_sys::scm_call_1(eval_lambda(NAMES), module_name))
+            {
+                while guile_sys::scm_is_pair(list) != 0 {
+                    names.push(string::to_string(guile_sys::scm_car(list)));
+                    list = guile_sys::scm_cdr(list);
+                }
+            }
+        }
+        names.sort();
+        names
+    }
+
+    /// Call the callback `name` with `args`, converting its result to `R`.
+    pub fn call<A, R>(&self, name: &str, args: A) -> Result<R, CallbackError>
+    where
+        A: CallbackArgs,
+        R: FromScm<'vm>,
+    {
+        let procedure = self
+            .lookup(name)?
+            .ok_or_else(|| CallbackError::NotRegistered(name.to_string()))?;
+        let result = args.apply(self.vm, procedure)?;
+        R::from_scm(result).ok_or_else(|| CallbackError::ReturnType {
+            name: name.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language};
+
+    #[test]
+    fn call_callbacks() {
+        init(|vm| {
+            let registry = CallbackRegistry::new(&vm, "guile-rs test callbacks").unwrap();
+            vm.eval_language(
+                Language::Scheme,
+                "(use-modules (guile-rs test callbacks))
+                 (register-callback! 'on-click
+                   (lambda (n label) (> n (string-length label))))
+                 (register-callback! \"on-close\" (lambda () 'closed))",
+            )
+            .unwrap();
+            assert_eq!(registry.names(), vec!["on-click", "on-close"]);
+
+            let clicked = registry.call::<(i32, String), bool>("on-click", (5, "abc".to_string()));
+            assert_eq!(clicked, Ok(true));
+            assert!(matches!(
+                registry.call::<(i32, String), String>("on-click", (5, "abc".to_string())),
+                Err(CallbackError::ReturnType { .. })
+            ));
+            assert!(matches!(
+                registry.call::<(i32,), bool>("on-click", (5,)),
+                Err(CallbackError::Guile(_))
+            ));
+            assert!(matches!(
+                registry.call::<(), bool>("on-open", ()),
+                Err(CallbackError::NotRegistered(_))
+            ));
+
+            vm.eval_language(Language::Scheme, "(unregister-callback! 'on-close)")
+                .unwrap();
+            assert!(!registry.is_registered("on-close"));
+        });
+    }
+}
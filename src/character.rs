@@ -0,0 +1,121 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::fmt;
+
+use crate::{GuileVM, Scm, ScmSymbol};
+
+/// A Scheme character. Guile characters are Unicode scalar values, the
+/// same set as Rust's `char`, so conversions either way are lossless.
+#[derive(Clone, Copy)]
+pub struct ScmChar<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> ScmChar<'vm> {
+    pub fn new(vm: &'vm GuileVM, c: char) -> ScmChar<'vm> {
+        unsafe {
+            ScmChar {
+                scm: Scm::from_raw(vm, guile_sys::SCM_MAKE_CHAR(c as u32)),
+            }
+        }
+    }
+
+    /// Returns `None` for surrogates and values past `U+10FFFF`, which
+    /// aren't characters in either language.
+    pub fn from_u32(vm: &'vm GuileVM, code: u32) -> Option<ScmChar<'vm>> {
+        char::from_u32(code).map(|c| ScmChar::new(vm, c))
+    }
+
+    /// Returns `None` if `scm` is not a character.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmChar<'vm>> {
+        if guile_sys::SCM_CHARP(scm.as_raw()) {
+            Some(ScmChar { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    pub fn to_char(&self) -> char {
+        char::from_u32(guile_sys::SCM_CHAR(self.scm.as_raw())).unwrap()
+    }
+
+    /// `char-upcase`, with Guile's (single-character) case mapping.
+    pub fn upcase(&self) -> ScmChar<'vm> {
+        ScmChar {
+            scm: self
+                .scm
+                .with_raw(unsafe { guile_sys::scm_char_upcase(self.scm.as_raw()) }),
+        }
+    }
+
+    /// `char-downcase`, with Guile's (single-character) case mapping.
+    pub fn downcase(&self) -> ScmChar<'vm> {
+        ScmChar {
+            scm: self
+                .scm
+                .with_raw(unsafe { guile_sys::scm_char_downcase(self.scm.as_raw()) }),
+        }
+    }
+
+    /// The Unicode general category, e.g. `Lu` or `Nd`.
+    pub fn general_category(&self) -> Option<String> {
+        let raw = unsafe { guile_sys::scm_char_general_category(self.scm.as_raw()) };
+        ScmSymbol::from_scm(self.scm.with_raw(raw)).map(|s| s.name())
+    }
+}
+
+impl<'vm> From<ScmChar<'vm>> for char {
+    fn from(c: ScmChar<'vm>) -> char {
+        c.to_char()
+    }
+}
+
+impl<'vm> fmt::Display for ScmChar<'vm> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language};
+
+    #[test]
+    fn unicode() {
+        init(|vm| {
+            let c = ScmChar::new(&vm, 'ß');
+            assert_eq!(char::from(c), 'ß');
+            assert_eq!(c.general_category().as_deref(), Some("Ll"));
+            assert_eq!(ScmChar::new(&vm, 'λ').upcase().to_char(), 'Λ');
+            assert_eq!(ScmChar::new(&vm, 'Q').downcase().to_string(), "q");
+
+            assert!(ScmChar::from_u32(&vm, 0xD800).is_none());
+            assert!(ScmChar::from_u32(&vm, 0x110000).is_none());
+            assert_eq!(ScmChar::from_u32(&vm, 0x1F600).unwrap().to_char(), '😀');
+
+            let scheme = vm.eval_language(Language::Scheme, "#\\x3bb").unwrap();
+            assert_eq!(ScmChar::from_scm(scheme).map(char::from), Some('λ'));
+            assert!(ScmChar::from_scm(vm.string_from_str("λ")).is_none());
+        });
+    }
+}
@@ -0,0 +1,191 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::any::Any;
+use std::ffi::CString;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, TryLockError};
+
+use guile_sys::{SCM, SCM_BOOL_F};
+use libc::c_void;
+
+use crate::error::GuileError;
+use crate::scm::{read_all, symbol};
+use crate::string;
+use crate::subr::error_args;
+use crate::{GuileVM, Scm, ScmListIter, ScmProcedure};
+
+/// A Rust callback behind a Scheme procedure. It gets the procedure's
+/// arguments as a list; an error is thrown with its key.
+pub(crate) type Callback = Box<dyn Fn(SCM) -> Result<SCM, GuileError> + Send>;
+
+const MAKE_CLOSURE: &str = "(lambda (trampoline data) (lambda args (trampoline data args)))";
+
+/// Make a Scheme procedure named `name` that calls `callback`.
+///
+/// The callback is boxed into a pointer object whose finalizer drops it
+/// once the procedure is garbage collected. Guile may run finalizers on
+/// another thread, hence `Send`.
+pub(crate) unsafe fn make_procedure(name: &str, callback: Callback) -> SCM {
+    let data = Box::into_raw(Box::new(callback));
+    let data = guile_sys::scm_from_pointer(data as *mut c_void, Some(drop_callback));
+    let trampoline_name = CString::new("rust-trampoline").unwrap();
+    let trampoline =
+        guile_sys::scm_c_make_gsubr(trampoline_name.as_ptr(), 2, 0, 0, trampoline as *mut c_void);
+    let make_closure = guile_sys::scm_primitive_eval(guile_sys::scm_car(read_all(MAKE_CLOSURE)));
+    let procedure = guile_sys::scm_call_2(make_closure, trampoline, data);
+    guile_sys::scm_set_procedure_property_x(procedure, symbol("name"), symbol(name));
+    procedure
+}
+
+unsafe extern "C" fn drop_callback(data: *mut c_void) {
+    drop(Box::from_raw(data as *mut Callback));
+}
+
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        String::from("Rust callback panicked")
+    }
+}
+
+/* Neither an error nor a panic can unwind into libguile, so they're
+ * turned into throws (a panic to `rust-panic`) once every Rust value in
+ * this frame has been dropped. */
+unsafe extern "C" fn trampoline(data: SCM, args: SCM) -> SCM {
+    let callback = &*(guile_sys::scm_to_pointer(data) as *const Callback);
+    let (key, throw_args) = match panic::catch_unwind(AssertUnwindSafe(|| callback(args))) {
+        Ok(Ok(result)) => return result,
+        Ok(Err(err)) => (symbol(err.key()), error_args(SCM_BOOL_F, &err.to_string())),
+        Err(payload) => (
+            symbol("rust-panic"),
+            guile_sys::scm_list_1(string::from_str(&panic_message(&*payload))),
+        ),
+    };
+    guile_sys::scm_throw(key, throw_args)
+}
+
+impl GuileVM {
+    /// A Scheme procedure named `name` that calls `f` with its arguments.
+    /// `f` can capture application state; it's dropped once the
+    /// procedure is garbage collected, possibly on another thread, and
+    /// may be called from several Guile threads at once. An `Err` is
+    /// thrown with its key, and a panic as `rust-panic`.
+    pub fn closure<F>(&self, name: &str, f: F) -> ScmProcedure<'_>
+    where
+        F: for<'vm> Fn(&'vm GuileVM, &[Scm<'vm>]) -> Result<Scm<'vm>, GuileError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        unsafe {
+            let raw = make_procedure(
+                name,
+                Box::new(move |args| {
+                    let vm = GuileVM::assume_guile_mode();
+                    let args: Vec<Scm> = ScmListIter::new(Scm::from_raw(&vm, args)).collect();
+                    f(&vm, &args).map(|result| result.as_raw())
+                }),
+            );
+            ScmProcedure::from_scm(Scm::from_raw(self, raw)).unwrap()
+        }
+    }
+
+    /// Like `closure`, for an `FnMut`. Calling the procedure while it's
+    /// already running, from Scheme code that `f` calls or from another
+    /// thread, throws a `misc-error` instead of waiting.
+    pub fn closure_mut<F>(&self, name: &str, f: F) -> ScmProcedure<'_>
+    where
+        F: for<'vm> FnMut(&'vm GuileVM, &[Scm<'vm>]) -> Result<Scm<'vm>, GuileError>
+            + Send
+            + 'static,
+    {
+        let f = Mutex::new(f);
+        self.closure(name, move |vm, args| {
+            let mut f = match f.try_lock() {
+                Ok(f) => f,
+                /* The panic that poisoned it was already thrown. */
+                Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+                Err(TryLockError::WouldBlock) => {
+                    return Err(GuileError::Throw {
+                        key: String::from("misc-error"),
+                        message: String::from("closure called again while it was running"),
+                    })
+                }
+            };
+            (*f)(vm, args)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language, ToScm};
+
+    #[test]
+    fn stateful_closure() {
+        init(|vm| {
+            let mut total = 0;
+            let add = vm.closure_mut("add!", move |vm, args| {
+                for arg in args {
+                    total += arg.get::<i32>().unwrap_or(0);
+                }
+                Ok(total.to_scm(vm))
+            });
+            assert_eq!(
+                add.call(&[1.to_scm(&vm), 2.to_scm(&vm)])
+                    .unwrap()
+                    .get::<i32>(),
+                Some(3)
+            );
+            assert_eq!(add.call(&[4.to_scm(&vm)]).unwrap().get::<i32>(), Some(7));
+        });
+    }
+
+    #[test]
+    fn errors_and_panics() {
+        init(|vm| {
+            let check = vm.closure("check", |vm, args| match args {
+                [arg] if arg.get::<i32>().is_some() => Ok(true.to_scm(vm)),
+                _ => Err(GuileError::Throw {
+                    key: String::from("wrong-type-arg"),
+                    message: String::from("expected one integer"),
+                }),
+            });
+            assert_eq!(
+                check.call(&[1.to_scm(&vm)]).unwrap().get::<bool>(),
+                Some(true)
+            );
+            let err = check.call(&[]).unwrap_err();
+            assert_eq!(err.key(), "wrong-type-arg");
+            assert!(err.to_string().contains("expected one integer"));
+
+            let recurse = vm.closure_mut("recurse", |vm, args| {
+                let code = "(lambda (f) (f f))";
+                let call_self = vm.eval_language(Language::Scheme, code)?;
+                call_self.call1(args[0])
+            });
+            let err = recurse.call(&[recurse.as_scm()]).unwrap_err();
+            assert_eq!(err.key(), "misc-error");
+            assert!(err.to_string().contains("called again"));
+        });
+    }
+}
@@ -0,0 +1,259 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::{BuildHasher, Hash};
+
+use guile_sys::{SCM, SCM_UNSPECIFIED};
+
+use crate::scm::list_from_raw;
+use crate::{FromScm, GuileVM, Scm, ScmHashTable, ToScm};
+
+/* Maps become association lists by default, the most common way to pass
+ * keyed data around in Scheme; wrap them in `HashTable` to get an
+ * `equal?` hash table instead. Sets become lists. */
+
+unsafe fn alist<'a, K, V, I>(vm: &GuileVM, entries: I) -> SCM
+where
+    K: ToScm + 'a,
+    V: ToScm + 'a,
+    I: Iterator<Item = (&'a K, &'a V)>,
+{
+    list_from_raw(entries.map(|(k, v)| {
+        let key = k.to_scm(vm).as_raw();
+        guile_sys::scm_cons(key, v.to_scm(vm).as_raw())
+    }))
+}
+
+/// Calls `f` with each entry of an alist or hash table. Fails if `scm`
+/// is neither, or `f` does.
+fn for_each_entry<'vm, F>(scm: Scm<'vm>, mut f: F) -> Option<()>
+where
+    F: FnMut(Scm<'vm>, Scm<'vm>) -> Option<()>,
+{
+    if let Some(table) = ScmHashTable::from_scm(scm) {
+        return table.iter().try_for_each(|(k, v)| f(k, v));
+    }
+    let mut rest = scm.as_raw();
+    unsafe {
+        while guile_sys::scm_is_pair(rest) != 0 {
+            let entry = guile_sys::scm_car(rest);
+            if guile_sys::scm_is_pair(entry) == 0 {
+                return None;
+            }
+            f(
+                scm.with_raw(guile_sys::scm_car(entry)),
+                scm.with_raw(guile_sys::scm_cdr(entry)),
+            )?;
+            rest = guile_sys::scm_cdr(rest);
+        }
+    }
+    guile_sys::scm_is_null(rest).then_some(())
+}
+
+impl<K: ToScm, V: ToScm, S> ToScm for HashMap<K, V, S> {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe { Scm::from_raw(vm, alist(vm, self.iter())) }
+    }
+}
+
+/// From an alist or a hash table. Later alist entries win, unlike
+/// `assoc`.
+impl<'vm, K, V, S> FromScm<'vm> for HashMap<K, V, S>
+where
+    K: FromScm<'vm> + Eq + Hash,
+    V: FromScm<'vm>,
+    S: BuildHasher + Default,
+{
+    fn from_scm(scm: Scm<'vm>) -> Option<HashMap<K, V, S>> {
+        let mut map = HashMap::default();
+        for_each_entry(scm, |k, v| {
+            map.insert(K::from_scm(k)?, V::from_scm(v)?);
+            Some(())
+        })?;
+        Some(map)
+    }
+}
+
+/// An alist in key order.
+impl<K: ToScm, V: ToScm> ToScm for BTreeMap<K, V> {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe { Scm::from_raw(vm, alist(vm, self.iter())) }
+    }
+}
+
+/// From an alist or a hash table. Later alist entries win, unlike
+/// `assoc`.
+impl<'vm, K: FromScm<'vm> + Ord, V: FromScm<'vm>> FromScm<'vm> for BTreeMap<K, V> {
+    fn from_scm(scm: Scm<'vm>) -> Option<BTreeMap<K, V>> {
+        let mut map = BTreeMap::new();
+        for_each_entry(scm, |k, v| {
+            map.insert(K::from_scm(k)?, V::from_scm(v)?);
+            Some(())
+        })?;
+        Some(map)
+    }
+}
+
+impl<T: ToScm, S> ToScm for HashSet<T, S> {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe {
+            Scm::from_raw(
+                vm,
+                list_from_raw(self.iter().map(|v| v.to_scm(vm).as_raw())),
+            )
+        }
+    }
+}
+
+/// From a list; duplicates are dropped.
+impl<'vm, T, S> FromScm<'vm> for HashSet<T, S>
+where
+    T: FromScm<'vm> + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn from_scm(scm: Scm<'vm>) -> Option<HashSet<T, S>> {
+        Vec::<T>::from_scm(scm).map(|items| items.into_iter().collect())
+    }
+}
+
+impl<T: ToScm> ToScm for BTreeSet<T> {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe {
+            Scm::from_raw(
+                vm,
+                list_from_raw(self.iter().map(|v| v.to_scm(vm).as_raw())),
+            )
+        }
+    }
+}
+
+/// From a list; duplicates are dropped.
+impl<'vm, T: FromScm<'vm> + Ord> FromScm<'vm> for BTreeSet<T> {
+    fn from_scm(scm: Scm<'vm>) -> Option<BTreeSet<T>> {
+        Vec::<T>::from_scm(scm).map(|items| items.into_iter().collect())
+    }
+}
+
+/// Converts a map to and from an `equal?` hash table, rather than an
+/// alist.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HashTable<M>(pub M);
+
+fn hash_table<'a, 'vm, K, V, I>(vm: &'vm GuileVM, entries: I) -> Scm<'vm>
+where
+    K: ToScm + 'a,
+    V: ToScm + 'a,
+    I: Iterator<Item = (&'a K, &'a V)>,
+{
+    let table = ScmHashTable::new(vm);
+    for (k, v) in entries {
+        let key = k.to_scm(vm);
+        table.insert(key, v.to_scm(vm));
+    }
+    table.as_scm()
+}
+
+impl<K: ToScm, V: ToScm, S> ToScm for HashTable<HashMap<K, V, S>> {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        hash_table(vm, self.0.iter())
+    }
+}
+
+impl<K: ToScm, V: ToScm> ToScm for HashTable<BTreeMap<K, V>> {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        hash_table(vm, self.0.iter())
+    }
+}
+
+impl<'vm, M: FromScm<'vm>> FromScm<'vm> for HashTable<M> {
+    fn from_scm(scm: Scm<'vm>) -> Option<HashTable<M>> {
+        ScmHashTable::from_scm(scm)?;
+        M::from_scm(scm).map(HashTable)
+    }
+}
+
+/// Converts a `Vec` to and from a Scheme vector, rather than a list.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Vector<T>(pub Vec<T>);
+
+impl<T: ToScm> ToScm for Vector<T> {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe {
+            let vector = guile_sys::scm_c_make_vector(self.0.len(), SCM_UNSPECIFIED);
+            for (idx, item) in self.0.iter().enumerate() {
+                guile_sys::scm_c_vector_set_x(vector, idx, item.to_scm(vm).as_raw());
+            }
+            Scm::from_raw(vm, vector)
+        }
+    }
+}
+
+impl<'vm, T: FromScm<'vm>> FromScm<'vm> for Vector<T> {
+    fn from_scm(scm: Scm<'vm>) -> Option<Vector<T>> {
+        let raw = scm.as_raw();
+        unsafe {
+            if guile_sys::scm_is_vector(raw) == 0 {
+                return None;
+            }
+            (0..guile_sys::scm_c_vector_length(raw))
+                .map(|idx| T::from_scm(scm.with_raw(guile_sys::scm_c_vector_ref(raw, idx))))
+                .collect::<Option<_>>()
+                .map(Vector)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language};
+
+    #[test]
+    fn maps_and_sets() {
+        init(|vm| {
+            let map = BTreeMap::from([("a".to_string(), 1u32), ("b".to_string(), 2)]);
+            let alist = map.to_scm(&vm);
+            assert_eq!(vm.write(alist), "((\"a\" . 1) (\"b\" . 2))");
+            assert_eq!(BTreeMap::from_scm(alist), Some(map.clone()));
+
+            let hashed: HashMap<String, u32> = map.clone().into_iter().collect();
+            let table = HashTable(hashed.clone()).to_scm(&vm);
+            assert!(ScmHashTable::from_scm(table).is_some());
+            assert_eq!(HashMap::from_scm(table), Some(hashed.clone()));
+            assert_eq!(HashTable::<HashMap<String, u32>>::from_scm(alist), None);
+            assert_eq!(HashTable::from_scm(table), Some(HashTable(hashed)));
+
+            let set = BTreeSet::from([3u8, 1, 2]);
+            assert_eq!(vm.write(set.to_scm(&vm)), "(1 2 3)");
+            let dupes = vm.eval_language(Language::Scheme, "'(1 1 2)").unwrap();
+            assert_eq!(HashSet::<u8>::from_scm(dupes), Some(HashSet::from([1, 2])));
+            assert_eq!(BTreeMap::<String, u32>::from_scm(dupes), None);
+        });
+    }
+
+    #[test]
+    fn vectors() {
+        init(|vm| {
+            let v = Vector(vec![1i32, 2, 3]).to_scm(&vm);
+            assert_eq!(vm.write(v), "#(1 2 3)");
+            assert_eq!(Vector::<i32>::from_scm(v), Some(Vector(vec![1, 2, 3])));
+            assert_eq!(Vec::<i32>::from_scm(v), None);
+            assert_eq!(Vector::<i32>::from_scm(vec![1i32].to_scm(&vm)), None);
+        });
+    }
+}
@@ -0,0 +1,168 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use crate::error::{protect, GuileError};
+use crate::scm::{keyword, public_ref, read_all, symbol};
+use crate::string;
+use crate::{GuileVM, Scm, ScmProcedure};
+
+/// Source languages known to Guile's compiler tower.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language<'a> {
+    Scheme,
+    /// Emacs Lisp.
+    Elisp,
+    /// ECMAScript. Only available in Guile versions that still ship
+    /// `(language ecmascript)`.
+    Ecmascript,
+    /// Any other language registered under this name, e.g. `"brainfuck"`.
+    Other(&'a str),
+}
+
+impl<'a> Language<'a> {
+    fn name(&self) -> &str {
+        match *self {
+            Language::Scheme => "scheme",
+            Language::Elisp => "elisp",
+            Language::Ecmascript => "ecmascript",
+            Language::Other(name) => name,
+        }
+    }
+}
+
+impl GuileVM {
+    /// Read all of `code` as `lang` and evaluate it in the current
+    /// module, using `read-and-compile` from `(system base compile)`.
+    pub fn eval_language(&self, lang: Language, code: &str) -> Result<Scm<'_>, GuileError> {
+        unsafe {
+            let result = protect(|| {
+                let port = guile_sys::scm_open_input_string(string::from_str(code));
+                let mut args = [
+                    port,
+                    keyword("from"),
+                    symbol(lang.name()),
+                    keyword("to"),
+                    symbol("value"),
+                ];
+                guile_sys::scm_call_n(
+                    public_ref("system base compile", "read-and-compile"),
+                    args.as_mut_ptr(),
+                    args.len(),
+                )
+            })?;
+            Ok(Scm::from_raw(self, result))
+        }
+    }
+}
+
+impl GuileVM {
+    /// Compile `code` once into a thunk that evaluates it in the current
+    /// module, so it can be run many times without being read and
+    /// expanded again.
+    pub fn compile(&self, code: &str) -> Result<ScmProcedure<'_>, GuileError> {
+        unsafe {
+            let thunk = protect(|| {
+                /* Compile to bytecode and load that, as `compile-file` and
+                 * `load` do, rather than wrapping the code in a lambda:
+                 * the loaded thunk runs it as top-level forms, so its
+                 * `define`s reach the module. */
+                let exp = guile_sys::scm_cons(guile_sys::scm_sym_begin, read_all(code));
+                let mut args = [
+                    exp,
+                    keyword("to"),
+                    symbol("bytecode"),
+                    keyword("env"),
+                    guile_sys::scm_current_module(),
+                ];
+                let bytecode = guile_sys::scm_call_n(
+                    public_ref("system base compile", "compile"),
+                    args.as_mut_ptr(),
+                    args.len(),
+                );
+                guile_sys::scm_call_1(
+                    public_ref("system vm loader", "load-thunk-from-memory"),
+                    bytecode,
+                )
+            })?;
+            Ok(ScmProcedure::from_scm(Scm::from_raw(self, thunk)).unwrap())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::init;
+
+    #[test]
+    fn scheme() {
+        init(|vm| {
+            let result = vm
+                .eval_language(Language::Scheme, "(define x 2) (* x 21)")
+                .unwrap();
+            assert_eq!(unsafe { guile_sys::scm_to_int64(result.as_raw()) }, 42);
+        });
+    }
+
+    #[test]
+    fn elisp() {
+        init(|vm| {
+            let result = vm.eval_language(Language::Elisp, "(+ 1 2)").unwrap();
+            assert_eq!(unsafe { guile_sys::scm_to_int64(result.as_raw()) }, 3);
+        });
+    }
+
+    #[test]
+    fn compile_once() {
+        init(|vm| {
+            vm.eval_language(Language::Scheme, "(define counter 0)")
+                .unwrap();
+            let thunk = vm.compile("(set! counter (+ counter 1)) counter").unwrap();
+            thunk.call(&[]).unwrap();
+            let result = thunk.call(&[]).unwrap();
+            assert_eq!(unsafe { guile_sys::scm_to_int64(result.as_raw()) }, 2);
+        });
+    }
+
+    #[test]
+    fn compile_define() {
+        init(|vm| {
+            let thunk = vm.compile("(define compiled-x 2)").unwrap();
+            thunk.call(&[]).unwrap();
+            let result = vm
+                .eval_language(Language::Scheme, "(* compiled-x 21)")
+                .unwrap();
+            assert_eq!(unsafe { guile_sys::scm_to_int64(result.as_raw()) }, 42);
+        });
+    }
+
+    #[test]
+    fn compile_error() {
+        init(|vm| {
+            assert!(vm.compile("(unbalanced").is_err());
+        });
+    }
+
+    #[test]
+    fn unknown_language() {
+        init(|vm| {
+            assert!(vm
+                .eval_language(Language::Other("no-such-lang"), "1")
+                .is_err());
+        });
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use crate::{FromScm, GuileVM, Scm, ToScm};
+
+/// A complex number with `f64` parts, as Guile stores non-real numbers.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Complex64 {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex64 {
+    pub fn new(re: f64, im: f64) -> Complex64 {
+        Complex64 { re, im }
+    }
+}
+
+/// Always an inexact number; Guile turns a zero imaginary part into a
+/// real.
+impl ToScm for Complex64 {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe { Scm::from_raw(vm, guile_sys::scm_c_make_rectangular(self.re, self.im)) }
+    }
+}
+
+/// Any number converts; reals get a zero imaginary part.
+impl<'vm> FromScm<'vm> for Complex64 {
+    fn from_scm(scm: Scm<'vm>) -> Option<Complex64> {
+        let raw = scm.as_raw();
+        unsafe {
+            if guile_sys::scm_is_complex(raw) != 0 {
+                Some(Complex64 {
+                    re: guile_sys::scm_c_real_part(raw),
+                    im: guile_sys::scm_c_imag_part(raw),
+                })
+            } else {
+                None
+            }
+        }
+    }
+}
+
+#[cfg(feature = "num")]
+impl From<num_complex::Complex64> for Complex64 {
+    fn from(z: num_complex::Complex64) -> Complex64 {
+        Complex64 { re: z.re, im: z.im }
+    }
+}
+
+#[cfg(feature = "num")]
+impl From<Complex64> for num_complex::Complex64 {
+    fn from(z: Complex64) -> num_complex::Complex64 {
+        num_complex::Complex64::new(z.re, z.im)
+    }
+}
+
+#[cfg(feature = "num")]
+impl ToScm for num_complex::Complex64 {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        Complex64::from(*self).to_scm(vm)
+    }
+}
+
+#[cfg(feature = "num")]
+impl<'vm> FromScm<'vm> for num_complex::Complex64 {
+    fn from_scm(scm: Scm<'vm>) -> Option<num_complex::Complex64> {
+        Complex64::from_scm(scm).map(From::from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language};
+
+    #[test]
+    fn rectangular() {
+        init(|vm| {
+            let z = vm.eval_language(Language::Scheme, "(sqrt -4)").unwrap();
+            assert_eq!(Complex64::from_scm(z), Some(Complex64::new(0.0, 2.0)));
+
+            let w = Complex64::new(1.5, -2.0).to_scm(&vm);
+            assert_eq!(vm.write(w), "1.5-2.0i");
+            assert_eq!(
+                Complex64::from_scm(3i32.to_scm(&vm)),
+                Some(Complex64::new(3.0, 0.0))
+            );
+            assert_eq!(Complex64::from_scm("3".to_scm(&vm)), None);
+        });
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+
+use crate::{from_scm, DeserializeError, GuileError, GuileVM, SandboxOptions};
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file couldn't be read.
+    Io(io::Error),
+    /// Evaluating the file raised an error or hit the sandbox's limits.
+    Guile(GuileError),
+    /// The file's value doesn't have the expected shape.
+    Deserialize(DeserializeError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => err.fmt(f),
+            ConfigError::Guile(err) => err.fmt(f),
+            ConfigError::Deserialize(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io(err) => Some(err),
+            ConfigError::Guile(err) => Some(err),
+            ConfigError::Deserialize(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> ConfigError {
+        ConfigError::Io(err)
+    }
+}
+
+impl From<GuileError> for ConfigError {
+    fn from(err: GuileError) -> ConfigError {
+        ConfigError::Guile(err)
+    }
+}
+
+impl From<DeserializeError> for ConfigError {
+    fn from(err: DeserializeError) -> ConfigError {
+        ConfigError::Deserialize(err)
+    }
+}
+
+/// Load the configuration file at `path`. It's evaluated in a sandbox
+/// with only the pure bindings, and the value of its last expression is
+/// deserialized with `from_scm`, e.g.
+///
+/// ```scheme
+/// (define base-port 8000)
+/// `((name . "server")
+///   (ports ,base-port ,(+ base-port 1)))
+/// ```
+pub fn load<T: DeserializeOwned, P: AsRef<Path>>(vm: &GuileVM, path: P) -> Result<T, ConfigError> {
+    load_with(vm, path, &SandboxOptions::new())
+}
+
+/// Load the configuration file at `path`, evaluating it with `options`.
+pub fn load_with<T: DeserializeOwned, P: AsRef<Path>>(
+    vm: &GuileVM,
+    path: P,
+    options: &SandboxOptions,
+) -> Result<T, ConfigError> {
+    let code = fs::read_to_string(path)?;
+    let value = vm.eval_sandboxed(&code, options)?;
+    Ok(from_scm(value)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::init;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Server {
+        name: String,
+        ports: Vec<u16>,
+    }
+
+    #[test]
+    fn load_config() {
+        let path = std::env::temp_dir().join(format!("guile-rs-config-{}.scm", std::process::id()));
+        fs::write(
+            &path,
+            "(define base-port 8000)
+             `((name . \"server\") (ports ,base-port ,(+ base-port 1)))",
+        )
+        .unwrap();
+        init(|vm| {
+            let server: Server = load(&vm, &path).unwrap();
+            assert_eq!(
+                server,
+                Server {
+                    name: "server".to_string(),
+                    ports: vec![8000, 8001],
+                }
+            );
+            assert!(matches!(
+                load::<Vec<String>, _>(&vm, &path),
+                Err(ConfigError::Deserialize(_))
+            ));
+        });
+        fs::write(&path, "(open-input-file \"/etc/passwd\")").unwrap();
+        init(|vm| {
+            assert!(matches!(
+                load::<String, _>(&vm, &path),
+                Err(ConfigError::Guile(_))
+            ));
+        });
+        fs::remove_file(path).unwrap();
+    }
+}
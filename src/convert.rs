@@ -0,0 +1,1014 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::error::Error;
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use guile_sys::{SCM, SCM_UNDEFINED, SCM_UNSPECIFIED};
+
+use crate::scm::{list_from_raw, symbol};
+use crate::string::{self, Encoding, InvalidSequence};
+use crate::{GuileVM, Scm, ScmString};
+
+/// Types that can be converted into a Scheme value.
+pub trait ToScm {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm>;
+}
+
+/// Types that can be extracted from a Scheme value.
+pub trait FromScm<'vm>: Sized {
+    /// Returns `None` if `scm` has the wrong type or can't be
+    /// represented as `Self`.
+    fn from_scm(scm: Scm<'vm>) -> Option<Self>;
+}
+
+/* For wrappers whose `Scm<'vm>` already proves we're in guile mode, so
+ * arguments can be converted without the caller passing the VM along. */
+pub(crate) fn to_raw(value: &(impl ToScm + ?Sized)) -> SCM {
+    let vm = unsafe { GuileVM::assume_guile_mode() };
+    value.to_scm(&vm).as_raw()
+}
+
+impl<T: ToScm + ?Sized> ToScm for &T {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        (**self).to_scm(vm)
+    }
+}
+
+impl<'a> ToScm for Scm<'a> {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe { Scm::from_raw(vm, self.as_raw()) }
+    }
+}
+
+impl<'vm> FromScm<'vm> for Scm<'vm> {
+    fn from_scm(scm: Scm<'vm>) -> Option<Scm<'vm>> {
+        Some(scm)
+    }
+}
+
+impl ToScm for bool {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe { Scm::from_raw(vm, guile_sys::scm_from_bool(*self)) }
+    }
+}
+
+/// Only `#t` and `#f` convert; use `Scm::is_true` for Scheme truthiness.
+impl<'vm> FromScm<'vm> for bool {
+    fn from_scm(scm: Scm<'vm>) -> Option<bool> {
+        if unsafe { guile_sys::scm_is_bool(scm.as_raw()) } != 0 {
+            Some(scm.is_true())
+        } else {
+            None
+        }
+    }
+}
+
+macro_rules! signed_conversions {
+    ($($ty:ty),*) => {$(
+        impl ToScm for $ty {
+            fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+                unsafe { Scm::from_raw(vm, guile_sys::scm_from_int64(*self as i64)) }
+            }
+        }
+
+        impl<'vm> FromScm<'vm> for $ty {
+            fn from_scm(scm: Scm<'vm>) -> Option<$ty> {
+                unsafe {
+                    let raw = scm.as_raw();
+                    if guile_sys::scm_is_signed_integer(raw, <$ty>::MIN as i64, <$ty>::MAX as i64) != 0 {
+                        Some(guile_sys::scm_to_int64(raw) as $ty)
+                    } else {
+                        None
+                    }
+                }
+            }
+        }
+    )*};
+}
+
+macro_rules! unsigned_conversions {
+    ($($ty:ty),*) => {$(
+        impl ToScm for $ty {
+            fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+                unsafe { Scm::from_raw(vm, guile_sys::scm_from_uint64(*self as u64)) }
+            }
+        }
+
+        impl<'vm> FromScm<'vm> for $ty {
+            fn from_scm(scm: Scm<'vm>) -> Option<$ty> {
+                unsafe {
+                    let raw = scm.as_raw();
+                    if guile_sys::scm_is_unsigned_integer(raw, 0, <$ty>::MAX as u64) != 0 {
+                        Some(guile_sys::scm_to_uint64(raw) as $ty)
+                    } else {
+                        None
+                    }
+                }
+            }
+        }
+    )*};
+}
+
+signed_conversions!(i8, i16, i32, i64, isize);
+unsigned_conversions!(u8, u16, u32, u64, usize);
+
+/* libguile has no 128-bit conversions, so these go through two 64-bit
+ * halves and let Guile's bignums do the arithmetic. */
+
+unsafe fn from_halves(hi: SCM, lo: u64) -> SCM {
+    let shifted = guile_sys::scm_ash(hi, guile_sys::scm_from_int64(64));
+    guile_sys::scm_sum(shifted, guile_sys::scm_from_uint64(lo))
+}
+
+/// The low 64 bits and the rest, if `raw` is an exact integer of at most
+/// `bits` bits (not counting the sign).
+unsafe fn to_halves(raw: SCM, bits: u64) -> Option<(SCM, u64)> {
+    if guile_sys::scm_is_exact_integer(raw) == 0
+        || guile_sys::scm_to_uint64(guile_sys::scm_integer_length(raw)) > bits
+    {
+        return None;
+    }
+    let lo = guile_sys::scm_logand(raw, guile_sys::scm_from_uint64(u64::MAX));
+    let hi = guile_sys::scm_ash(raw, guile_sys::scm_from_int64(-64));
+    Some((hi, guile_sys::scm_to_uint64(lo)))
+}
+
+impl ToScm for i128 {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe {
+            let hi = guile_sys::scm_from_int64((*self >> 64) as i64);
+            Scm::from_raw(vm, from_halves(hi, *self as u64))
+        }
+    }
+}
+
+impl<'vm> FromScm<'vm> for i128 {
+    fn from_scm(scm: Scm<'vm>) -> Option<i128> {
+        unsafe {
+            let (hi, lo) = to_halves(scm.as_raw(), 127)?;
+            Some(((guile_sys::scm_to_int64(hi) as i128) << 64) | lo as i128)
+        }
+    }
+}
+
+impl ToScm for u128 {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe {
+            let hi = guile_sys::scm_from_uint64((*self >> 64) as u64);
+            Scm::from_raw(vm, from_halves(hi, *self as u64))
+        }
+    }
+}
+
+impl<'vm> FromScm<'vm> for u128 {
+    fn from_scm(scm: Scm<'vm>) -> Option<u128> {
+        unsafe {
+            /* `negative?` throws on a non-number, so check that it's an
+             * exact integer first. */
+            let (hi, lo) = to_halves(scm.as_raw(), 128)?;
+            if guile_sys::scm_is_true(guile_sys::scm_negative_p(scm.as_raw())) {
+                return None;
+            }
+            Some(((guile_sys::scm_to_uint64(hi) as u128) << 64) | lo as u128)
+        }
+    }
+}
+
+impl ToScm for f64 {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe { Scm::from_raw(vm, guile_sys::scm_from_double(*self)) }
+    }
+}
+
+/// Any real number converts. Exact numbers are rounded to the nearest
+/// `f64`, and ones too large for it become infinite; use `Lossless` to
+/// reject those instead. `+inf.0`, `-inf.0` and `+nan.0` map to the
+/// corresponding `f64` values, and back.
+impl<'vm> FromScm<'vm> for f64 {
+    fn from_scm(scm: Scm<'vm>) -> Option<f64> {
+        unsafe {
+            if guile_sys::scm_is_real(scm.as_raw()) != 0 {
+                Some(guile_sys::scm_to_double(scm.as_raw()))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+impl ToScm for f32 {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        f64::from(*self).to_scm(vm)
+    }
+}
+
+impl<'vm> FromScm<'vm> for f32 {
+    fn from_scm(scm: Scm<'vm>) -> Option<f32> {
+        f64::from_scm(scm).map(|x| x as f32)
+    }
+}
+
+impl ToScm for char {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe { Scm::from_raw(vm, guile_sys::SCM_MAKE_CHAR(*self as u32)) }
+    }
+}
+
+impl<'vm> FromScm<'vm> for char {
+    fn from_scm(scm: Scm<'vm>) -> Option<char> {
+        let raw = scm.as_raw();
+        if guile_sys::SCM_CHARP(raw) {
+            char::from_u32(guile_sys::SCM_CHAR(raw))
+        } else {
+            None
+        }
+    }
+}
+
+impl ToScm for str {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        ScmString::new(vm, self).as_scm()
+    }
+}
+
+impl ToScm for String {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        self.as_str().to_scm(vm)
+    }
+}
+
+impl<'vm> FromScm<'vm> for String {
+    fn from_scm(scm: Scm<'vm>) -> Option<String> {
+        scm.string_value()
+    }
+}
+
+impl ToScm for () {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe { Scm::from_raw(vm, SCM_UNSPECIFIED) }
+    }
+}
+
+/// `None` is `#f`, so `Some(false)` doesn't round-trip; use `Maybe` if it
+/// needs to.
+impl<T: ToScm> ToScm for Option<T> {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        match self {
+            Some(value) => value.to_scm(vm),
+            None => false.to_scm(vm),
+        }
+    }
+}
+
+impl<'vm, T: FromScm<'vm>> FromScm<'vm> for Option<T> {
+    fn from_scm(scm: Scm<'vm>) -> Option<Option<T>> {
+        if scm.is_false() {
+            Some(None)
+        } else {
+            T::from_scm(scm).map(Some)
+        }
+    }
+}
+
+/// An `Option` that encodes as `(just x)` or `nothing`, for when `#f` is
+/// a legitimate value and the plain `Option` mapping would be ambiguous.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Maybe<T>(pub Option<T>);
+
+impl<T: ToScm> ToScm for Maybe<T> {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe {
+            let raw = match &self.0 {
+                Some(value) => list_from_raw([symbol("just"), value.to_scm(vm).as_raw()]),
+                None => symbol("nothing"),
+            };
+            Scm::from_raw(vm, raw)
+        }
+    }
+}
+
+impl<'vm, T: FromScm<'vm>> FromScm<'vm> for Maybe<T> {
+    fn from_scm(scm: Scm<'vm>) -> Option<Maybe<T>> {
+        let raw = scm.as_raw();
+        unsafe {
+            if guile_sys::scm_is_eq(raw, symbol("nothing")) {
+                return Some(Maybe(None));
+            }
+            if guile_sys::scm_is_pair(raw) == 0
+                || !guile_sys::scm_is_eq(guile_sys::scm_car(raw), symbol("just"))
+            {
+                return None;
+            }
+            let rest = guile_sys::scm_cdr(raw);
+            if guile_sys::scm_is_pair(rest) == 0
+                || !guile_sys::scm_is_null(guile_sys::scm_cdr(rest))
+            {
+                return None;
+            }
+            T::from_scm(scm.with_raw(guile_sys::scm_car(rest))).map(|value| Maybe(Some(value)))
+        }
+    }
+}
+
+impl<T> From<Option<T>> for Maybe<T> {
+    fn from(value: Option<T>) -> Maybe<T> {
+        Maybe(value)
+    }
+}
+
+impl<T> From<Maybe<T>> for Option<T> {
+    fn from(value: Maybe<T>) -> Option<T> {
+        value.0
+    }
+}
+
+/// `(ok . value)` or `(error . value)`, so procedures can return errors
+/// as data rather than throwing.
+impl<T: ToScm, E: ToScm> ToScm for Result<T, E> {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        let (tag, value) = match self {
+            Ok(value) => ("ok", value.to_scm(vm)),
+            Err(error) => ("error", error.to_scm(vm)),
+        };
+        unsafe { Scm::from_raw(vm, guile_sys::scm_cons(symbol(tag), value.as_raw())) }
+    }
+}
+
+impl<'vm, T: FromScm<'vm>, E: FromScm<'vm>> FromScm<'vm> for Result<T, E> {
+    fn from_scm(scm: Scm<'vm>) -> Option<Result<T, E>> {
+        let raw = scm.as_raw();
+        unsafe {
+            if guile_sys::scm_is_pair(raw) == 0 {
+                return None;
+            }
+            let tag = guile_sys::scm_car(raw);
+            let value = scm.with_raw(guile_sys::scm_cdr(raw));
+            if guile_sys::scm_is_eq(tag, symbol("ok")) {
+                T::from_scm(value).map(Ok)
+            } else if guile_sys::scm_is_eq(tag, symbol("error")) {
+                E::from_scm(value).map(Err)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/* Tuples are Scheme multiple values: `(values a b c)` converts to
+ * `(A, B, C)`, and back. The converted elements are kept in an array on
+ * the stack while the values object is built. */
+macro_rules! tuple_values {
+    ($($ty:ident $var:ident $idx:tt),+) => {
+        impl<$($ty: ToScm),+> ToScm for ($($ty,)+) {
+            fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+                let ($($var,)+) = self;
+                let mut values = [$($var.to_scm(vm).as_raw()),+];
+                unsafe {
+                    Scm::from_raw(vm, guile_sys::scm_c_values(values.as_mut_ptr(), values.len()))
+                }
+            }
+        }
+
+        /// Fails unless there are exactly as many values as elements.
+        impl<'vm, $($ty: FromScm<'vm>),+> FromScm<'vm> for ($($ty,)+) {
+            fn from_scm(scm: Scm<'vm>) -> Option<($($ty,)+)> {
+                let raw = scm.as_raw();
+                unsafe {
+                    if guile_sys::scm_c_nvalues(raw) != [$($idx),+].len() {
+                        return None;
+                    }
+                    Some(($($ty::from_scm(scm.with_raw(guile_sys::scm_c_value_ref(raw, $idx)))?,)+))
+                }
+            }
+        }
+    };
+}
+
+tuple_values!(A a 0);
+tuple_values!(A a 0, B b 1);
+tuple_values!(A a 0, B b 1, C c 2);
+tuple_values!(A a 0, B b 1, C c 2, D d 3);
+tuple_values!(A a 0, B b 1, C c 2, D d 3, E e 4);
+tuple_values!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5);
+tuple_values!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6);
+tuple_values!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7);
+tuple_values!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8);
+tuple_values!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9);
+tuple_values!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9, K k 10);
+tuple_values!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9, K k 10, L l 11);
+
+/// Slices and `Vec`s become proper lists.
+impl<T: ToScm> ToScm for [T] {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe {
+            Scm::from_raw(
+                vm,
+                list_from_raw(self.iter().map(|v| v.to_scm(vm).as_raw())),
+            )
+        }
+    }
+}
+
+impl<T: ToScm> ToScm for Vec<T> {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        self.as_slice().to_scm(vm)
+    }
+}
+
+/// Fails unless `scm` is a proper list whose elements all convert. The
+/// elements stay reachable through `scm`, so a `Vec<Scm>` is safe for as
+/// long as `scm` is.
+impl<'vm, T: FromScm<'vm>> FromScm<'vm> for Vec<T> {
+    fn from_scm(scm: Scm<'vm>) -> Option<Vec<T>> {
+        let mut items = Vec::new();
+        let mut rest = scm.as_raw();
+        unsafe {
+            while guile_sys::scm_is_pair(rest) != 0 {
+                items.push(T::from_scm(scm.with_raw(guile_sys::scm_car(rest)))?);
+                rest = guile_sys::scm_cdr(rest);
+            }
+        }
+        if guile_sys::scm_is_null(rest) {
+            Some(items)
+        } else {
+            None
+        }
+    }
+}
+
+/* Filenames and C strings are converted with the locale encoding, which
+ * is what libguile itself uses for them (e.g. in `open-file`), so the
+ * bytes Scheme ends up passing to the OS are the bytes we started with.
+ * Guile installs the locale from the environment when it boots. Bytes
+ * the locale can't decode are substituted, since Guile strings have no
+ * way to carry them; going the other way, characters the locale can't
+ * encode make the conversion fail. Call `setlocale` (or use `Builder`)
+ * with a UTF-8 locale so non-ASCII names survive. */
+
+fn bytes_to_scm<'vm>(vm: &'vm GuileVM, bytes: &[u8]) -> Scm<'vm> {
+    ScmString::decode(vm, bytes, Encoding::Locale, InvalidSequence::Escape)
+        .expect("locale decoding with substitution failed")
+        .as_scm()
+}
+
+fn bytes_from_scm(scm: Scm) -> Option<Vec<u8>> {
+    ScmString::from_scm(scm)?
+        .encode(Encoding::Locale, InvalidSequence::Error)
+        .ok()
+}
+
+#[cfg(unix)]
+fn os_str_to_bytes(s: &OsStr) -> &[u8] {
+    use std::os::unix::ffi::OsStrExt;
+    s.as_bytes()
+}
+
+#[cfg(unix)]
+fn os_string_from_bytes(bytes: Vec<u8>) -> Option<OsString> {
+    use std::os::unix::ffi::OsStringExt;
+    Some(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn os_str_to_bytes(s: &OsStr) -> &[u8] {
+    s.to_str().unwrap_or("\u{fffd}").as_bytes()
+}
+
+#[cfg(not(unix))]
+fn os_string_from_bytes(bytes: Vec<u8>) -> Option<OsString> {
+    String::from_utf8(bytes).ok().map(OsString::from)
+}
+
+impl ToScm for OsStr {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        bytes_to_scm(vm, os_str_to_bytes(self))
+    }
+}
+
+impl ToScm for OsString {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        self.as_os_str().to_scm(vm)
+    }
+}
+
+impl<'vm> FromScm<'vm> for OsString {
+    fn from_scm(scm: Scm<'vm>) -> Option<OsString> {
+        os_string_from_bytes(bytes_from_scm(scm)?)
+    }
+}
+
+impl ToScm for Path {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        self.as_os_str().to_scm(vm)
+    }
+}
+
+impl ToScm for PathBuf {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        self.as_os_str().to_scm(vm)
+    }
+}
+
+impl<'vm> FromScm<'vm> for PathBuf {
+    fn from_scm(scm: Scm<'vm>) -> Option<PathBuf> {
+        OsString::from_scm(scm).map(PathBuf::from)
+    }
+}
+
+impl ToScm for CStr {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        bytes_to_scm(vm, self.to_bytes())
+    }
+}
+
+impl ToScm for CString {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        self.as_c_str().to_scm(vm)
+    }
+}
+
+/// Fails if the string contains a NUL character.
+impl<'vm> FromScm<'vm> for CString {
+    fn from_scm(scm: Scm<'vm>) -> Option<CString> {
+        CString::new(bytes_from_scm(scm)?).ok()
+    }
+}
+
+/// Converts C strings with Latin-1 rather than the locale encoding, for
+/// C libraries that deal in raw bytes: every byte maps to one character,
+/// so any C string round-trips.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Latin1<T>(pub T);
+
+impl<T: AsRef<CStr>> ToScm for Latin1<T> {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        ScmString::from_latin1(vm, self.0.as_ref().to_bytes()).as_scm()
+    }
+}
+
+/// Fails if the string contains a NUL or a character past `U+00FF`.
+impl<'vm> FromScm<'vm> for Latin1<CString> {
+    fn from_scm(scm: Scm<'vm>) -> Option<Latin1<CString>> {
+        let bytes = ScmString::from_scm(scm)?
+            .to_latin1(InvalidSequence::Error)
+            .ok()?;
+        CString::new(bytes).ok().map(Latin1)
+    }
+}
+
+/// Why a `TryFromScm` conversion failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConversionError {
+    /// The value isn't of the expected Scheme type.
+    WrongType {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// An exact integer that doesn't fit the Rust type.
+    OutOfRange { value: String, target: &'static str },
+    /// An inexact number where an exact integer was required, e.g. `3.0`.
+    NotExact,
+    /// An exact number that a float can't represent, e.g. `1/3`.
+    LossOfPrecision,
+    /// A string with characters the locale encoding can't represent, so
+    /// it can't become a file name or C string.
+    Unencodable,
+    /// A string with a NUL character, which a C string can't hold.
+    InteriorNul,
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConversionError::WrongType { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+            ConversionError::OutOfRange { value, target } => {
+                write!(f, "{} is out of range for {}", value, target)
+            }
+            ConversionError::NotExact => write!(f, "expected an exact integer"),
+            ConversionError::LossOfPrecision => write!(f, "number can't be represented exactly"),
+            ConversionError::Unencodable => write!(f, "string can't be encoded in the locale"),
+            ConversionError::InteriorNul => write!(f, "string contains a NUL character"),
+        }
+    }
+}
+
+impl Error for ConversionError {}
+
+/// Like `FromScm`, but says why the conversion failed.
+pub trait TryFromScm<'vm>: Sized {
+    fn try_from_scm(scm: Scm<'vm>) -> Result<Self, ConversionError>;
+}
+
+pub(crate) fn wrong_type(expected: &'static str, scm: Scm) -> ConversionError {
+    ConversionError::WrongType {
+        expected,
+        found: scm.type_name(),
+    }
+}
+
+/// Checks shared by every integer type: an exact integer, or else the
+/// reason it isn't one.
+fn check_integer(scm: Scm) -> Result<(), ConversionError> {
+    let raw = scm.as_raw();
+    unsafe {
+        if guile_sys::scm_is_exact_integer(raw) != 0 {
+            Ok(())
+        } else if guile_sys::scm_is_integer(raw) != 0 {
+            Err(ConversionError::NotExact)
+        } else {
+            Err(wrong_type("exact integer", scm))
+        }
+    }
+}
+
+fn out_of_range(scm: Scm, target: &'static str) -> ConversionError {
+    let value =
+        unsafe { string::to_string(guile_sys::scm_number_to_string(scm.as_raw(), SCM_UNDEFINED)) };
+    ConversionError::OutOfRange { value, target }
+}
+
+macro_rules! try_integer_conversions {
+    ($($ty:ty),*) => {$(
+        impl<'vm> TryFromScm<'vm> for $ty {
+            fn try_from_scm(scm: Scm<'vm>) -> Result<$ty, ConversionError> {
+                check_integer(scm)?;
+                <$ty>::from_scm(scm).ok_or_else(|| out_of_range(scm, stringify!($ty)))
+            }
+        }
+    )*};
+}
+
+try_integer_conversions!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+macro_rules! try_simple_conversions {
+    ($($ty:ty => $expected:expr),*) => {$(
+        impl<'vm> TryFromScm<'vm> for $ty {
+            fn try_from_scm(scm: Scm<'vm>) -> Result<$ty, ConversionError> {
+                <$ty>::from_scm(scm).ok_or_else(|| wrong_type($expected, scm))
+            }
+        }
+    )*};
+}
+
+try_simple_conversions!(
+    bool => "boolean",
+    f64 => "real",
+    f32 => "real",
+    char => "character",
+    String => "string"
+);
+
+/// Opts a float conversion into failing with `LossOfPrecision` rather
+/// than rounding an exact number, e.g. `Lossless::<f64>::try_from_scm`.
+/// Inexact numbers always convert to `f64`, and to `f32` when they fit
+/// it exactly (or are infinite or NaN).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lossless<T>(pub T);
+
+impl<'vm> TryFromScm<'vm> for Lossless<f64> {
+    fn try_from_scm(scm: Scm<'vm>) -> Result<Lossless<f64>, ConversionError> {
+        let x = f64::try_from_scm(scm)?;
+        let raw = scm.as_raw();
+        unsafe {
+            if guile_sys::scm_is_exact(raw) == 0 {
+                return Ok(Lossless(x));
+            }
+            if x.is_finite() {
+                let back = guile_sys::scm_inexact_to_exact(guile_sys::scm_from_double(x));
+                if guile_sys::scm_is_true(guile_sys::scm_num_eq_p(back, raw)) {
+                    return Ok(Lossless(x));
+                }
+            }
+        }
+        Err(ConversionError::LossOfPrecision)
+    }
+}
+
+impl<'vm> TryFromScm<'vm> for Lossless<f32> {
+    fn try_from_scm(scm: Scm<'vm>) -> Result<Lossless<f32>, ConversionError> {
+        let Lossless(x) = Lossless::<f64>::try_from_scm(scm)?;
+        let narrowed = x as f32;
+        if f64::from(narrowed) == x || !x.is_finite() {
+            Ok(Lossless(narrowed))
+        } else {
+            Err(ConversionError::LossOfPrecision)
+        }
+    }
+}
+
+impl<'vm> TryFromScm<'vm> for OsString {
+    fn try_from_scm(scm: Scm<'vm>) -> Result<OsString, ConversionError> {
+        ScmString::from_scm(scm).ok_or_else(|| wrong_type("string", scm))?;
+        OsString::from_scm(scm).ok_or(ConversionError::Unencodable)
+    }
+}
+
+impl<'vm> TryFromScm<'vm> for PathBuf {
+    fn try_from_scm(scm: Scm<'vm>) -> Result<PathBuf, ConversionError> {
+        OsString::try_from_scm(scm).map(PathBuf::from)
+    }
+}
+
+impl<'vm> TryFromScm<'vm> for CString {
+    fn try_from_scm(scm: Scm<'vm>) -> Result<CString, ConversionError> {
+        ScmString::from_scm(scm).ok_or_else(|| wrong_type("string", scm))?;
+        let bytes = bytes_from_scm(scm).ok_or(ConversionError::Unencodable)?;
+        CString::new(bytes).map_err(|_| ConversionError::InteriorNul)
+    }
+}
+
+impl<'vm> TryFromScm<'vm> for Scm<'vm> {
+    fn try_from_scm(scm: Scm<'vm>) -> Result<Scm<'vm>, ConversionError> {
+        Ok(scm)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn primitives_round_trip() {
+        crate::init(|vm| {
+            assert_eq!(bool::from_scm(true.to_scm(&vm)), Some(true));
+            assert_eq!(i8::from_scm((-5i64).to_scm(&vm)), Some(-5));
+            assert_eq!(u8::from_scm(256u32.to_scm(&vm)), None);
+            assert_eq!(u64::from_scm((-1i32).to_scm(&vm)), None);
+            assert_eq!(u64::from_scm(u64::MAX.to_scm(&vm)), Some(u64::MAX));
+            assert_eq!(f64::from_scm(3i32.to_scm(&vm)), Some(3.0));
+            assert_eq!(char::from_scm('λ'.to_scm(&vm)), Some('λ'));
+            assert_eq!(String::from_scm("λx".to_scm(&vm)).as_deref(), Some("λx"));
+            assert_eq!(i32::from_scm("1".to_scm(&vm)), None);
+        });
+    }
+
+    #[test]
+    fn integer_bounds() {
+        crate::init(|vm| {
+            for n in [i128::MIN, -1, 0, i64::MAX as i128 + 1, i128::MAX] {
+                assert_eq!(i128::from_scm(n.to_scm(&vm)), Some(n));
+            }
+            for n in [0, u64::MAX as u128 + 1, u128::MAX] {
+                assert_eq!(u128::from_scm(n.to_scm(&vm)), Some(n));
+            }
+            assert_eq!(i128::from_scm(u128::MAX.to_scm(&vm)), None);
+
+            let eval = |code| vm.eval_language(crate::Language::Scheme, code).unwrap();
+            assert_eq!(u128::from_scm(eval("(expt 2 127)")), Some(1 << 127));
+            assert_eq!(i128::from_scm(eval("(expt 2 127)")), None);
+            assert_eq!(i128::from_scm(eval("(- (expt 2 127))")), Some(i128::MIN));
+            assert_eq!(u128::from_scm(eval("(expt 2 128)")), None);
+            assert!(eval("(- (expt 2 100) 1)").is_eqv(((1i128 << 100) - 1).to_scm(&vm)));
+            assert_eq!(u128::from_scm((-1i32).to_scm(&vm)), None);
+            assert_eq!(u128::from_scm("12".to_scm(&vm)), None);
+            assert_eq!(u128::from_scm(eval("1+2i")), None);
+            assert_eq!(i64::from_scm((i64::MAX as i128 + 1).to_scm(&vm)), None);
+            assert_eq!(i64::from_scm(i64::MIN.to_scm(&vm)), Some(i64::MIN));
+            assert_eq!(isize::from_scm(isize::MAX.to_scm(&vm)), Some(isize::MAX));
+            assert!(matches!(
+                u128::try_from_scm((-1i32).to_scm(&vm)),
+                Err(ConversionError::OutOfRange { .. })
+            ));
+        });
+    }
+
+    #[test]
+    fn float_semantics() {
+        crate::init(|vm| {
+            let eval = |code| vm.eval_language(crate::Language::Scheme, code).unwrap();
+            assert_eq!(f64::from_scm(eval("+inf.0")), Some(f64::INFINITY));
+            assert_eq!(f64::from_scm(eval("-inf.0")), Some(f64::NEG_INFINITY));
+            assert!(f64::from_scm(eval("+nan.0")).unwrap().is_nan());
+            assert_eq!(vm.write(f64::NAN.to_scm(&vm)), "+nan.0");
+            assert_eq!(vm.write(f64::NEG_INFINITY.to_scm(&vm)), "-inf.0");
+
+            assert_eq!(f64::from_scm(eval("1/4")), Some(0.25));
+            assert_eq!(f64::from_scm(eval("(expt 10 400)")), Some(f64::INFINITY));
+            assert_eq!(
+                Lossless::<f64>::try_from_scm(eval("1/4")),
+                Ok(Lossless(0.25))
+            );
+            assert_eq!(
+                Lossless::<f64>::try_from_scm(eval("1/3")),
+                Err(ConversionError::LossOfPrecision)
+            );
+            assert_eq!(
+                Lossless::<f64>::try_from_scm(eval("(+ (expt 2 53) 1)")),
+                Err(ConversionError::LossOfPrecision)
+            );
+            assert_eq!(
+                Lossless::<f64>::try_from_scm(eval("(expt 10 400)")),
+                Err(ConversionError::LossOfPrecision)
+            );
+            assert!(Lossless::<f64>::try_from_scm(eval("+nan.0"))
+                .unwrap()
+                .0
+                .is_nan());
+            assert_eq!(
+                Lossless::<f32>::try_from_scm(eval("0.1")),
+                Err(ConversionError::LossOfPrecision)
+            );
+            assert_eq!(
+                Lossless::<f32>::try_from_scm(eval("0.5")),
+                Ok(Lossless(0.5))
+            );
+        });
+    }
+
+    #[test]
+    fn checked_conversions() {
+        crate::init(|vm| {
+            assert_eq!(u8::try_from_scm(7i32.to_scm(&vm)), Ok(7));
+            assert_eq!(
+                u8::try_from_scm(300i32.to_scm(&vm)),
+                Err(ConversionError::OutOfRange {
+                    value: "300".into(),
+                    target: "u8"
+                })
+            );
+            assert_eq!(
+                i32::try_from_scm(3.0f64.to_scm(&vm)),
+                Err(ConversionError::NotExact)
+            );
+            assert_eq!(
+                i32::try_from_scm("3".to_scm(&vm)),
+                Err(ConversionError::WrongType {
+                    expected: "exact integer",
+                    found: "string"
+                })
+            );
+            assert_eq!(
+                String::try_from_scm(false.to_scm(&vm)),
+                Err(ConversionError::WrongType {
+                    expected: "string",
+                    found: "boolean"
+                })
+            );
+            assert_eq!(f64::try_from_scm(2i32.to_scm(&vm)), Ok(2.0));
+        });
+    }
+
+    #[test]
+    fn multiple_values() {
+        crate::init(|vm| {
+            let values = vm
+                .eval_language(crate::Language::Scheme, "(values 1 \"two\" #\\3)")
+                .unwrap();
+            assert_eq!(
+                <(u32, String, char)>::from_scm(values),
+                Some((1, "two".to_string(), '3'))
+            );
+            assert_eq!(<(u32, String)>::from_scm(values), None);
+            assert_eq!(<(u32,)>::from_scm(7u32.to_scm(&vm)), Some((7,)));
+
+            let back = (
+                1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8, 8u8, 9u8, 10u8, 11u8, 12u8,
+            )
+                .to_scm(&vm);
+            assert_eq!(unsafe { guile_sys::scm_c_nvalues(back.as_raw()) }, 12);
+            assert_eq!(
+                <(u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8)>::from_scm(back)
+                    .unwrap()
+                    .11,
+                12
+            );
+        });
+    }
+
+    #[test]
+    fn containers_round_trip() {
+        crate::init(|vm| {
+            let list = vec![1u32, 2, 3].to_scm(&vm);
+            assert_eq!(Vec::<u32>::from_scm(list), Some(vec![1, 2, 3]));
+            assert_eq!(Vec::<String>::from_scm(list), None);
+            assert_eq!(Vec::<u32>::from_scm(1u32.to_scm(&vm)), None);
+            assert_eq!(
+                Vec::<u32>::from_scm(Vec::<u32>::new().to_scm(&vm)),
+                Some(vec![])
+            );
+
+            assert_eq!(Option::<u32>::from_scm(None::<u32>.to_scm(&vm)), Some(None));
+            assert_eq!(
+                Option::<u32>::from_scm(Some(4u32).to_scm(&vm)),
+                Some(Some(4))
+            );
+            assert_eq!(Option::<u32>::from_scm("x".to_scm(&vm)), None);
+
+            let f = Maybe(Some(false)).to_scm(&vm);
+            assert_eq!(vm.write(f), "(just #f)");
+            assert_eq!(Maybe::<bool>::from_scm(f), Some(Maybe(Some(false))));
+            let nothing = Maybe::<bool>(None).to_scm(&vm);
+            assert_eq!(vm.write(nothing), "nothing");
+            assert_eq!(Maybe::<bool>::from_scm(nothing), Some(Maybe(None)));
+            assert_eq!(Maybe::<bool>::from_scm(false.to_scm(&vm)), None);
+
+            let ok: Result<u32, String> = Ok(3);
+            assert_eq!(vm.write(ok.to_scm(&vm)), "(ok . 3)");
+            assert_eq!(Result::<u32, String>::from_scm(ok.to_scm(&vm)), Some(ok));
+            let err: Result<u32, String> = Err("bad input".into());
+            assert_eq!(vm.write(err.to_scm(&vm)), "(error . \"bad input\")");
+            assert_eq!(Result::<u32, String>::from_scm(err.to_scm(&vm)), Some(err));
+            assert_eq!(Result::<u32, String>::from_scm(3u32.to_scm(&vm)), None);
+        });
+    }
+
+    #[test]
+    fn path_round_trip() {
+        crate::init(|vm| {
+            let path = Path::new("/tmp/some dir/file.scm");
+            let scm = path.to_scm(&vm);
+            assert_eq!(
+                ScmString::from_scm(scm).unwrap().to_string(),
+                "/tmp/some dir/file.scm"
+            );
+            assert_eq!(PathBuf::from_scm(scm).unwrap(), path);
+        });
+    }
+
+    #[test]
+    fn path_errors() {
+        crate::init(|vm| {
+            assert_eq!(
+                PathBuf::try_from_scm(1i32.to_scm(&vm)),
+                Err(ConversionError::WrongType {
+                    expected: "string",
+                    found: "exact integer"
+                })
+            );
+            let path = PathBuf::from("relative/file.scm");
+            assert_eq!(PathBuf::try_from_scm(path.as_path().to_scm(&vm)), Ok(path));
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::ffi::OsStrExt;
+                let raw = OsStr::from_bytes(b"caf\xe9");
+                assert!(ScmString::from_scm(raw.to_scm(&vm)).is_some());
+            }
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_ascii_path_open() {
+        let path = std::env::temp_dir().join(format!("guile-rs-café-{}", std::process::id()));
+        std::fs::write(&path, "42").unwrap();
+        crate::init(|vm| {
+            if vm
+                .setlocale(crate::LocaleCategory::CType, "C.UTF-8")
+                .is_err()
+            {
+                return;
+            }
+            let open = vm
+                .eval_language(
+                    crate::Language::Scheme,
+                    "(lambda (path) (let ((port (open-input-file path))) (read port)))",
+                )
+                .unwrap();
+            let open = crate::ScmProcedure::from_scm(open).unwrap();
+            let scm = path.as_path().to_scm(&vm);
+            assert_eq!(i32::from_scm(open.call(&[scm]).unwrap()), Some(42));
+            assert_eq!(PathBuf::from_scm(scm).as_deref(), Some(path.as_path()));
+        });
+    }
+
+    #[test]
+    fn cstring_round_trip() {
+        crate::init(|vm| {
+            let s = CString::new("hello").unwrap();
+            assert_eq!(CString::from_scm(s.to_scm(&vm)), Some(s));
+
+            let nul = ScmString::new(&vm, "a\0b").as_scm();
+            assert_eq!(CString::from_scm(nul), None);
+            assert_eq!(
+                CString::try_from_scm(nul),
+                Err(ConversionError::InteriorNul)
+            );
+
+            let raw = CString::new(b"caf\xe9".to_vec()).unwrap();
+            let scm = Latin1(raw.as_c_str()).to_scm(&vm);
+            assert_eq!(ScmString::from_scm(scm).unwrap().to_string(), "café");
+            assert_eq!(Latin1::<CString>::from_scm(scm), Some(Latin1(raw)));
+            assert_eq!(Latin1::<CString>::from_scm("λ".to_scm(&vm)), None);
+        });
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use guile_sys::{SCM, SCM_UNSPECIFIED};
+
+use crate::error::{protect, GuileError};
+use crate::scm::public_ref;
+use crate::string;
+use crate::{GuileVM, Scm, ScmProcedure};
+
+unsafe fn coverage(name: &str) -> SCM {
+    public_ref("system vm coverage", name)
+}
+
+/// Coverage data collected by `GuileVM::with_code_coverage`.
+#[derive(Clone, Copy)]
+pub struct CoverageData<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> CoverageData<'vm> {
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    /// The source files that had code running while data was collected.
+    pub fn source_files(&self) -> Result<Vec<String>, GuileError> {
+        let data = self.scm.as_raw();
+        unsafe {
+            let mut files =
+                protect(|| guile_sys::scm_call_1(coverage("instrumented-source-files"), data))?;
+            let mut result = Vec::new();
+            while !guile_sys::scm_is_null(files) {
+                result.push(string::to_string(guile_sys::scm_car(files)));
+                files = guile_sys::scm_cdr(files);
+            }
+            Ok(result)
+        }
+    }
+
+    /// `(line, count)` for every instrumented line of `file`, with
+    /// zero-based line numbers as in Guile's source properties.
+    pub fn line_execution_counts(&self, file: &str) -> Result<Vec<(u32, u64)>, GuileError> {
+        let data = self.scm.as_raw();
+        unsafe {
+            let file = string::from_str(file);
+            let mut counts =
+                protect(|| guile_sys::scm_call_2(coverage("line-execution-counts"), data, file))?;
+            let mut result = Vec::new();
+            while guile_sys::scm_is_pair(counts) != 0 {
+                let entry = guile_sys::scm_car(counts);
+                result.push((
+                    guile_sys::scm_to_uint32(guile_sys::scm_car(entry)),
+                    guile_sys::scm_to_uint64(guile_sys::scm_cdr(entry)),
+                ));
+                counts = guile_sys::scm_cdr(counts);
+            }
+            result.sort_unstable();
+            Ok(result)
+        }
+    }
+
+    /// The number of `(instrumented, executed)` lines in `file`.
+    pub fn line_totals(&self, file: &str) -> Result<(u64, u64), GuileError> {
+        let data = self.scm.as_raw();
+        unsafe {
+            let file = string::from_str(file);
+            let totals = protect(|| {
+                guile_sys::scm_call_2(coverage("instrumented/executed-lines"), data, file)
+            })?;
+            Ok((
+                guile_sys::scm_to_uint64(guile_sys::scm_c_value_ref(totals, 0)),
+                guile_sys::scm_to_uint64(guile_sys::scm_c_value_ref(totals, 1)),
+            ))
+        }
+    }
+
+    /// How many times `proc_` was called.
+    pub fn procedure_execution_count(&self, proc_: &ScmProcedure) -> Result<u64, GuileError> {
+        let data = self.scm.as_raw();
+        let proc_ = proc_.as_scm().as_raw();
+        unsafe {
+            let count = protect(|| {
+                guile_sys::scm_call_2(coverage("procedure-execution-count"), data, proc_)
+            })?;
+            Ok(guile_sys::scm_to_uint64(count))
+        }
+    }
+
+    /// The data in LCOV tracefile format, e.g. to be merged with the
+    /// coverage of the Rust side by `lcov --add-tracefile`.
+    pub fn to_lcov(&self) -> Result<String, GuileError> {
+        let data = self.scm.as_raw();
+        unsafe {
+            let lcov = protect(|| {
+                let port = guile_sys::scm_open_output_string();
+                guile_sys::scm_call_2(coverage("coverage-data->lcov"), data, port);
+                guile_sys::scm_get_output_string(port)
+            })?;
+            Ok(string::to_string(lcov))
+        }
+    }
+}
+
+impl GuileVM {
+    /// Call `thunk` while collecting code coverage, returning the data
+    /// along with the thunk's (first) return value.
+    pub fn with_code_coverage<'vm>(
+        &'vm self,
+        thunk: &ScmProcedure<'vm>,
+    ) -> Result<(CoverageData<'vm>, Scm<'vm>), GuileError> {
+        let thunk = thunk.as_scm().as_raw();
+        unsafe {
+            let values = protect(|| guile_sys::scm_call_1(coverage("with-code-coverage"), thunk))?;
+            let result = if guile_sys::scm_c_nvalues(values) > 1 {
+                guile_sys::scm_c_value_ref(values, 1)
+            } else {
+                SCM_UNSPECIFIED
+            };
+            Ok((
+                CoverageData {
+                    scm: Scm::from_raw(self, guile_sys::scm_c_value_ref(values, 0)),
+                },
+                Scm::from_raw(self, result),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::init;
+
+    #[test]
+    fn coverage() {
+        init(|vm| {
+            let thunk = vm.compile("(define (f x) (* x 2)) (f 21)").unwrap();
+            let (data, result) = vm.with_code_coverage(&thunk).unwrap();
+            assert_eq!(unsafe { guile_sys::scm_to_int64(result.as_raw()) }, 42);
+            assert!(data.to_lcov().is_ok());
+            for file in data.source_files().unwrap() {
+                let (instrumented, executed) = data.line_totals(&file).unwrap();
+                assert!(executed <= instrumented);
+            }
+        });
+    }
+}
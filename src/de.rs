@@ -0,0 +1,547 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::error;
+use std::fmt;
+
+use guile_sys::SCM;
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+
+use crate::display;
+use crate::string;
+use crate::Scm;
+
+/// An error deserializing a Scheme value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeserializeError {
+    message: String,
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl error::Error for DeserializeError {}
+
+impl de::Error for DeserializeError {
+    fn custom<T: fmt::Display>(msg: T) -> DeserializeError {
+        DeserializeError {
+            message: msg.to_string(),
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, DeserializeError>;
+
+/// Deserialize a Rust value from Scheme data.
+///
+/// Sequences are lists or vectors, maps and structs are alists keyed by
+/// symbols, strings or keywords, unit enum variants are symbols and other
+/// variants are `(variant . value)` pairs. `#f` deserializes as `None`.
+pub fn from_scm<T: de::DeserializeOwned>(scm: Scm<'_>) -> Result<T> {
+    T::deserialize(Deserializer { raw: scm.as_raw() })
+}
+
+/* Only ever created for a value reachable from the `Scm` passed to
+ * `from_scm`, which keeps it alive. */
+struct Deserializer {
+    raw: SCM,
+}
+
+unsafe fn is(predicate: SCM) -> bool {
+    guile_sys::scm_is_true(predicate)
+}
+
+impl Deserializer {
+    fn unexpected(&self, expected: &str) -> DeserializeError {
+        let found = unsafe { display::print(self.raw, guile_sys::scm_write) };
+        de::Error::custom(format!("expected {}, found {}", expected, found))
+    }
+
+    fn signed(&self, min: i64, max: i64) -> Result<i64> {
+        unsafe {
+            if guile_sys::scm_is_signed_integer(self.raw, min, max) != 0 {
+                Ok(guile_sys::scm_to_int64(self.raw))
+            } else {
+                Err(self.unexpected("an integer in range"))
+            }
+        }
+    }
+
+    fn unsigned(&self, max: u64) -> Result<u64> {
+        unsafe {
+            if guile_sys::scm_is_unsigned_integer(self.raw, 0, max) != 0 {
+                Ok(guile_sys::scm_to_uint64(self.raw))
+            } else {
+                Err(self.unexpected("a non-negative integer in range"))
+            }
+        }
+    }
+
+    /// Strings, symbols and keywords, which all name things in a config.
+    fn string(&self) -> Result<String> {
+        unsafe {
+            let mut raw = self.raw;
+            if guile_sys::scm_is_keyword(raw) != 0 {
+                raw = guile_sys::scm_keyword_to_symbol(raw);
+            }
+            if is(guile_sys::scm_symbol_p(raw)) {
+                raw = guile_sys::scm_symbol_to_string(raw);
+            }
+            if is(guile_sys::scm_string_p(raw)) {
+                Ok(string::to_string(raw))
+            } else {
+                Err(self.unexpected("a string"))
+            }
+        }
+    }
+}
+
+macro_rules! deserialize_signed {
+    ($($method:ident => $visit:ident: $ty:ty),*) => {
+        $(fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            visitor.$visit(self.signed(<$ty>::MIN as i64, <$ty>::MAX as i64)? as $ty)
+        })*
+    };
+}
+
+macro_rules! deserialize_unsigned {
+    ($($method:ident => $visit:ident: $ty:ty),*) => {
+        $(fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            visitor.$visit(self.unsigned(<$ty>::MAX as u64)? as $ty)
+        })*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let x = self.raw;
+        unsafe {
+            if guile_sys::scm_is_bool(x) != 0 {
+                visitor.visit_bool(guile_sys::scm_is_true(x))
+            } else if guile_sys::scm_is_signed_integer(x, i64::MIN, i64::MAX) != 0 {
+                visitor.visit_i64(guile_sys::scm_to_int64(x))
+            } else if guile_sys::scm_is_unsigned_integer(x, 0, u64::MAX) != 0 {
+                visitor.visit_u64(guile_sys::scm_to_uint64(x))
+            } else if guile_sys::scm_is_real(x) != 0 {
+                visitor.visit_f64(guile_sys::scm_to_double(x))
+            } else if guile_sys::SCM_CHARP(x) {
+                self.deserialize_char(visitor)
+            } else if is(guile_sys::scm_string_p(x))
+                || is(guile_sys::scm_symbol_p(x))
+                || guile_sys::scm_is_keyword(x) != 0
+            {
+                visitor.visit_string(self.string()?)
+            } else if guile_sys::scm_is_bytevector(x) != 0 {
+                self.deserialize_byte_buf(visitor)
+            } else if guile_sys::scm_is_null(x)
+                || guile_sys::scm_is_pair(x) != 0
+                || guile_sys::scm_is_vector(x) != 0
+            {
+                self.deserialize_seq(visitor)
+            } else if guile_sys::scm_is_eq(x, guile_sys::SCM_UNSPECIFIED) {
+                visitor.visit_unit()
+            } else {
+                Err(self.unexpected("a boolean, number, character, string, list or vector"))
+            }
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if unsafe { guile_sys::scm_is_bool(self.raw) } != 0 {
+            visitor.visit_bool(guile_sys::scm_is_true(self.raw))
+        } else {
+            Err(self.unexpected("a boolean"))
+        }
+    }
+
+    deserialize_signed!(
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64
+    );
+
+    deserialize_unsigned!(
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64
+    );
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        unsafe {
+            if guile_sys::scm_is_real(self.raw) != 0 {
+                visitor.visit_f64(guile_sys::scm_to_double(self.raw))
+            } else {
+                Err(self.unexpected("a real number"))
+            }
+        }
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match Some(self.raw)
+            .filter(|&x| guile_sys::SCM_CHARP(x))
+            .and_then(|x| char::from_u32(guile_sys::SCM_CHAR(x)))
+        {
+            Some(c) => visitor.visit_char(c),
+            None => Err(self.unexpected("a character")),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_string(self.string()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_string(self.string()?)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        unsafe {
+            if guile_sys::scm_is_bytevector(self.raw) != 0 {
+                let len = guile_sys::scm_c_bytevector_length(self.raw);
+                let bytes = (0..len)
+                    .map(|idx| guile_sys::scm_c_bytevector_ref(self.raw, idx))
+                    .collect();
+                visitor.visit_byte_buf(bytes)
+            } else {
+                self.deserialize_seq(visitor)
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if guile_sys::scm_is_false(self.raw) {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if guile_sys::scm_is_null(self.raw)
+            || guile_sys::scm_is_eq(self.raw, guile_sys::SCM_UNSPECIFIED)
+        {
+            visitor.visit_unit()
+        } else {
+            Err(self.unexpected("'() or an unspecified value"))
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        unsafe {
+            if guile_sys::scm_is_vector(self.raw) != 0 {
+                visitor.visit_seq(VectorAccess {
+                    vector: self.raw,
+                    idx: 0,
+                    len: guile_sys::scm_c_vector_length(self.raw),
+                })
+            } else if is(guile_sys::scm_list_p(self.raw)) {
+                visitor.visit_seq(ListAccess { rest: self.raw })
+            } else {
+                Err(self.unexpected("a list or vector"))
+            }
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if unsafe { is(guile_sys::scm_list_p(self.raw)) } {
+            visitor.visit_map(AlistAccess { rest: self.raw })
+        } else {
+            Err(self.unexpected("an alist"))
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        unsafe {
+            if guile_sys::scm_is_pair(self.raw) != 0 {
+                visitor.visit_enum(EnumAccess {
+                    variant: guile_sys::scm_car(self.raw),
+                    value: Some(guile_sys::scm_cdr(self.raw)),
+                })
+            } else {
+                visitor.visit_enum(EnumAccess {
+                    variant: self.raw,
+                    value: None,
+                })
+            }
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+}
+
+struct ListAccess {
+    rest: SCM,
+}
+
+impl<'de> de::SeqAccess<'de> for ListAccess {
+    type Error = DeserializeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if guile_sys::scm_is_null(self.rest) {
+            return Ok(None);
+        }
+        let raw = unsafe { guile_sys::scm_car(self.rest) };
+        self.rest = unsafe { guile_sys::scm_cdr(self.rest) };
+        seed.deserialize(Deserializer { raw }).map(Some)
+    }
+}
+
+struct VectorAccess {
+    vector: SCM,
+    idx: usize,
+    len: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for VectorAccess {
+    type Error = DeserializeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.idx == self.len {
+            return Ok(None);
+        }
+        let raw = unsafe { guile_sys::scm_c_vector_ref(self.vector, self.idx) };
+        self.idx += 1;
+        seed.deserialize(Deserializer { raw }).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.idx)
+    }
+}
+
+struct AlistAccess {
+    rest: SCM,
+}
+
+impl<'de> de::MapAccess<'de> for AlistAccess {
+    type Error = DeserializeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if guile_sys::scm_is_null(self.rest) {
+            return Ok(None);
+        }
+        let entry = unsafe { guile_sys::scm_car(self.rest) };
+        if unsafe { guile_sys::scm_is_pair(entry) } == 0 {
+            return Err(Deserializer { raw: entry }.unexpected("a (key . value) pair"));
+        }
+        seed.deserialize(Deserializer {
+            raw: unsafe { guile_sys::scm_car(entry) },
+        })
+        .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let entry = unsafe { guile_sys::scm_car(self.rest) };
+        self.rest = unsafe { guile_sys::scm_cdr(self.rest) };
+        seed.deserialize(Deserializer {
+            raw: unsafe { guile_sys::scm_cdr(entry) },
+        })
+    }
+}
+
+struct EnumAccess {
+    variant: SCM,
+    value: Option<SCM>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess {
+    type Error = DeserializeError;
+    type Variant = VariantAccess;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, VariantAccess)> {
+        let name = Deserializer { raw: self.variant }.string()?;
+        let variant = seed.deserialize(name.into_deserializer())?;
+        Ok((variant, VariantAccess { value: self.value }))
+    }
+}
+
+struct VariantAccess {
+    value: Option<SCM>,
+}
+
+impl VariantAccess {
+    fn value(self) -> Result<Deserializer> {
+        match self.value {
+            Some(raw) => Ok(Deserializer { raw }),
+            None => Err(de::Error::custom("expected a (variant . value) pair")),
+        }
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess {
+    type Error = DeserializeError;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.value {
+            None => Ok(()),
+            Some(raw) => de::Deserialize::deserialize(Deserializer { raw }),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self.value()?)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self.value()?, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(self.value()?, visitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Level {
+        Quiet,
+        Verbose(u8),
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Settings {
+        name: String,
+        ports: Vec<u16>,
+        ratio: f64,
+        level: Level,
+        extra: Option<HashMap<String, i32>>,
+        missing: Option<bool>,
+    }
+
+    #[test]
+    fn deserialize_struct() {
+        init(|vm| {
+            let scm = vm
+                .eval_language(
+                    Language::Scheme,
+                    "'((name . \"server\") (ports 80 443) (ratio . 1/2)
+                       (level . (Verbose . 2)) (extra . ((a . 1) (#:b . -2))))",
+                )
+                .unwrap();
+            let settings: Settings = from_scm(scm).unwrap();
+            assert_eq!(settings.name, "server");
+            assert_eq!(settings.ports, vec![80, 443]);
+            assert_eq!(settings.ratio, 0.5);
+            assert_eq!(settings.level, Level::Verbose(2));
+            assert_eq!(settings.extra.unwrap()["b"], -2);
+            assert_eq!(settings.missing, None);
+
+            let quiet = vm.eval_language(Language::Scheme, "'Quiet").unwrap();
+            assert_eq!(from_scm::<Level>(quiet).unwrap(), Level::Quiet);
+        });
+    }
+
+    #[test]
+    fn type_mismatch() {
+        init(|vm| {
+            let scm = vm.eval_language(Language::Scheme, "#(1 2 300)").unwrap();
+            assert_eq!(from_scm::<Vec<u16>>(scm).unwrap(), vec![1, 2, 300]);
+            assert!(from_scm::<Vec<u8>>(scm).is_err());
+        });
+    }
+
+    #[test]
+    fn unprintable_mismatch() {
+        init(|vm| {
+            let scm = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(use-modules (srfi srfi-9 gnu))
+                     (define-record-type <broken> (make-broken) broken?)
+                     (set-record-type-printer! <broken> (lambda (r port) (error \"no\")))
+                     (make-broken)",
+                )
+                .unwrap();
+            let error = from_scm::<String>(scm).unwrap_err();
+            assert_eq!(error.to_string(), "expected a string, found #<unprintable>");
+        });
+    }
+}
@@ -0,0 +1,76 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use guile_sys::SCM;
+
+use crate::error::protect;
+use crate::string;
+use crate::{GuileVM, Scm};
+
+/// What `display`/`write` return for values whose printer throws.
+const UNPRINTABLE: &str = "#<unprintable>";
+
+pub(crate) unsafe fn print(value: SCM, printer: unsafe extern "C" fn(SCM, SCM) -> SCM) -> String {
+    protect(|| {
+        let port = guile_sys::scm_open_output_string();
+        printer(value, port);
+        guile_sys::scm_get_output_string(port)
+    })
+    .map(|s| string::to_string(s))
+    .unwrap_or_else(|_| String::from(UNPRINTABLE))
+}
+
+impl GuileVM {
+    /// `value` as `display` prints it. Never fails, so it's safe to use
+    /// when logging errors.
+    pub fn display(&self, value: Scm) -> String {
+        unsafe { print(value.as_raw(), guile_sys::scm_display) }
+    }
+
+    /// `value` as `write` prints it. Never fails, so it's safe to use
+    /// when logging errors.
+    pub fn write(&self, value: Scm) -> String {
+        unsafe { print(value.as_raw(), guile_sys::scm_write) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{init, Language};
+
+    #[test]
+    fn display_and_write() {
+        init(|vm| {
+            let value = vm
+                .eval_language(Language::Scheme, "(list \"a\" #\\b 'c 1.5)")
+                .unwrap();
+            assert_eq!(vm.display(value), "(a b c 1.5)");
+            assert_eq!(vm.write(value), "(\"a\" #\\b c 1.5)");
+
+            let unprintable = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(use-modules (srfi srfi-9 gnu))
+                     (define-record-type <broken> (make-broken) broken?)
+                     (set-record-type-printer! <broken> (lambda (r port) (error \"no\")))
+                     (make-broken)",
+                )
+                .unwrap();
+            assert_eq!(vm.write(unprintable), "#<unprintable>");
+        });
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use crate::error::{protect, GuileError};
+use crate::scm::list_from_raw;
+use crate::string;
+use crate::GuileVM;
+
+impl GuileVM {
+    /// Set what `(command-line)` and `(program-arguments)` return to
+    /// scripts, independently of the real process argv. By convention
+    /// the first argument is the program name.
+    pub fn set_program_arguments<I, S>(&self, args: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        unsafe {
+            let args = list_from_raw(args.into_iter().map(|arg| string::from_str(arg.as_ref())));
+            guile_sys::scm_set_program_arguments_scm(args);
+        }
+    }
+
+    /// What `(command-line)` currently returns to scripts.
+    pub fn program_arguments(&self) -> Vec<String> {
+        let mut result = Vec::new();
+        unsafe {
+            let mut args = guile_sys::scm_program_arguments();
+            while guile_sys::scm_is_pair(args) != 0 {
+                result.push(string::to_string(guile_sys::scm_car(args)));
+                args = guile_sys::scm_cdr(args);
+            }
+        }
+        result
+    }
+
+    /// Scheme's `getenv`.
+    pub fn getenv(&self, name: &str) -> Option<String> {
+        unsafe {
+            let value = guile_sys::scm_getenv(string::from_str(name));
+            if guile_sys::scm_is_true(value) {
+                Some(string::to_string(value))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Scheme's `setenv`. Affects the whole process.
+    pub fn setenv(&self, name: &str, value: &str) -> Result<(), GuileError> {
+        self.putenv(&format!("{}={}", name, value))
+    }
+
+    /// Scheme's `unsetenv`. Affects the whole process.
+    pub fn unsetenv(&self, name: &str) -> Result<(), GuileError> {
+        self.putenv(name)
+    }
+
+    fn putenv(&self, s: &str) -> Result<(), GuileError> {
+        unsafe {
+            let s = string::from_str(s);
+            protect(|| guile_sys::scm_putenv(s))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::init;
+
+    #[test]
+    fn environment() {
+        init(|vm| {
+            vm.setenv("GUILE_RS_TEST_VAR", "some value").unwrap();
+            assert_eq!(
+                vm.getenv("GUILE_RS_TEST_VAR").as_deref(),
+                Some("some value")
+            );
+            assert_eq!(
+                std::env::var("GUILE_RS_TEST_VAR").as_deref(),
+                Ok("some value")
+            );
+
+            vm.unsetenv("GUILE_RS_TEST_VAR").unwrap();
+            assert_eq!(vm.getenv("GUILE_RS_TEST_VAR"), None);
+        });
+    }
+
+    #[test]
+    fn program_arguments() {
+        init(|vm| {
+            vm.set_program_arguments(["my-app", "--verbose"]);
+            assert_eq!(vm.program_arguments(), vec!["my-app", "--verbose"]);
+        });
+    }
+}
@@ -0,0 +1,270 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::ptr;
+
+use guile_sys::{SCM, SCM_BOOL_F, SCM_BOOL_T, SCM_UNSPECIFIED};
+use libc::c_void;
+
+use crate::string;
+
+/// An error raised by Scheme code that was caught before it could
+/// unwind into Rust.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GuileError {
+    /// A `throw` (or `raise-exception`) with the given key, e.g.
+    /// `wrong-type-arg` or `misc-error`.
+    Throw { key: String, message: String },
+    /// A `system-error` thrown by a failing system call.
+    System { errno: i32, message: String },
+    /// A time limit set with `Limits` or `SandboxOptions` ran out.
+    TimeLimitExceeded,
+    /// An allocation limit set with `Limits` or `SandboxOptions` ran out.
+    AllocationLimitExceeded,
+    /// The deadline given to `GuileVM::eval_with_deadline` passed.
+    Interrupted,
+}
+
+impl GuileError {
+    unsafe fn from_throw(key: SCM, args: SCM) -> GuileError {
+        let name = if guile_sys::scm_is_true(guile_sys::scm_symbol_p(key)) {
+            string::to_string(guile_sys::scm_symbol_to_string(key))
+        } else {
+            String::from("unknown")
+        };
+        let message = protect(|| {
+            let port = guile_sys::scm_open_output_string();
+            guile_sys::scm_print_exception(port, SCM_BOOL_F, key, args);
+            guile_sys::scm_get_output_string(port)
+        })
+        .map(|s| string::to_string(s).trim_end().to_string())
+        .unwrap_or_else(|_| name.clone());
+        if let Some(errno) = system_errno(&name, args) {
+            return GuileError::System { errno, message };
+        }
+        match limit_subr(&name, args).as_deref() {
+            Some("with-time-limit") => GuileError::TimeLimitExceeded,
+            Some("with-allocation-limit") => GuileError::AllocationLimitExceeded,
+            _ => GuileError::Throw { key: name, message },
+        }
+    }
+
+    /// The throw key, as a symbol name.
+    pub fn key(&self) -> &str {
+        match self {
+            GuileError::Throw { key, .. } => key,
+            GuileError::System { .. } => "system-error",
+            GuileError::TimeLimitExceeded | GuileError::AllocationLimitExceeded => "limit-exceeded",
+            GuileError::Interrupted => "interrupted",
+        }
+    }
+}
+
+impl fmt::Display for GuileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GuileError::Throw { message, .. } | GuileError::System { message, .. } => {
+                write!(f, "{}", message)
+            }
+            GuileError::TimeLimitExceeded => write!(f, "time limit exceeded"),
+            GuileError::AllocationLimitExceeded => write!(f, "allocation limit exceeded"),
+            GuileError::Interrupted => write!(f, "evaluation interrupted"),
+        }
+    }
+}
+
+impl Error for GuileError {}
+
+impl From<GuileError> for io::Error {
+    fn from(error: GuileError) -> io::Error {
+        match error {
+            GuileError::System { errno, .. } => io::Error::from_raw_os_error(errno),
+            error => io::Error::other(error),
+        }
+    }
+}
+
+type ErrorRule<E> = (ErrorMatch, Box<dyn Fn(GuileError) -> E>);
+
+enum ErrorMatch {
+    Key(String),
+    Errno(i32),
+}
+
+/// Maps `GuileError`s to an application's own error type, so callers
+/// can match on its variants instead of comparing throw keys:
+///
+/// ```ignore
+/// let errors = ErrorMap::new(AppError::Scheme)
+///     .errno(libc::ENOENT, |_| AppError::FileMissing)
+///     .key("wrong-type-arg", |err| AppError::BadArgument(err.to_string()));
+/// vm.eval_language(Language::Scheme, code).map_err(|err| errors.map(err))?;
+/// ```
+pub struct ErrorMap<E> {
+    rules: Vec<ErrorRule<E>>,
+    fallback: Box<dyn Fn(GuileError) -> E>,
+}
+
+impl<E> ErrorMap<E> {
+    /// Errors no rule matches are converted with `fallback`.
+    pub fn new<F: Fn(GuileError) -> E + 'static>(fallback: F) -> ErrorMap<E> {
+        ErrorMap {
+            rules: Vec::new(),
+            fallback: Box::new(fallback),
+        }
+    }
+
+    /// Convert errors with the throw key `key`, as returned by
+    /// `GuileError::key`.
+    pub fn key<F: Fn(GuileError) -> E + 'static>(mut self, key: &str, f: F) -> ErrorMap<E> {
+        self.rules
+            .push((ErrorMatch::Key(key.to_string()), Box::new(f)));
+        self
+    }
+
+    /// Convert `GuileError::System` errors with `errno`.
+    pub fn errno<F: Fn(GuileError) -> E + 'static>(mut self, errno: i32, f: F) -> ErrorMap<E> {
+        self.rules.push((ErrorMatch::Errno(errno), Box::new(f)));
+        self
+    }
+
+    /// Convert `error` with the first matching rule.
+    pub fn map(&self, error: GuileError) -> E {
+        let rule = self.rules.iter().find(|(pattern, _)| match pattern {
+            ErrorMatch::Key(key) => error.key() == key,
+            ErrorMatch::Errno(errno) => {
+                matches!(error, GuileError::System { errno: e, .. } if e == *errno)
+            }
+        });
+        match rule {
+            Some((_, f)) => f(error),
+            None => (self.fallback)(error),
+        }
+    }
+}
+
+/* (ice-9 sandbox) throws limit-exceeded with the subr telling which
+ * limit it was. */
+unsafe fn limit_subr(key: &str, args: SCM) -> Option<String> {
+    if key != "limit-exceeded" || guile_sys::scm_is_pair(args) == 0 {
+        return None;
+    }
+    let subr = guile_sys::scm_car(args);
+    if guile_sys::scm_is_true(guile_sys::scm_string_p(subr)) {
+        Some(string::to_string(subr))
+    } else {
+        None
+    }
+}
+
+/* system-error's args are (subr message message-args (errno)) */
+unsafe fn system_errno(key: &str, args: SCM) -> Option<i32> {
+    if key != "system-error" || guile_sys::scm_ilength(args) != 4 {
+        return None;
+    }
+    let rest = guile_sys::scm_cadddr(args);
+    if guile_sys::scm_is_pair(rest) == 0 {
+        return None;
+    }
+    let errno = guile_sys::scm_car(rest);
+    if guile_sys::scm_is_signed_integer(errno, i32::MIN as i64, i32::MAX as i64) != 0 {
+        Some(guile_sys::scm_to_int32(errno))
+    } else {
+        None
+    }
+}
+
+/// Run `body` inside a catch-all, turning any Scheme throw into a
+/// `GuileError`.
+///
+/// A throw unwinds with a longjmp, so `body` must not hold anything
+/// that needs dropping across the libguile calls it makes.
+pub(crate) unsafe fn protect<F, T>(body: F) -> Result<T, GuileError>
+where
+    F: FnOnce() -> T,
+{
+    let mut data: (Option<F>, Option<T>) = (Some(body), None);
+    let mut error: Option<GuileError> = None;
+    guile_sys::scm_c_catch(
+        SCM_BOOL_T,
+        Some(catch_body::<F, T>),
+        &mut data as *mut _ as *mut c_void,
+        Some(catch_handler),
+        &mut error as *mut _ as *mut c_void,
+        None,
+        ptr::null_mut(),
+    );
+    match (error, data.1) {
+        (Some(error), _) => Err(error),
+        (None, Some(result)) => Ok(result),
+        (None, None) => unreachable!("catch body neither returned nor threw"),
+    }
+}
+
+unsafe extern "C" fn catch_body<F, T>(data: *mut c_void) -> SCM
+where
+    F: FnOnce() -> T,
+{
+    let (body, result) = &mut *(data as *mut (Option<F>, Option<T>));
+    *result = Some((body.take().unwrap())());
+    SCM_UNSPECIFIED
+}
+
+unsafe extern "C" fn catch_handler(data: *mut c_void, key: SCM, args: SCM) -> SCM {
+    let error = &mut *(data as *mut Option<GuileError>);
+    *error = Some(GuileError::from_throw(key, args));
+    SCM_UNSPECIFIED
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language};
+
+    #[derive(Debug, PartialEq)]
+    enum AppError {
+        FileMissing,
+        BadArgument,
+        Scheme(String),
+    }
+
+    #[test]
+    fn map_errors() {
+        init(|vm| {
+            let errors = ErrorMap::new(|err: GuileError| AppError::Scheme(err.key().to_string()))
+                .errno(libc::ENOENT, |_| AppError::FileMissing)
+                .key("wrong-type-arg", |_| AppError::BadArgument);
+            let map = |code| {
+                vm.eval_language(Language::Scheme, code)
+                    .map(|_| ())
+                    .map_err(|err| errors.map(err))
+            };
+            assert_eq!(
+                map("(open-input-file \"/nonexistent/guile-rs\")"),
+                Err(AppError::FileMissing)
+            );
+            assert_eq!(map("(car 1)"), Err(AppError::BadArgument));
+            assert_eq!(
+                map("(throw 'custom-key)"),
+                Err(AppError::Scheme("custom-key".to_string()))
+            );
+        });
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::sync::OnceLock;
+
+use guile_sys::SCM;
+
+use crate::error::protect;
+use crate::scm::{list_from_raw, public_ref, read_all, symbol};
+use crate::string;
+
+/* Whether a module can be loaded, without failing if it can't. */
+const HAS_MODULE: &str = "
+(lambda (name)
+  (false-if-exception (and (resolve-interface name) #t)))
+";
+
+/// What the linked libguile supports, see `features`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Features {
+    /// `(major minor micro)`, e.g. `(3, 0, 9)`.
+    pub version: (u32, u32, u32),
+    /// Built with thread support.
+    pub threads: bool,
+    /// `(ice-9 readline)` can be loaded.
+    pub readline: bool,
+    /// `(ice-9 regex)` can be loaded, which needs the system's regcomp.
+    pub regex: bool,
+    /// `(oop goops)` can be loaded.
+    pub goops: bool,
+}
+
+unsafe fn version_part(part: SCM) -> u32 {
+    string::to_string(part).parse().unwrap_or(0)
+}
+
+unsafe fn provided(feature: &str) -> bool {
+    protect(|| guile_sys::scm_call_1(public_ref("guile", "provided?"), symbol(feature)))
+        .is_ok_and(guile_sys::scm_is_true)
+}
+
+unsafe fn has_module(name: &str) -> bool {
+    let name = list_from_raw(name.split_whitespace().map(|c| symbol(c)));
+    protect(|| {
+        let has_module = guile_sys::scm_primitive_eval(guile_sys::scm_car(read_all(HAS_MODULE)));
+        guile_sys::scm_is_true(guile_sys::scm_call_1(has_module, name))
+    })
+    .unwrap_or(false)
+}
+
+/// Probe the linked Guile for its version and optional features, so an
+/// application can degrade gracefully on builds that lack some of them.
+///
+/// The probe enters guile mode if needed, and only runs once per process.
+pub fn features() -> &'static Features {
+    static FEATURES: OnceLock<Features> = OnceLock::new();
+    FEATURES.get_or_init(|| {
+        let features = std::cell::Cell::new(None);
+        crate::init(|_| unsafe {
+            features.set(Some(Features {
+                version: (
+                    version_part(guile_sys::scm_major_version()),
+                    version_part(guile_sys::scm_minor_version()),
+                    version_part(guile_sys::scm_micro_version()),
+                ),
+                threads: provided("threads"),
+                readline: has_module("ice-9 readline"),
+                regex: provided("regex") && has_module("ice-9 regex"),
+                goops: has_module("oop goops"),
+            }));
+        });
+        features.take().unwrap()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn probe() {
+        let features = features();
+        assert!(features.version >= (2, 0, 0));
+        assert!(features.goops);
+    }
+}
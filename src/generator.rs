@@ -0,0 +1,259 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::sync::{Mutex, TryLockError};
+
+use guile_sys::{SCM, SCM_EOF_VAL};
+
+use crate::closure::make_procedure;
+use crate::error::{protect, GuileError};
+use crate::scm::{public_ref, read_all};
+use crate::{GuileVM, Scm, ScmModule, ToScm};
+
+/* Evaluated in (srfi srfi-41), which has the stream syntax. */
+const GENERATOR_TO_STREAM: &str = "
+(lambda (gen)
+  (letrec ((next (stream-lambda ()
+                   (let ((x (gen)))
+                     (if (eof-object? x)
+                         stream-nil
+                         (stream-cons x (next)))))))
+    (next)))
+";
+
+impl GuileVM {
+    /// A generator procedure: each call returns the iterator's next item,
+    /// or the EOF object once it's exhausted. Lets scripts pull data the
+    /// host produces lazily. Calling it while it's already running
+    /// throws a `misc-error`, as with `closure_mut`.
+    pub fn generator<I>(&self, iter: I) -> Scm<'_>
+    where
+        I: Iterator + Send + 'static,
+        I::Item: ToScm,
+    {
+        let iter = Mutex::new(iter);
+        unsafe {
+            let raw = make_procedure(
+                "rust-generator",
+                Box::new(move |_args| {
+                    let vm = GuileVM::assume_guile_mode();
+                    let mut iter = match iter.try_lock() {
+                        Ok(iter) => iter,
+                        /* The panic that poisoned it was already thrown. */
+                        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+                        Err(TryLockError::WouldBlock) => {
+                            return Err(GuileError::Throw {
+                                key: String::from("misc-error"),
+                                message: String::from(
+                                    "generator called again while it was running",
+                                ),
+                            })
+                        }
+                    };
+                    Ok(match iter.next() {
+                        Some(item) => item.to_scm(&vm).as_raw(),
+                        None => SCM_EOF_VAL,
+                    })
+                }),
+            );
+            Scm::from_raw(self, raw)
+        }
+    }
+
+    /// A SRFI-41 stream of the iterator's items, pulled as the stream is
+    /// forced.
+    pub fn stream<I>(&self, iter: I) -> Result<Scm<'_>, GuileError>
+    where
+        I: Iterator + Send + 'static,
+        I::Item: ToScm,
+    {
+        let srfi41 = ScmModule::resolve(self, "srfi srfi-41")?.as_scm().as_raw();
+        let generator = self.generator(iter).as_raw();
+        unsafe {
+            let stream = protect(|| {
+                let code = guile_sys::scm_car(read_all(GENERATOR_TO_STREAM));
+                guile_sys::scm_call_1(guile_sys::scm_eval(code, srfi41), generator)
+            })?;
+            Ok(Scm::from_raw(self, stream))
+        }
+    }
+
+    /// Iterate over a SRFI-41 stream, forcing one element per step, so
+    /// infinite streams are fine as long as the caller stops. Fails if
+    /// `stream` isn't a stream.
+    pub fn stream_iter<'vm>(&'vm self, stream: Scm<'vm>) -> Result<ScmStreamIter<'vm>, GuileError> {
+        let raw = stream.as_raw();
+        unsafe {
+            let (is_stream, pair_p, car, cdr) = protect(|| {
+                let srfi41 = |name| public_ref("srfi srfi-41", name);
+                (
+                    guile_sys::scm_call_1(srfi41("stream?"), raw),
+                    srfi41("stream-pair?"),
+                    srfi41("stream-car"),
+                    srfi41("stream-cdr"),
+                )
+            })?;
+            if guile_sys::scm_is_false(is_stream) {
+                return Err(GuileError::Throw {
+                    key: String::from("wrong-type-arg"),
+                    message: format!("not a stream: {}", self.write(stream)),
+                });
+            }
+            Ok(ScmStreamIter {
+                rest: stream,
+                pair_p,
+                car,
+                cdr,
+                error: None,
+            })
+        }
+    }
+}
+
+/// Iterator over a stream's elements, see `GuileVM::stream_iter`. Keep
+/// it on the stack: the rest of the stream is only kept alive through
+/// it.
+pub struct ScmStreamIter<'vm> {
+    rest: Scm<'vm>,
+    pair_p: SCM,
+    car: SCM,
+    cdr: SCM,
+    error: Option<GuileError>,
+}
+
+impl<'vm> ScmStreamIter<'vm> {
+    /// Why iteration stopped early, if forcing an element threw.
+    pub fn error(&self) -> Option<&GuileError> {
+        self.error.as_ref()
+    }
+}
+
+impl<'vm> Iterator for ScmStreamIter<'vm> {
+    type Item = Scm<'vm>;
+
+    fn next(&mut self) -> Option<Scm<'vm>> {
+        if self.error.is_some() {
+            return None;
+        }
+        let (rest, pair_p, car, cdr) = (self.rest.as_raw(), self.pair_p, self.car, self.cdr);
+        let step = unsafe {
+            protect(|| {
+                if guile_sys::scm_is_true(guile_sys::scm_call_1(pair_p, rest)) {
+                    Some((
+                        guile_sys::scm_call_1(car, rest),
+                        guile_sys::scm_call_1(cdr, rest),
+                    ))
+                } else {
+                    None
+                }
+            })
+        };
+        match step {
+            Ok(Some((item, rest))) => {
+                self.rest = self.rest.with_raw(rest);
+                Some(self.rest.with_raw(item))
+            }
+            Ok(None) => None,
+            Err(error) => {
+                self.error = Some(error);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{init, GuileVM, Language, ScmProcedure, ToScm};
+
+    #[test]
+    fn generator() {
+        init(|vm| {
+            let gen = ScmProcedure::from_scm(vm.generator(1..=2)).unwrap();
+            assert_eq!(vm.write(gen.call(&[]).unwrap()), "1");
+            assert_eq!(vm.write(gen.call(&[]).unwrap()), "2");
+            assert_eq!(vm.write(gen.call(&[]).unwrap()), "#<eof>");
+        });
+    }
+
+    #[test]
+    fn reentrant_generator() {
+        init(|vm| {
+            /* Each item is the key of the error from pulling again. */
+            let gen = vm.generator((0..).map(|_| {
+                let vm = unsafe { GuileVM::assume_guile_mode() };
+                let again = vm.eval_language(Language::Scheme, "(reentrant-gen)");
+                again.err().map(|error| error.key().to_string())
+            }));
+            let set = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(define reentrant-gen #f) (lambda (g) (set! reentrant-gen g))",
+                )
+                .unwrap();
+            ScmProcedure::from_scm(set).unwrap().call(&[gen]).unwrap();
+            let gen = ScmProcedure::from_scm(gen).unwrap();
+            assert_eq!(vm.write(gen.call(&[]).unwrap()), "\"misc-error\"");
+        });
+    }
+
+    #[test]
+    fn stream() {
+        init(|vm| {
+            let stream = vm.stream((0..).map(|n| n * n)).unwrap();
+            let take = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(lambda (s) ((@ (srfi srfi-41) stream->list) 4 s))",
+                )
+                .unwrap();
+            let squares = ScmProcedure::from_scm(take)
+                .unwrap()
+                .call(&[stream])
+                .unwrap();
+            assert_eq!(vm.write(squares), "(0 1 4 9)");
+        });
+    }
+
+    #[test]
+    fn stream_iter() {
+        init(|vm| {
+            let squares = vm.stream((0..).map(|n: i32| n * n)).unwrap();
+            let firsts: Vec<i32> = vm
+                .stream_iter(squares)
+                .unwrap()
+                .take(4)
+                .filter_map(|x| x.get())
+                .collect();
+            assert_eq!(firsts, vec![0, 1, 4, 9]);
+
+            let failing = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(use-modules (srfi srfi-41))
+                     (stream-cons 1 (stream-cons (error \"boom\") stream-nil))",
+                )
+                .unwrap();
+            let mut iter = vm.stream_iter(failing).unwrap();
+            assert_eq!(iter.next().and_then(|x| x.get::<i32>()), Some(1));
+            assert!(iter.next().is_none());
+            assert!(iter.error().is_some());
+
+            assert!(vm.stream_iter(1i32.to_scm(&vm)).is_err());
+        });
+    }
+}
@@ -0,0 +1,374 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use guile_sys::{
+    scm_t_weak_table_kind, scm_t_weak_table_kind_SCM_WEAK_TABLE_KIND_BOTH,
+    scm_t_weak_table_kind_SCM_WEAK_TABLE_KIND_KEY, scm_t_weak_table_kind_SCM_WEAK_TABLE_KIND_VALUE,
+    SCM, SCM_BOOL_T, SCM_EOL,
+};
+
+use crate::convert::to_raw;
+use crate::error::protect;
+use crate::scm::public_ref;
+use crate::{FromScm, GuileVM, Scm, ToScm};
+
+/// How a hash table compares keys. Guile tables don't record this, so a
+/// table has to be accessed the same way it was filled: `hashq-set!`
+/// entries can't be found with `hash-ref`, and vice versa.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashKind {
+    /// `eq?`, as `hashq-ref` and friends; right for symbols and keywords.
+    Eq,
+    /// `eqv?`, as `hashv-ref`; right for numbers and characters.
+    Eqv,
+    /// `equal?`, as `hash-ref`; right for strings and lists.
+    Equal,
+}
+
+type Ref = unsafe extern "C" fn(SCM, SCM, SCM) -> SCM;
+type SetX = unsafe extern "C" fn(SCM, SCM, SCM) -> SCM;
+type RemoveX = unsafe extern "C" fn(SCM, SCM) -> SCM;
+
+impl HashKind {
+    fn functions(self) -> (Ref, SetX, RemoveX) {
+        match self {
+            HashKind::Eq => (
+                guile_sys::scm_hashq_ref,
+                guile_sys::scm_hashq_set_x,
+                guile_sys::scm_hashq_remove_x,
+            ),
+            HashKind::Eqv => (
+                guile_sys::scm_hashv_ref,
+                guile_sys::scm_hashv_set_x,
+                guile_sys::scm_hashv_remove_x,
+            ),
+            HashKind::Equal => (
+                guile_sys::scm_hash_ref,
+                guile_sys::scm_hash_set_x,
+                guile_sys::scm_hash_remove_x,
+            ),
+        }
+    }
+}
+
+/// A Scheme hash table. Keys are compared with `equal?`, as
+/// `make-hash-table` tables usually are, unless a different `HashKind`
+/// is given.
+#[derive(Clone, Copy)]
+pub struct ScmHashTable<'vm> {
+    scm: Scm<'vm>,
+    kind: HashKind,
+}
+
+impl<'vm> ScmHashTable<'vm> {
+    pub fn new(vm: &'vm GuileVM) -> ScmHashTable<'vm> {
+        ScmHashTable::with_kind(vm, HashKind::Equal)
+    }
+
+    pub fn with_kind(vm: &'vm GuileVM, kind: HashKind) -> ScmHashTable<'vm> {
+        unsafe {
+            ScmHashTable {
+                scm: Scm::from_raw(vm, guile_sys::scm_c_make_hash_table(0)),
+                kind,
+            }
+        }
+    }
+
+    fn weak(
+        vm: &'vm GuileVM,
+        kind: HashKind,
+        weakness: scm_t_weak_table_kind,
+    ) -> ScmHashTable<'vm> {
+        unsafe {
+            ScmHashTable {
+                scm: Scm::from_raw(vm, guile_sys::scm_c_make_weak_table(0, weakness)),
+                kind,
+            }
+        }
+    }
+
+    /// A table whose entries disappear once their key is otherwise
+    /// unreachable, e.g. for attaching data to Scheme objects.
+    pub fn weak_keys(vm: &'vm GuileVM, kind: HashKind) -> ScmHashTable<'vm> {
+        ScmHashTable::weak(vm, kind, scm_t_weak_table_kind_SCM_WEAK_TABLE_KIND_KEY)
+    }
+
+    /// A table whose entries disappear once their value is otherwise
+    /// unreachable, e.g. for a cache of objects built on demand.
+    pub fn weak_values(vm: &'vm GuileVM, kind: HashKind) -> ScmHashTable<'vm> {
+        ScmHashTable::weak(vm, kind, scm_t_weak_table_kind_SCM_WEAK_TABLE_KIND_VALUE)
+    }
+
+    /// A table whose entries disappear once either their key or their
+    /// value is otherwise unreachable.
+    pub fn doubly_weak(vm: &'vm GuileVM, kind: HashKind) -> ScmHashTable<'vm> {
+        ScmHashTable::weak(vm, kind, scm_t_weak_table_kind_SCM_WEAK_TABLE_KIND_BOTH)
+    }
+
+    /// Returns `None` if `scm` is not a hash table.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmHashTable<'vm>> {
+        ScmHashTable::from_scm_with_kind(scm, HashKind::Equal)
+    }
+
+    /// Wrap a table that Scheme code fills with `hashq-set!` or
+    /// `hashv-set!`. Returns `None` if `scm` is not a hash table.
+    pub fn from_scm_with_kind(scm: Scm<'vm>, kind: HashKind) -> Option<ScmHashTable<'vm>> {
+        if guile_sys::scm_is_true(unsafe { guile_sys::scm_hash_table_p(scm.as_raw()) }) {
+            Some(ScmHashTable { scm, kind })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    pub fn kind(&self) -> HashKind {
+        self.kind
+    }
+
+    pub fn get(&self, key: impl ToScm) -> Option<Scm<'vm>> {
+        let (ref_, _, _) = self.kind.functions();
+        /* Weak tables don't support `hash-get-handle`, so tell a missing
+         * key apart with a default no stored value can be `eq?` to. */
+        unsafe {
+            let missing = guile_sys::scm_cons(SCM_EOL, SCM_EOL);
+            let value = ref_(self.scm.as_raw(), to_raw(&key), missing);
+            if guile_sys::scm_is_eq(value, missing) {
+                None
+            } else {
+                Some(self.scm.with_raw(value))
+            }
+        }
+    }
+
+    /// `get`, converted to `T`; `None` if the key is missing or the value
+    /// has the wrong type.
+    pub fn get_as<T: FromScm<'vm>>(&self, key: impl ToScm) -> Option<T> {
+        self.get(key).and_then(T::from_scm)
+    }
+
+    pub fn contains_key(&self, key: impl ToScm) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn insert(&self, key: impl ToScm, value: impl ToScm) {
+        let (_, set_x, _) = self.kind.functions();
+        unsafe { set_x(self.scm.as_raw(), to_raw(&key), to_raw(&value)) };
+    }
+
+    /// Remove `key`'s entry, returning its value if there was one.
+    pub fn remove(&self, key: impl ToScm) -> Option<Scm<'vm>> {
+        let (_, _, remove_x) = self.kind.functions();
+        let key = to_raw(&key);
+        let value = self.get(self.scm.with_raw(key));
+        unsafe { remove_x(self.scm.as_raw(), key) };
+        value
+    }
+
+    /// The entries, in no particular order. They're collected with
+    /// `hash-fold` up front, so the table may be modified while iterating.
+    ///
+    /// # Panics
+    ///
+    /// If `hash-fold` raises an error, which for a hash table only
+    /// happens when Guile runs out of memory.
+    pub fn iter(&self) -> ScmHashTableIter<'vm> {
+        let table = self.scm.as_raw();
+        let entries = unsafe {
+            protect(|| guile_sys::scm_hash_fold(public_ref("guile", "acons"), SCM_EOL, table))
+                .unwrap_or_else(|e| panic!("couldn't collect hash table entries: {}", e))
+        };
+        ScmHashTableIter {
+            rest: self.scm.with_raw(entries),
+            remaining: unsafe { guile_sys::scm_ilength(entries) }.max(0) as usize,
+        }
+    }
+
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        unsafe {
+            let always = guile_sys::scm_call_1(public_ref("guile", "const"), SCM_BOOL_T);
+            guile_sys::scm_to_uint64(guile_sys::scm_hash_count(self.scm.as_raw(), always)) as usize
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn for_each<F: FnMut(Scm<'vm>, Scm<'vm>)>(&self, mut f: F) {
+        for (key, value) in self.iter() {
+            f(key, value);
+        }
+    }
+}
+
+/// Iterator over a hash table's entries, see `ScmHashTable::iter`. Keep
+/// it on the stack: the entries are only kept alive through it.
+pub struct ScmHashTableIter<'vm> {
+    rest: Scm<'vm>,
+    remaining: usize,
+}
+
+impl<'vm> Iterator for ScmHashTableIter<'vm> {
+    type Item = (Scm<'vm>, Scm<'vm>);
+
+    fn next(&mut self) -> Option<(Scm<'vm>, Scm<'vm>)> {
+        unsafe {
+            let rest = self.rest.as_raw();
+            if guile_sys::scm_is_pair(rest) == 0 {
+                return None;
+            }
+            let entry = guile_sys::scm_car(rest);
+            self.rest = self.rest.with_raw(guile_sys::scm_cdr(rest));
+            self.remaining -= 1;
+            Some((
+                self.rest.with_raw(guile_sys::scm_car(entry)),
+                self.rest.with_raw(guile_sys::scm_cdr(entry)),
+            ))
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for ScmHashTableIter<'_> {}
+
+impl<'vm> IntoIterator for ScmHashTable<'vm> {
+    type Item = (Scm<'vm>, Scm<'vm>);
+    type IntoIter = ScmHashTableIter<'vm>;
+
+    fn into_iter(self) -> ScmHashTableIter<'vm> {
+        self.iter()
+    }
+}
+
+impl<'vm> IntoIterator for &ScmHashTable<'vm> {
+    type Item = (Scm<'vm>, Scm<'vm>);
+    type IntoIter = ScmHashTableIter<'vm>;
+
+    fn into_iter(self) -> ScmHashTableIter<'vm> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language, ScmSymbol};
+
+    #[test]
+    fn iterate() {
+        init(|vm| {
+            let table = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(let ((h (make-hash-table)))
+                       (hash-set! h \"a\" 1)
+                       (hash-set! h \"b\" 2)
+                       h)",
+                )
+                .unwrap();
+            let table = ScmHashTable::from_scm(table).unwrap();
+            table.insert("c".to_scm(&vm), 3.to_scm(&vm));
+            assert_eq!(i32::from_scm(table.get("b".to_scm(&vm)).unwrap()), Some(2));
+            assert!(table.get("z".to_scm(&vm)).is_none());
+
+            let mut entries: Vec<(String, i32)> = table
+                .iter()
+                .map(|(k, v)| (String::from_scm(k).unwrap(), i32::from_scm(v).unwrap()))
+                .collect();
+            entries.sort();
+            assert_eq!(
+                entries,
+                vec![
+                    ("a".to_string(), 1),
+                    ("b".to_string(), 2),
+                    ("c".to_string(), 3)
+                ]
+            );
+
+            assert_eq!(table.remove("a").and_then(|v| v.get::<i32>()), Some(1));
+            assert!(table.remove("a").is_none());
+            assert!(!table.contains_key("a"));
+
+            let mut sum = 0;
+            table.for_each(|_, v| sum += i32::from_scm(v).unwrap());
+            assert_eq!(sum, 5);
+
+            assert_eq!(table.len(), 2);
+            let entries = table.iter();
+            assert_eq!(entries.len(), 2);
+            for (key, _) in &table {
+                table.remove(key);
+            }
+            assert!(table.is_empty());
+            assert_eq!(entries.count(), 2);
+        });
+    }
+
+    #[test]
+    fn key_kinds() {
+        init(|vm| {
+            let table = ScmHashTable::with_kind(&vm, HashKind::Eqv);
+            table.insert(1.5f64, "x");
+            assert_eq!(table.get_as::<String>(1.5f64).as_deref(), Some("x"));
+            assert_eq!(table.get_as::<i32>(1.5f64), None);
+
+            let filled = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(let ((h (make-hash-table)))
+                       (hashq-set! h 'port 80)
+                       h)",
+                )
+                .unwrap();
+            let port = ScmSymbol::new(&vm, "port").as_scm();
+            let by_equal = ScmHashTable::from_scm(filled).unwrap();
+            assert_eq!(by_equal.kind(), HashKind::Equal);
+            let by_eq = ScmHashTable::from_scm_with_kind(filled, HashKind::Eq).unwrap();
+            assert_eq!(by_eq.get_as::<u16>(port), Some(80));
+        });
+    }
+
+    #[test]
+    fn weak_tables() {
+        init(|vm| {
+            let cache = ScmHashTable::weak_values(&vm, HashKind::Equal);
+            let value = ScmSymbol::new(&vm, "cached").as_scm();
+            cache.insert("key", value);
+            assert_eq!(cache.get("key").map(|v| v.as_raw()), Some(value.as_raw()));
+            assert!(ScmHashTable::from_scm(cache.as_scm()).is_some());
+            assert_eq!(cache.iter().count(), 1);
+            assert_eq!(
+                cache.remove("key").map(|v| v.as_raw()),
+                Some(value.as_raw())
+            );
+
+            let props = ScmHashTable::weak_keys(&vm, HashKind::Eq);
+            props.insert(value, 1i32);
+            assert_eq!(props.get_as::<i32>(value), Some(1));
+            let both = ScmHashTable::doubly_weak(&vm, HashKind::Eqv);
+            both.insert(1i32, value);
+            assert!(both.contains_key(1i32));
+        });
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::sync::mpsc::Sender;
+
+use guile_sys::{SCM, SCM_BOOL_T, SCM_UNSPECIFIED};
+
+use crate::closure::make_procedure;
+use crate::error::{protect, GuileError};
+use crate::{FromScm, GuileVM, Scm};
+
+/// Argument tuples a hook's arguments can be converted to. Only owned
+/// values can be converted, since they're sent to other threads.
+pub trait HookArgs: Sized {
+    /// Returns `None` if `args`, a list, has the wrong length or types.
+    fn from_args(args: Scm) -> Option<Self>;
+}
+
+impl HookArgs for () {
+    fn from_args(args: Scm) -> Option<()> {
+        if guile_sys::scm_is_null(args.as_raw()) {
+            Some(())
+        } else {
+            None
+        }
+    }
+}
+
+macro_rules! hook_args {
+    ($($ty:ident $var:ident),+) => {
+        impl<$($ty: for<'a> FromScm<'a>),+> HookArgs for ($($ty,)+) {
+            fn from_args(args: Scm) -> Option<Self> {
+                let mut rest = args.as_raw();
+                $(
+                    if unsafe { guile_sys::scm_is_pair(rest) } == 0 {
+                        return None;
+                    }
+                    let $var = $ty::from_scm(args.with_raw(unsafe { guile_sys::scm_car(rest) }))?;
+                    rest = unsafe { guile_sys::scm_cdr(rest) };
+                )+
+                if guile_sys::scm_is_null(rest) {
+                    Some(($($var,)+))
+                } else {
+                    None
+                }
+            }
+        }
+    };
+}
+
+hook_args!(A a);
+hook_args!(A a, B b);
+hook_args!(A a, B b, C c);
+hook_args!(A a, B b, C c, D d);
+hook_args!(A a, B b, C c, D d, E e);
+hook_args!(A a, B b, C c, D d, E e, F f);
+
+/// A procedure added to a hook by `GuileVM::forward_hook`. The hook must
+/// stay reachable from Scheme until the forwarder is removed.
+pub struct HookForwarder {
+    hook: SCM,
+    proc_: SCM,
+}
+
+impl GuileVM {
+    /// Send the arguments of every run of `hook` over `sender`, so threads
+    /// outside guile mode can react to it. Runs whose arguments don't
+    /// convert to `T` are skipped, as are runs after the receiver hung up.
+    pub fn forward_hook<T>(&self, hook: Scm, sender: Sender<T>) -> Result<HookForwarder, GuileError>
+    where
+        T: HookArgs + Send + 'static,
+    {
+        let hook = hook.as_raw();
+        unsafe {
+            let proc_ = make_procedure(
+                "hook-forwarder",
+                Box::new(move |args| {
+                    let vm = GuileVM::assume_guile_mode();
+                    if let Some(args) = T::from_args(Scm::from_raw(&vm, args)) {
+                        let _ = sender.send(args);
+                    }
+                    Ok(SCM_UNSPECIFIED)
+                }),
+            );
+            protect(|| guile_sys::scm_add_hook_x(hook, proc_, SCM_BOOL_T))?;
+            Ok(HookForwarder { hook, proc_ })
+        }
+    }
+
+    pub fn remove_hook_forwarder(&self, forwarder: HookForwarder) -> Result<(), GuileError> {
+        unsafe {
+            protect(|| guile_sys::scm_remove_hook_x(forwarder.hook, forwarder.proc_))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{init, Language};
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    fn forward_to_channel() {
+        init(|vm| {
+            let hook = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(define forwarded-hook (make-hook 2)) forwarded-hook",
+                )
+                .unwrap();
+            let (sender, receiver) = mpsc::channel::<(i32, String)>();
+            let forwarder = vm.forward_hook(hook, sender).unwrap();
+            vm.eval_language(
+                Language::Scheme,
+                "(run-hook forwarded-hook 1 \"one\")
+                 (run-hook forwarded-hook 'wrong \"type\")
+                 (run-hook forwarded-hook 2 \"two\")",
+            )
+            .unwrap();
+            vm.remove_hook_forwarder(forwarder).unwrap();
+            vm.eval_language(Language::Scheme, "(run-hook forwarded-hook 3 \"three\")")
+                .unwrap();
+
+            let received = thread::spawn(move || receiver.try_iter().collect::<Vec<_>>())
+                .join()
+                .unwrap();
+            assert_eq!(
+                received,
+                vec![(1, "one".to_string()), (2, "two".to_string())]
+            );
+        });
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::path::Path;
+
+use guile_sys::{SCM, SCM_UNDEFINED};
+use libc::c_int;
+
+use crate::error::{protect, GuileError};
+use crate::scm::public_ref;
+use crate::string;
+use crate::{GuileVM, ToScm};
+
+/// Locale categories for `setlocale`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LocaleCategory {
+    All,
+    Collate,
+    CType,
+    Messages,
+    Monetary,
+    Numeric,
+    Time,
+}
+
+impl LocaleCategory {
+    fn to_c_int(self) -> c_int {
+        match self {
+            LocaleCategory::All => libc::LC_ALL,
+            LocaleCategory::Collate => libc::LC_COLLATE,
+            LocaleCategory::CType => libc::LC_CTYPE,
+            LocaleCategory::Messages => libc::LC_MESSAGES,
+            LocaleCategory::Monetary => libc::LC_MONETARY,
+            LocaleCategory::Numeric => libc::LC_NUMERIC,
+            LocaleCategory::Time => libc::LC_TIME,
+        }
+    }
+}
+
+unsafe fn optional_string(s: Option<&str>) -> SCM {
+    s.map_or(SCM_UNDEFINED, |s| string::from_str(s))
+}
+
+/* The gettext procedures have no C API, so go through (guile). */
+unsafe fn call_guile(name: &str, args: &mut [SCM]) -> Result<String, GuileError> {
+    let result = protect(|| {
+        guile_sys::scm_call_n(public_ref("guile", name), args.as_mut_ptr(), args.len())
+    })?;
+    Ok(string::to_string(result))
+}
+
+/* Trailing SCM_UNDEFINED arguments mean "not given". */
+fn given(args: &mut [SCM]) -> &mut [SCM] {
+    let len = args
+        .iter()
+        .rposition(|&arg| !guile_sys::scm_is_eq(arg, SCM_UNDEFINED))
+        .map_or(0, |idx| idx + 1);
+    &mut args[..len]
+}
+
+/// Message catalogs are per process, so a domain bound here is also
+/// visible to gettext calls made from Rust, and vice versa.
+impl GuileVM {
+    /// Scheme's `setlocale`. An empty `locale` selects it from the
+    /// environment, as in C. Returns the new locale name.
+    pub fn setlocale(&self, category: LocaleCategory, locale: &str) -> Result<String, GuileError> {
+        unsafe {
+            let locale = string::from_str(locale);
+            let result = protect(|| {
+                guile_sys::scm_setlocale(guile_sys::scm_from_int32(category.to_c_int()), locale)
+            })?;
+            Ok(string::to_string(result))
+        }
+    }
+
+    /// Set the default text domain, returning it.
+    pub fn textdomain(&self, domain: &str) -> Result<String, GuileError> {
+        unsafe { call_guile("textdomain", &mut [string::from_str(domain)]) }
+    }
+
+    /// Tell gettext where to find the catalogs for `domain`.
+    pub fn bindtextdomain(&self, domain: &str, dir: &Path) -> Result<String, GuileError> {
+        let dir = dir.to_scm(self).as_raw();
+        unsafe { call_guile("bindtextdomain", &mut [string::from_str(domain), dir]) }
+    }
+
+    pub fn bind_textdomain_codeset(
+        &self,
+        domain: &str,
+        codeset: &str,
+    ) -> Result<String, GuileError> {
+        unsafe {
+            call_guile(
+                "bind-textdomain-codeset",
+                &mut [string::from_str(domain), string::from_str(codeset)],
+            )
+        }
+    }
+
+    /// Translate `msgid` in `domain`, or the default text domain. Returns
+    /// `msgid` itself if there is no translation.
+    pub fn gettext(&self, msgid: &str, domain: Option<&str>) -> Result<String, GuileError> {
+        unsafe {
+            let mut args = [string::from_str(msgid), optional_string(domain)];
+            call_guile("gettext", given(&mut args))
+        }
+    }
+
+    /// Translate a message with a plural form, picked according to `n`.
+    pub fn ngettext(
+        &self,
+        msgid: &str,
+        msgid_plural: &str,
+        n: u64,
+        domain: Option<&str>,
+    ) -> Result<String, GuileError> {
+        unsafe {
+            let mut args = [
+                string::from_str(msgid),
+                string::from_str(msgid_plural),
+                guile_sys::scm_from_uint64(n),
+                optional_string(domain),
+            ];
+            call_guile("ngettext", given(&mut args))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::init;
+
+    #[test]
+    fn untranslated() {
+        init(|vm| {
+            vm.textdomain("guile-rs-test").unwrap();
+            assert_eq!(vm.gettext("Hello", None).unwrap(), "Hello");
+            assert_eq!(
+                vm.ngettext("one file", "many files", 2, Some("guile-rs-test"))
+                    .unwrap(),
+                "many files"
+            );
+        });
+    }
+
+    #[test]
+    fn c_locale() {
+        init(|vm| {
+            assert_eq!(vm.setlocale(LocaleCategory::Messages, "C").unwrap(), "C");
+        });
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use guile_sys::SCM;
+
+use crate::error::{protect, GuileError};
+use crate::scm::read_all;
+use crate::{GuileVM, Scm};
+
+/* Returns (interrupt . run). `interrupt` is queued as an async on the
+ * evaluating thread; it only aborts if that thread is still inside
+ * `run`, so a late interrupt can't hit a later evaluation. `run` returns
+ * (value) or #f if it was interrupted. */
+const MAKE_INTERRUPTIBLE: &str = "
+(lambda ()
+  (let ((tag (make-prompt-tag \"interrupt\"))
+        (running (make-fluid #f)))
+    (cons (lambda ()
+            (when (fluid-ref running)
+              (abort-to-prompt tag)))
+          (lambda (exp)
+            (call-with-prompt tag
+              (lambda ()
+                (with-fluids ((running #t))
+                  (list (primitive-eval exp))))
+              (lambda (k) #f))))))
+";
+
+impl GuileVM {
+    /// Evaluate `code`, aborting it with `GuileError::Interrupted` if it's
+    /// still running at `deadline`.
+    ///
+    /// A watchdog thread interrupts the evaluation with a system async,
+    /// so code is stopped at its next safe point: the back-edge of a
+    /// loop, a procedure call or a blocking system call. Unlike
+    /// `Limits::time`, this doesn't install a timer signal handler.
+    pub fn eval_with_deadline(&self, code: &str, deadline: Instant) -> Result<Scm<'_>, GuileError> {
+        unsafe {
+            let interruptible = protect(|| {
+                guile_sys::scm_call_0(guile_sys::scm_primitive_eval(guile_sys::scm_car(read_all(
+                    MAKE_INTERRUPTIBLE,
+                ))))
+            })?;
+            let interrupt = guile_sys::scm_car(interruptible) as usize;
+            let thread = guile_sys::scm_current_thread() as usize;
+
+            let finished = Arc::new((Mutex::new(false), Condvar::new()));
+            let watchdog = {
+                let finished = Arc::clone(&finished);
+                thread::spawn(move || {
+                    let (lock, cvar) = &*finished;
+                    let mut done = lock.lock().unwrap();
+                    loop {
+                        if *done {
+                            return;
+                        }
+                        let now = Instant::now();
+                        if now >= deadline {
+                            break;
+                        }
+                        done = cvar.wait_timeout(done, deadline - now).unwrap().0;
+                    }
+                    drop(done);
+                    crate::init(|_| {
+                        guile_sys::scm_system_async_mark_for_thread(
+                            interrupt as SCM,
+                            thread as SCM,
+                        );
+                    });
+                })
+            };
+
+            let result = protect(|| {
+                let exp = guile_sys::scm_cons(guile_sys::scm_sym_begin, read_all(code));
+                guile_sys::scm_call_1(guile_sys::scm_cdr(interruptible), exp)
+            });
+
+            *finished.0.lock().unwrap() = true;
+            finished.1.notify_one();
+            watchdog.join().unwrap();
+            /* The watchdog may have used `interrupt` up to now. */
+            guile_sys::scm_remember_upto_here_1(interruptible);
+
+            let result = result?;
+            if guile_sys::scm_is_false(result) {
+                Err(GuileError::Interrupted)
+            } else {
+                Ok(Scm::from_raw(self, guile_sys::scm_car(result)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::init;
+    use std::time::Duration;
+
+    #[test]
+    fn interrupted() {
+        init(|vm| {
+            let deadline = Instant::now() + Duration::from_millis(50);
+            let result = vm.eval_with_deadline("(let loop () (loop))", deadline);
+            assert!(matches!(result, Err(GuileError::Interrupted)));
+        });
+    }
+
+    #[test]
+    fn finishes_in_time() {
+        init(|vm| {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            let result = vm
+                .eval_with_deadline("(define x 40) (+ x 2)", deadline)
+                .unwrap();
+            assert_eq!(unsafe { guile_sys::scm_to_int64(result.as_raw()) }, 42);
+        });
+    }
+}
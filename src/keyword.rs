@@ -0,0 +1,106 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::fmt;
+
+use crate::scm;
+use crate::{GuileVM, Scm, ScmSymbol};
+
+/// A Scheme keyword, e.g. `#:port`, for passing keyword arguments.
+#[derive(Clone, Copy)]
+pub struct ScmKeyword<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> ScmKeyword<'vm> {
+    /// `name` is given without the `#:` prefix.
+    pub fn new(vm: &'vm GuileVM, name: &str) -> ScmKeyword<'vm> {
+        unsafe {
+            ScmKeyword {
+                scm: Scm::from_raw(vm, scm::keyword(name)),
+            }
+        }
+    }
+
+    pub fn from_symbol(symbol: ScmSymbol<'vm>) -> ScmKeyword<'vm> {
+        let scm = symbol.as_scm();
+        ScmKeyword {
+            scm: scm.with_raw(unsafe { guile_sys::scm_symbol_to_keyword(scm.as_raw()) }),
+        }
+    }
+
+    /// Returns `None` if `scm` is not a keyword.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmKeyword<'vm>> {
+        if unsafe { guile_sys::scm_is_keyword(scm.as_raw()) } != 0 {
+            Some(ScmKeyword { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    pub fn to_symbol(&self) -> ScmSymbol<'vm> {
+        let raw = unsafe { guile_sys::scm_keyword_to_symbol(self.scm.as_raw()) };
+        ScmSymbol::from_scm(self.scm.with_raw(raw)).unwrap()
+    }
+
+    /// The name without the `#:` prefix.
+    pub fn name(&self) -> String {
+        self.to_symbol().name()
+    }
+}
+
+impl<'vm> fmt::Display for ScmKeyword<'vm> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#:{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language, ScmProcedure, ToScm};
+
+    #[test]
+    fn keyword_arguments() {
+        init(|vm| {
+            let port = ScmKeyword::new(&vm, "port");
+            assert_eq!(port.name(), "port");
+            assert_eq!(port.to_string(), "#:port");
+            let symbol = port.to_symbol();
+            assert!(ScmKeyword::from_symbol(symbol)
+                .as_scm()
+                .is_eq(port.as_scm()));
+            assert!(ScmKeyword::from_scm(symbol.as_scm()).is_none());
+
+            let f = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(use-modules (ice-9 optargs))
+                     (lambda* (#:key (scale 1)) (* 10 scale))",
+                )
+                .unwrap();
+            let f = ScmProcedure::from_scm(f).unwrap();
+            let scale = ScmKeyword::new(&vm, "scale").as_scm();
+            let result = f.call(&[scale, 3i32.to_scm(&vm)]).unwrap();
+            assert_eq!(result.get::<i32>(), Some(30));
+        });
+    }
+}
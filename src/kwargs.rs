@@ -0,0 +1,177 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::ffi::CString;
+
+use guile_sys::{scm_t_keyword_arguments_flags, SCM, SCM_UNDEFINED};
+use libc::c_char;
+
+use crate::error::{protect, GuileError};
+use crate::scm;
+use crate::{FromScm, GuileVM, ScmList};
+
+/// Structs that a procedure's keyword arguments, like
+/// `#:width 80 #:color 'red`, can be parsed into. Derive it:
+///
+/// ```ignore
+/// #[derive(FromKeywordArgs)]
+/// struct Style<'vm> {
+///     #[guile(default = 80)]
+///     width: i64,
+///     color: Option<Scm<'vm>>,
+///     #[guile(name = "bold?", default)]
+///     bold: bool,
+/// }
+/// ```
+///
+/// The keyword is the field name with `_` turned into `-`. `Option`
+/// fields are `None` when left out, fields marked `default` get a
+/// default, and leaving out any other field is an error. Unknown
+/// keywords are errors too, unless the struct is marked
+/// `#[guile(allow_other_keys)]`.
+pub trait FromKeywordArgs<'vm>: Sized {
+    /// Parse `args`, a list of keywords and values, for the procedure
+    /// named `subr`.
+    fn from_keyword_args(subr: &str, args: ScmList<'vm>) -> Result<Self, GuileError>;
+}
+
+/* The rest of this file backs `#[derive(FromKeywordArgs)]`: the derived
+ * impl passes its keywords and slots to `scm_c_bind_keyword_arguments`
+ * itself, since a variadic call can't be built at runtime. */
+
+#[doc(hidden)]
+pub unsafe fn bind_keyword_arguments<F>(
+    subr: &str,
+    args: ScmList,
+    allow_other_keys: bool,
+    bind: F,
+) -> Result<(), GuileError>
+where
+    F: FnOnce(*const c_char, SCM, scm_t_keyword_arguments_flags),
+{
+    let subr = CString::new(subr).unwrap();
+    let flags = if allow_other_keys {
+        guile_sys::scm_keyword_arguments_flags_SCM_ALLOW_OTHER_KEYS
+    } else {
+        0
+    };
+    let rest = args.as_scm().as_raw();
+    protect(|| bind(subr.as_ptr(), rest, flags))
+}
+
+/// The value bound to `#:keyword`, or `None` if it was left out.
+#[doc(hidden)]
+pub unsafe fn keyword_value<'vm, T: FromScm<'vm>>(
+    args: ScmList<'vm>,
+    keyword: &str,
+    raw: SCM,
+) -> Result<Option<T>, GuileError> {
+    if guile_sys::scm_is_eq(raw, SCM_UNDEFINED) {
+        return Ok(None);
+    }
+    let value = args.as_scm().with_raw(raw);
+    match T::from_scm(value) {
+        Some(value) => Ok(Some(value)),
+        None => Err(GuileError::Throw {
+            key: String::from("wrong-type-arg"),
+            message: format!(
+                "Wrong type argument for #:{}: {}",
+                keyword,
+                GuileVM::assume_guile_mode().write(value)
+            ),
+        }),
+    }
+}
+
+#[doc(hidden)]
+pub fn missing_keyword(keyword: &str) -> GuileError {
+    GuileError::Throw {
+        key: String::from("keyword-argument-error"),
+        message: format!("Missing keyword argument #:{}", keyword),
+    }
+}
+
+#[doc(hidden)]
+pub unsafe fn keyword(name: &str) -> SCM {
+    scm::keyword(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{guile_fn, init, FromKeywordArgs, Language, Rest, Scm};
+
+    #[derive(Debug, FromKeywordArgs)]
+    struct Style<'vm> {
+        #[guile(default = 80)]
+        width: i64,
+        color: Option<Scm<'vm>>,
+        #[guile(name = "bold?", default)]
+        bold: bool,
+    }
+
+    #[derive(FromKeywordArgs)]
+    #[guile(allow_other_keys)]
+    struct Label {
+        text: String,
+    }
+
+    #[guile_fn]
+    fn label_length(args: Rest) -> Result<usize, GuileError> {
+        let label = Label::from_keyword_args("label-length", args.as_list())?;
+        Ok(label.text.len())
+    }
+
+    #[test]
+    fn defaults_and_options() {
+        init(|vm| {
+            let parse = |code| {
+                let args = vm.eval_language(Language::Scheme, code).unwrap();
+                Style::from_keyword_args("draw", ScmList::from_scm(args).unwrap())
+            };
+            let style = parse("'(#:color red #:bold? #t)").unwrap();
+            assert_eq!(style.width, 80);
+            assert_eq!(vm.write(style.color.unwrap()), "red");
+            assert!(style.bold);
+
+            let style = parse("'(#:width 100)").unwrap();
+            assert_eq!(style.width, 100);
+            assert!(style.color.is_none());
+            assert!(!style.bold);
+
+            let err = parse("'(#:size 3)").unwrap_err();
+            assert_eq!(err.key(), "keyword-argument-error");
+            assert_eq!(
+                parse("'(#:width wide)").unwrap_err().key(),
+                "wrong-type-arg"
+            );
+        });
+    }
+
+    #[test]
+    fn from_scheme_call() {
+        init(|vm| {
+            vm.define_guile_fn::<label_length>();
+            let eval = |code| vm.eval_language(Language::Scheme, code);
+            let len = eval("(label-length #:text \"hello\" #:ignored 1)").unwrap();
+            assert_eq!(len.get::<usize>(), Some(5));
+            let err = eval("(label-length)").unwrap_err();
+            assert_eq!(err.key(), "keyword-argument-error");
+            assert!(err.to_string().contains("#:text"));
+        });
+    }
+}
@@ -17,11 +17,143 @@
 // <http://www.gnu.org/licenses/>.
 extern crate guile_sys;
 extern crate libc;
+/* So the code `#[guile_fn]` expands to can say `::guile` here too. */
+extern crate self as guile;
 
 use libc::{c_char, c_void};
 use std::ffi;
+use std::marker::PhantomData;
 
-pub struct GuileVM {}
+mod alist;
+mod array;
+mod atomic;
+mod bitvector;
+mod builder;
+mod bytevector;
+mod callbacks;
+mod character;
+mod closure;
+mod collections;
+mod compile;
+mod complex;
+#[cfg(feature = "serde")]
+pub mod config;
+mod convert;
+mod coverage;
+#[cfg(feature = "serde")]
+mod de;
+mod display;
+mod env;
+mod error;
+mod features;
+mod generator;
+mod hashtable;
+mod hooks;
+mod i18n;
+mod interrupt;
+mod keyword;
+mod kwargs;
+mod limits;
+#[cfg(feature = "rustyline")]
+mod line_editor;
+mod list;
+#[doc(hidden)]
+pub mod literal;
+#[cfg(feature = "log")]
+mod logging;
+mod module;
+#[cfg(feature = "ndarray")]
+mod ndarray;
+mod number;
+mod pair;
+mod plugins;
+mod pointer;
+mod posix;
+pub mod prelude;
+mod procedure;
+mod promise;
+mod protected;
+#[cfg(feature = "num")]
+mod rational;
+mod repl;
+mod sandbox;
+mod scm;
+mod sexp;
+mod statprof;
+mod string;
+mod structs;
+mod subr;
+mod symbol;
+mod syntax;
+mod time;
+mod trace;
+mod uniform;
+mod vector;
+
+pub use alist::ScmAlist;
+pub use array::{ArrayDim, ArraySlice, ArraySliceMut, ScmArray};
+pub use atomic::ScmAtomicBox;
+pub use bitvector::{ScmBitvector, ScmBitvectorIter};
+pub use builder::{Builder, LocaleSetup};
+pub use bytevector::ScmBytevector;
+pub use callbacks::{CallbackArgs, CallbackError, CallbackRegistry};
+pub use character::ScmChar;
+pub use collections::{HashTable, Vector};
+pub use compile::Language;
+pub use complex::Complex64;
+pub use convert::{ConversionError, FromScm, Latin1, Lossless, Maybe, ToScm, TryFromScm};
+pub use coverage::CoverageData;
+#[cfg(feature = "serde")]
+pub use de::{from_scm, DeserializeError};
+pub use error::{ErrorMap, GuileError};
+pub use features::{features, Features};
+pub use generator::ScmStreamIter;
+pub use guile_macros::{guile_fn, FromKeywordArgs};
+pub use hashtable::{HashKind, ScmHashTable, ScmHashTableIter};
+pub use hooks::{HookArgs, HookForwarder};
+pub use i18n::LocaleCategory;
+pub use keyword::ScmKeyword;
+pub use kwargs::FromKeywordArgs;
+pub use limits::Limits;
+#[cfg(feature = "rustyline")]
+pub use line_editor::LineEditor;
+pub use list::{ListError, ListKind, ScmList, ScmListIter};
+pub use module::ScmModule;
+#[cfg(feature = "ndarray")]
+pub use ndarray::NdArrayView;
+pub use number::ScmNumber;
+pub use plugins::{Plugin, PluginError, PluginManager};
+pub use pointer::ScmPointer;
+pub use posix::{FileKind, Stat, Uname, WaitStatus};
+pub use procedure::{Arity, ScmProcedure, TypedProc};
+pub use promise::ScmPromise;
+pub use protected::ProtectedScm;
+pub use repl::{Completer, Interaction, Repl};
+pub use sandbox::{Bindings, SandboxOptions};
+pub use scm::Scm;
+pub use sexp::OwnedSexp;
+pub use statprof::{ProcedureSamples, ProfileReport};
+pub use string::{Encoding, InvalidSequence, ScmString, StringChars, StringCharsIter};
+pub use structs::{ScmStruct, ScmVtable};
+pub use subr::{subr_body, GuileFn, Rest, Subr, SubrArgs, SubrReturn};
+pub use symbol::ScmSymbol;
+pub use syntax::SyntaxRules;
+pub use time::Srfi19;
+pub use trace::{Frame, VmEngine, VmHook, VmHookHandle};
+pub use uniform::{ScmUniformVector, UniformElement};
+pub use vector::{ScmVector, ScmVectorElements, ScmVectorElementsMut, ScmVectorIter};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::kwargs::{bind_keyword_arguments, keyword, keyword_value, missing_keyword};
+    pub use guile_sys::{scm_c_bind_keyword_arguments, SCM, SCM_UNDEFINED};
+}
+
+/// Handle to guile mode on the current thread. Not `Send`: Scheme
+/// values are only valid on the thread that entered guile mode.
+pub struct GuileVM {
+    _not_send: PhantomData<*mut ()>,
+}
 
 pub fn init<F>(func: F)
 where
@@ -36,6 +168,14 @@ where
 }
 
 impl GuileVM {
+    /// A handle for a thread that is known to be in guile mode already,
+    /// e.g. inside a callback from Scheme.
+    pub(crate) unsafe fn assume_guile_mode() -> GuileVM {
+        GuileVM {
+            _not_send: PhantomData,
+        }
+    }
+
     pub fn shell(&self, args: Vec<String>) {
         unsafe {
             let mut argv: Vec<*mut c_char> = args
@@ -54,7 +194,7 @@ where
 {
     let callback = data as *mut F;
 
-    let vm = GuileVM {};
+    let vm = GuileVM::assume_guile_mode();
 
     (*callback)(vm);
 
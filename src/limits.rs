@@ -0,0 +1,142 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::time::Duration;
+
+use guile_sys::{SCM_BOOL_F, SCM_EOL};
+
+use crate::error::{protect, GuileError};
+use crate::scm::read_all;
+use crate::{GuileVM, Scm};
+
+/// Time and allocation budgets for an evaluation. Exceeding one fails
+/// with `GuileError::TimeLimitExceeded` or
+/// `GuileError::AllocationLimitExceeded`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    time: Option<Duration>,
+    allocation: Option<u64>,
+}
+
+impl Limits {
+    /// No limits.
+    pub fn new() -> Limits {
+        Limits::default()
+    }
+
+    /// Interrupt the evaluation after `limit` of wall-clock time.
+    pub fn time(mut self, limit: Duration) -> Limits {
+        self.time = Some(limit);
+        self
+    }
+
+    /// Interrupt the evaluation once it has allocated about `bytes`.
+    pub fn allocation(mut self, bytes: u64) -> Limits {
+        self.allocation = Some(bytes);
+        self
+    }
+
+    pub(crate) fn time_limit(&self) -> Option<Duration> {
+        self.time
+    }
+
+    pub(crate) fn allocation_limit(&self) -> Option<u64> {
+        self.allocation
+    }
+}
+
+/* Raises the same errors `call-with-time-and-allocation-limits` does,
+ * but lets either limit be #f. */
+const CALL_WITH_LIMITS: &str = "
+(lambda (thunk time-limit allocation-limit)
+  (define (limited thunk limit call-with-limit subr)
+    (if limit
+        (lambda ()
+          (call-with-limit limit thunk
+            (lambda ()
+              (scm-error 'limit-exceeded subr \"Limit exceeded\" '() #f))))
+        thunk))
+  ((limited (limited thunk allocation-limit
+                     (@ (ice-9 sandbox) call-with-allocation-limit)
+                     \"with-allocation-limit\")
+            time-limit
+            (@ (ice-9 sandbox) call-with-time-limit)
+            \"with-time-limit\")))
+";
+
+impl GuileVM {
+    /// Evaluate `code` in the current module, interrupting it if it
+    /// exceeds `limits`, via `call-with-time-limit` and
+    /// `call-with-allocation-limit` from `(ice-9 sandbox)`.
+    ///
+    /// Unlike `eval_sandboxed`, the code has access to every binding.
+    pub fn eval_limited(&self, code: &str, limits: &Limits) -> Result<Scm<'_>, GuileError> {
+        unsafe {
+            let result = protect(|| {
+                let call_with_limits =
+                    guile_sys::scm_primitive_eval(guile_sys::scm_car(read_all(CALL_WITH_LIMITS)));
+                let body = guile_sys::scm_cons(SCM_EOL, read_all(code));
+                let thunk = guile_sys::scm_primitive_eval(guile_sys::scm_cons(
+                    guile_sys::scm_sym_lambda,
+                    body,
+                ));
+                let time = limits.time.map_or(SCM_BOOL_F, |time| {
+                    guile_sys::scm_from_double(time.as_secs_f64())
+                });
+                let allocation = limits
+                    .allocation
+                    .map_or(SCM_BOOL_F, |bytes| guile_sys::scm_from_uint64(bytes));
+                guile_sys::scm_call_3(call_with_limits, thunk, time, allocation)
+            })?;
+            Ok(Scm::from_raw(self, result))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::init;
+
+    #[test]
+    fn within_limits() {
+        init(|vm| {
+            let limits = Limits::new()
+                .time(Duration::from_secs(5))
+                .allocation(10_000_000);
+            assert!(vm.eval_limited("(+ 1 2)", &limits).is_ok());
+        });
+    }
+
+    #[test]
+    fn time_limit() {
+        init(|vm| {
+            let limits = Limits::new().time(Duration::from_millis(50));
+            let result = vm.eval_limited("(let loop () (loop))", &limits);
+            assert!(matches!(result, Err(GuileError::TimeLimitExceeded)));
+        });
+    }
+
+    #[test]
+    fn allocation_limit() {
+        init(|vm| {
+            let limits = Limits::new().allocation(100_000);
+            let result = vm.eval_limited("(make-list 1000000 'x)", &limits);
+            assert!(matches!(result, Err(GuileError::AllocationLimitExceeded)));
+        });
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::path::{Path, PathBuf};
+
+use rustyline::completion::Completer as RustylineCompleter;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Editor, Helper};
+
+use crate::{Completer, GuileError, GuileVM, Repl};
+
+/// Whether `input` ends inside a string or an unclosed list.
+fn incomplete(input: &str) -> bool {
+    let mut depth = 0i32;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ';' => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            '#' if chars.as_str().starts_with('\\') => {
+                chars.nth(1);
+            }
+            '"' => loop {
+                match chars.next() {
+                    Some('\\') => {
+                        chars.next();
+                    }
+                    Some('"') => break,
+                    Some(_) => {}
+                    None => return true,
+                }
+            },
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+/* Characters that end an identifier when looking back from the cursor. */
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "()[]'`,\"".contains(c)
+}
+
+struct SchemeHelper;
+
+impl RustylineCompleter for SchemeHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos].rfind(is_delimiter).map_or(0, |idx| idx + 1);
+        let completer = Completer { extra: None };
+        Ok((start, completer.complete(&line[start..pos])))
+    }
+}
+
+impl Hinter for SchemeHelper {
+    type Hint = String;
+}
+
+impl Highlighter for SchemeHelper {}
+
+impl Validator for SchemeHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if incomplete(ctx.input()) {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Helper for SchemeHelper {}
+
+/// A rustyline editor for Scheme input, with completion of bound names,
+/// multi-line editing of unbalanced expressions and optional persistent
+/// history. Works whether or not Guile was built with readline.
+pub struct LineEditor {
+    editor: Editor<SchemeHelper, DefaultHistory>,
+    history: Option<PathBuf>,
+}
+
+impl LineEditor {
+    pub fn new() -> rustyline::Result<LineEditor> {
+        let mut editor = Editor::new()?;
+        editor.set_helper(Some(SchemeHelper));
+        Ok(LineEditor {
+            editor,
+            history: None,
+        })
+    }
+
+    /// Load history from `path`, if it exists, and save it there on
+    /// `save_history`.
+    pub fn history_file<P: Into<PathBuf>>(mut self, path: P) -> LineEditor {
+        let path = path.into();
+        let _ = self.editor.load_history(&path);
+        self.history = Some(path);
+        self
+    }
+
+    /// Read one (possibly multi-line) entry. Ctrl-C abandons the entry
+    /// and returns an empty line; Ctrl-D returns `None`.
+    pub fn read_line(&mut self, prompt: &str) -> Option<String> {
+        match self.editor.readline(prompt) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    let _ = self.editor.add_history_entry(line.as_str());
+                }
+                Some(line)
+            }
+            Err(ReadlineError::Interrupted) => Some(String::new()),
+            Err(_) => None,
+        }
+    }
+
+    pub fn save_history(&mut self) -> rustyline::Result<()> {
+        match &self.history {
+            Some(path) => self.editor.save_history(path),
+            None => Ok(()),
+        }
+    }
+}
+
+impl GuileVM {
+    /// Run an interactive REPL on the terminal, edited with rustyline
+    /// instead of Guile's readline support. History is kept in `history`,
+    /// if given.
+    pub fn interactive_repl(&self, history: Option<&Path>) -> Result<(), GuileError> {
+        let mut editor = LineEditor::new().map_err(|err| GuileError::Throw {
+            key: String::from("rustyline-error"),
+            message: err.to_string(),
+        })?;
+        if let Some(path) = history {
+            editor = editor.history_file(path);
+        }
+        Repl::new(|prompt, _| editor.read_line(prompt)).run(self)?;
+        let _ = editor.save_history();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn incomplete_input() {
+        assert!(!incomplete("(define x 1)"));
+        assert!(incomplete("(define (f x)\n  (+ x"));
+        assert!(incomplete("(display \"a ) b"));
+        assert!(!incomplete("(display \"a ( b\")"));
+        assert!(!incomplete("(list #\\( 1)"));
+        assert!(!incomplete("(+ 1 ; unclosed (\n 2)"));
+    }
+}
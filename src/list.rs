@@ -0,0 +1,334 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::error::Error;
+use std::fmt;
+
+use guile_sys::{SCM, SCM_EOL};
+
+use crate::error::protect;
+use crate::scm::list_from_raw;
+use crate::{GuileVM, Scm, ToScm};
+
+/// How a walk over a list that wasn't proper ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListError {
+    /// The last cdr wasn't `'()`.
+    Improper,
+    /// The cdrs loop back on themselves.
+    Circular,
+}
+
+impl fmt::Display for ListError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ListError::Improper => write!(f, "improper list"),
+            ListError::Circular => write!(f, "circular list"),
+        }
+    }
+}
+
+impl Error for ListError {}
+
+/// The shape of a chain of pairs, from `Scm::classify_list`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ListKind<'vm> {
+    /// A proper list of this many elements; `'()` is `Proper(0)`.
+    Proper(usize),
+    /// `len` pairs ending in a non-`'()` `tail`. Any value that isn't a
+    /// pair or `'()` is `Dotted { len: 0, tail: value }`.
+    Dotted { len: usize, tail: Scm<'vm> },
+    /// The cdrs loop back on themselves.
+    Circular,
+}
+
+impl<'vm> Scm<'vm> {
+    /// Tell proper, dotted and circular lists apart without looping
+    /// forever; `scm_ilength` returns -1 for both of the latter.
+    pub fn classify_list(&self) -> ListKind<'vm> {
+        let mut iter = ScmListIter::new(*self);
+        let len = iter.by_ref().count();
+        match iter.error {
+            None => ListKind::Proper(len),
+            Some(ListError::Improper) => ListKind::Dotted {
+                len,
+                tail: iter.rest,
+            },
+            Some(ListError::Circular) => ListKind::Circular,
+        }
+    }
+}
+
+/// A proper Scheme list.
+#[derive(Clone, Copy)]
+pub struct ScmList<'vm> {
+    head: Scm<'vm>,
+}
+
+impl<'vm> ScmList<'vm> {
+    /// The empty list.
+    pub fn new(vm: &'vm GuileVM) -> ScmList<'vm> {
+        ScmList { head: Scm::eol(vm) }
+    }
+
+    pub fn from_iter<I>(vm: &'vm GuileVM, items: I) -> ScmList<'vm>
+    where
+        I: IntoIterator,
+        I::Item: ToScm,
+    {
+        let raw = unsafe { list_from_raw(items.into_iter().map(|i| i.to_scm(vm).as_raw())) };
+        ScmList {
+            head: unsafe { Scm::from_raw(vm, raw) },
+        }
+    }
+
+    /// Returns `None` unless `scm` is a proper list.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmList<'vm>> {
+        if unsafe { guile_sys::scm_ilength(scm.as_raw()) } >= 0 {
+            Some(ScmList { head: scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.head
+    }
+
+    /// Number of elements. Scheme code may have made the list improper
+    /// since it was wrapped; that counts the pairs up to the problem.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_null()
+    }
+
+    /// Append `value` at the end, mutating the list in place. This walks
+    /// the list each time, since copies of it (or Scheme code) may have
+    /// appended to it since the last push. Fails, leaving the list alone,
+    /// if Scheme code has made it improper or circular.
+    pub fn push(&mut self, value: Scm<'vm>) -> Result<(), ListError> {
+        unsafe {
+            let pair = guile_sys::scm_cons(value.as_raw(), SCM_EOL);
+            if self.head.is_null() {
+                self.head = self.head.with_raw(pair);
+                return Ok(());
+            }
+            let head = self.head.as_raw();
+            /* `last-pair` throws on a cycle. */
+            let last =
+                protect(|| guile_sys::scm_last_pair(head)).map_err(|_| ListError::Circular)?;
+            if !guile_sys::scm_is_null(guile_sys::scm_cdr(last)) {
+                return Err(ListError::Improper);
+            }
+            guile_sys::scm_set_cdr_x(last, pair);
+            Ok(())
+        }
+    }
+
+    pub fn iter(&self) -> ScmListIter<'vm> {
+        ScmListIter::new(self.head)
+    }
+}
+
+/// Collecting needs values that are already `Scm`s, since there's no VM
+/// to convert with: `.map(|x| x.to_scm(&vm)).collect::<ScmList>()`.
+impl<'vm> FromIterator<Scm<'vm>> for ScmList<'vm> {
+    fn from_iter<I: IntoIterator<Item = Scm<'vm>>>(items: I) -> ScmList<'vm> {
+        let raw = unsafe { list_from_raw(items.into_iter().map(|i| i.as_raw())) };
+        ScmList {
+            head: Scm::immediate(raw),
+        }
+    }
+}
+
+impl<'vm> IntoIterator for ScmList<'vm> {
+    type Item = Scm<'vm>;
+    type IntoIter = ScmListIter<'vm>;
+
+    fn into_iter(self) -> ScmListIter<'vm> {
+        self.iter()
+    }
+}
+
+impl<'vm> IntoIterator for &ScmList<'vm> {
+    type Item = Scm<'vm>;
+    type IntoIter = ScmListIter<'vm>;
+
+    fn into_iter(self) -> ScmListIter<'vm> {
+        self.iter()
+    }
+}
+
+/// Walks the cdrs of a list, stopping early (and recording why) at an
+/// improper tail or a cycle.
+pub struct ScmListIter<'vm> {
+    rest: Scm<'vm>,
+    /// Trails `rest` at half speed; meeting it means a cycle.
+    slow: SCM,
+    move_slow: bool,
+    error: Option<ListError>,
+}
+
+impl<'vm> ScmListIter<'vm> {
+    pub(crate) fn new(list: Scm<'vm>) -> ScmListIter<'vm> {
+        ScmListIter {
+            rest: list,
+            slow: list.as_raw(),
+            move_slow: false,
+            error: None,
+        }
+    }
+
+    /// Why the walk stopped early, once it has; `None` for a proper list.
+    pub fn error(&self) -> Option<ListError> {
+        self.error
+    }
+}
+
+impl<'vm> Iterator for ScmListIter<'vm> {
+    type Item = Scm<'vm>;
+
+    fn next(&mut self) -> Option<Scm<'vm>> {
+        if self.error.is_some() || self.rest.is_null() {
+            return None;
+        }
+        if !self.rest.is_pair() {
+            self.error = Some(ListError::Improper);
+            return None;
+        }
+        unsafe {
+            let raw = self.rest.as_raw();
+            let item = self.rest.with_raw(guile_sys::scm_car(raw));
+            self.rest = self.rest.with_raw(guile_sys::scm_cdr(raw));
+            if self.move_slow {
+                self.slow = guile_sys::scm_cdr(self.slow);
+            }
+            self.move_slow = !self.move_slow;
+            if guile_sys::scm_is_eq(self.rest.as_raw(), self.slow) {
+                self.error = Some(ListError::Circular);
+            }
+            Some(item)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language};
+
+    #[test]
+    fn build_and_walk() {
+        init(|vm| {
+            let mut list = ScmList::new(&vm);
+            assert!(list.is_empty());
+            list.push(1i32.to_scm(&vm)).unwrap();
+            list.push(2i32.to_scm(&vm)).unwrap();
+            assert_eq!(format!("{}", list.as_scm()), "(1 2)");
+
+            let mut list = ScmList::from_iter(&vm, ["a", "b"]);
+            list.push("c".to_scm(&vm)).unwrap();
+            assert_eq!(list.len(), 3);
+            let items: Vec<String> = list.iter().filter_map(|s| s.get()).collect();
+            assert_eq!(items, ["a", "b", "c"]);
+
+            let mut copy = list;
+            copy.push("d".to_scm(&vm)).unwrap();
+            list.push("e".to_scm(&vm)).unwrap();
+            assert_eq!(format!("{}", list.as_scm()), "(a b c d e)");
+
+            let eval = |code| vm.eval_language(Language::Scheme, code).unwrap();
+            let mut circular = ScmList::from_scm(eval("(list 1)")).unwrap();
+            eval("(lambda (l) (set-cdr! l l))")
+                .call1(circular.as_scm())
+                .unwrap();
+            assert_eq!(circular.push(2i32.to_scm(&vm)), Err(ListError::Circular));
+            let mut dotted = ScmList::from_scm(eval("(list 1)")).unwrap();
+            eval("(lambda (l) (set-cdr! l 2))")
+                .call1(dotted.as_scm())
+                .unwrap();
+            assert_eq!(dotted.push(3i32.to_scm(&vm)), Err(ListError::Improper));
+        });
+    }
+
+    #[test]
+    fn classify() {
+        init(|vm| {
+            let eval = |code| vm.eval_language(Language::Scheme, code).unwrap();
+            assert_eq!(eval("'(1 2 3)").classify_list(), ListKind::Proper(3));
+            assert_eq!(Scm::eol(&vm).classify_list(), ListKind::Proper(0));
+            match eval("'(1 2 . 3)").classify_list() {
+                ListKind::Dotted { len, tail } => {
+                    assert_eq!(len, 2);
+                    assert_eq!(tail.get::<i32>(), Some(3));
+                }
+                other => panic!("{:?}", other),
+            }
+            assert!(matches!(
+                eval("5").classify_list(),
+                ListKind::Dotted { len: 0, .. }
+            ));
+            let circular = eval("(let ((l (list 1 2 3))) (set-cdr! (cddr l) (cdr l)) l)");
+            assert_eq!(circular.classify_list(), ListKind::Circular);
+        });
+    }
+
+    #[test]
+    fn collect_and_loop() {
+        init(|vm| {
+            let list: ScmList = (1..=3).map(|n: i32| (n * n).to_scm(&vm)).collect();
+            assert_eq!(format!("{}", list.as_scm()), "(1 4 9)");
+            let mut sum = 0;
+            for item in list {
+                sum += item.get::<i32>().unwrap();
+            }
+            assert_eq!(sum, 14);
+            assert_eq!((&list).into_iter().count(), 3);
+
+            let empty: ScmList = std::iter::empty().collect();
+            assert!(empty.is_empty());
+        });
+    }
+
+    #[test]
+    fn bad_lists() {
+        init(|vm| {
+            let dotted = vm.eval_language(Language::Scheme, "'(1 2 . 3)").unwrap();
+            assert!(ScmList::from_scm(dotted).is_none());
+            let mut iter = ScmListIter::new(dotted);
+            assert_eq!(iter.by_ref().count(), 2);
+            assert_eq!(iter.error(), Some(ListError::Improper));
+
+            for n in 1..5 {
+                let code = format!("(let ((l (iota {}))) (set-cdr! (last-pair l) l) l)", n);
+                let circular = vm.eval_language(Language::Scheme, &code).unwrap();
+                assert!(ScmList::from_scm(circular).is_none());
+                let mut iter = ScmListIter::new(circular);
+                assert!(iter.by_ref().count() <= 2 * n);
+                assert_eq!(iter.error(), Some(ListError::Circular));
+            }
+
+            let proper = ScmList::from_scm(vm.eval_language(Language::Scheme, "'(1 2)").unwrap());
+            let mut iter = proper.unwrap().iter();
+            assert_eq!(iter.by_ref().count(), 2);
+            assert_eq!(iter.error(), None);
+        });
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+//! The `scm!` macro, and the helpers its expansion calls.
+
+use crate::scm;
+use crate::{GuileVM, Scm};
+
+/// Build Scheme data from a quasiquote-like literal:
+///
+/// ```ignore
+/// let port = 8080;
+/// let config = scm!(&vm, (server (host "localhost") #:port ,port #(1 2)));
+/// ```
+///
+/// Identifiers are symbols, Rust literals convert with `ToScm`, `#:name`
+/// is a keyword, `#t`/`#f` are booleans, `#( ... )` is a vector and
+/// `(a . b)` a pair. `,x` interpolates any `ToScm` value; wrap anything
+/// longer than a single token in parentheses, as in `,(x + 1)`.
+/// Symbols and keywords are limited to Rust identifiers, so interpolate
+/// an `ScmSymbol` for names like `list-ref`.
+#[macro_export]
+macro_rules! scm {
+    ($vm:expr, $($datum:tt)+) => {{
+        let vm: &$crate::GuileVM = &$vm;
+        $crate::scm!(@one vm [$($datum)+])
+    }};
+
+    (@one $vm:ident [, $e:tt]) => { $crate::ToScm::to_scm(&$e, $vm) };
+    (@one $vm:ident [# : $kw:ident]) => { $crate::literal::keyword($vm, stringify!($kw)) };
+    (@one $vm:ident [# t]) => { $crate::literal::boolean($vm, true) };
+    (@one $vm:ident [# f]) => { $crate::literal::boolean($vm, false) };
+    (@one $vm:ident [# ( $($item:tt)* )]) => {
+        $crate::literal::vector($crate::scm!(@list $vm [$($item)*]))
+    };
+    (@one $vm:ident [( $($item:tt)* )]) => { $crate::scm!(@list $vm [$($item)*]) };
+    (@one $vm:ident [$lit:literal]) => { $crate::ToScm::to_scm(&$lit, $vm) };
+    (@one $vm:ident [$sym:ident]) => { $crate::literal::symbol($vm, stringify!($sym)) };
+
+    (@list $vm:ident []) => { $crate::literal::nil($vm) };
+    (@list $vm:ident [. $($tail:tt)+]) => { $crate::scm!(@one $vm [$($tail)+]) };
+    (@list $vm:ident [, $e:tt $($rest:tt)*]) => {
+        $crate::literal::cons($crate::scm!(@one $vm [, $e]), $crate::scm!(@list $vm [$($rest)*]))
+    };
+    (@list $vm:ident [# : $kw:ident $($rest:tt)*]) => {
+        $crate::literal::cons($crate::scm!(@one $vm [# : $kw]), $crate::scm!(@list $vm [$($rest)*]))
+    };
+    (@list $vm:ident [# $hash:tt $($rest:tt)*]) => {
+        $crate::literal::cons($crate::scm!(@one $vm [# $hash]), $crate::scm!(@list $vm [$($rest)*]))
+    };
+    (@list $vm:ident [$lit:literal $($rest:tt)*]) => {
+        $crate::literal::cons($crate::scm!(@one $vm [$lit]), $crate::scm!(@list $vm [$($rest)*]))
+    };
+    (@list $vm:ident [$first:tt $($rest:tt)*]) => {
+        $crate::literal::cons($crate::scm!(@one $vm [$first]), $crate::scm!(@list $vm [$($rest)*]))
+    };
+}
+
+pub fn nil<'vm>(vm: &'vm GuileVM) -> Scm<'vm> {
+    Scm::eol(vm)
+}
+
+pub fn boolean<'vm>(vm: &'vm GuileVM, value: bool) -> Scm<'vm> {
+    Scm::bool(vm, value)
+}
+
+pub fn cons<'vm>(car: Scm<'vm>, cdr: Scm<'vm>) -> Scm<'vm> {
+    car.with_raw(unsafe { guile_sys::scm_cons(car.as_raw(), cdr.as_raw()) })
+}
+
+pub fn symbol<'vm>(vm: &'vm GuileVM, name: &str) -> Scm<'vm> {
+    unsafe { Scm::from_raw(vm, scm::symbol(name)) }
+}
+
+pub fn keyword<'vm>(vm: &'vm GuileVM, name: &str) -> Scm<'vm> {
+    unsafe { Scm::from_raw(vm, scm::keyword(name)) }
+}
+
+pub fn vector<'vm>(list: Scm<'vm>) -> Scm<'vm> {
+    list.with_raw(unsafe { guile_sys::scm_vector(list.as_raw()) })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{init, ScmSymbol};
+
+    #[test]
+    fn literals() {
+        init(|vm| {
+            let port = 8080;
+            let host = "localhost";
+            let value = scm!(&vm, (server (host ,host) #:port ,port #(1 -2 #t) "x y" 'c' . tail));
+            assert_eq!(
+                format!("{:?}", value),
+                "(server (host \"localhost\") #:port 8080 #(1 -2 #t) \"x y\" #\\c . tail)"
+            );
+
+            let name = ScmSymbol::new(&vm, "list-ref");
+            assert_eq!(
+                format!("{:?}", scm!(&vm, (,(name.as_scm()) ,(port + 1)))),
+                "(list-ref 8081)"
+            );
+            assert_eq!(format!("{:?}", scm!(&vm, ())), "()");
+            assert_eq!(format!("{:?}", scm!(&vm, #:key)), "#:key");
+            assert_eq!(format!("{:?}", scm!(&vm, #f)), "#f");
+            assert_eq!(format!("{:?}", scm!(&vm, 1.5)), "1.5");
+            assert_eq!(format!("{:?}", scm!(&vm, ,port)), "8080");
+        });
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use guile_sys::{SCM, SCM_UNSPECIFIED};
+
+use crate::closure::make_procedure;
+use crate::error::{protect, GuileError};
+use crate::scm::read_all;
+use crate::string;
+use crate::GuileVM;
+
+/* An unbuffered output port that calls `log-line` with every complete
+ * line written to it. */
+const MAKE_LOG_PORT: &str = "
+(lambda (log-line)
+  (let ((pending \"\"))
+    (define (write-string s)
+      (set! pending (string-append pending s))
+      (let loop ()
+        (let ((end (string-index pending #\\newline)))
+          (when end
+            (log-line (substring pending 0 end))
+            (set! pending (substring pending (+ end 1)))
+            (loop)))))
+    (define (flush)
+      (unless (string-null? pending)
+        (log-line pending)
+        (set! pending \"\")))
+    (let ((port (make-soft-port
+                 (vector (lambda (c) (write-string (string c)))
+                         write-string
+                         flush
+                         (lambda () (eof-object))
+                         flush)
+                 \"w\")))
+      (setvbuf port 'none)
+      port)))
+";
+
+impl GuileVM {
+    /// Send everything written to the current warning port, including
+    /// deprecation warnings, to `log::warn!` with the target `guile`, one
+    /// record per line.
+    pub fn log_warnings(&self) -> Result<(), GuileError> {
+        unsafe {
+            let log_line = make_procedure(
+                "log-warning",
+                Box::new(|args: SCM| {
+                    let line = string::to_string(guile_sys::scm_car(args));
+                    log::warn!(target: "guile", "{}", line);
+                    Ok(SCM_UNSPECIFIED)
+                }),
+            );
+            protect(|| {
+                let make_port =
+                    guile_sys::scm_primitive_eval(guile_sys::scm_car(read_all(MAKE_LOG_PORT)));
+                guile_sys::scm_set_current_warning_port(guile_sys::scm_call_1(make_port, log_line))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{init, Language};
+    use log::{Level, Log, Metadata, Record};
+    use std::sync::Mutex;
+
+    static RECORDS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+    struct Recorder;
+
+    impl Log for Recorder {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= Level::Warn
+        }
+
+        fn log(&self, record: &Record) {
+            RECORDS
+                .lock()
+                .unwrap()
+                .push((record.target().to_string(), record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn warnings_are_logged() {
+        log::set_logger(&Recorder).unwrap();
+        log::set_max_level(log::LevelFilter::Warn);
+        init(|vm| {
+            vm.log_warnings().unwrap();
+            vm.eval_language(
+                Language::Scheme,
+                "(display \"first\\nsecond\" (current-warning-port))
+                 (newline (current-warning-port))",
+            )
+            .unwrap();
+        });
+        let records = RECORDS.lock().unwrap();
+        assert_eq!(
+            *records,
+            vec![
+                ("guile".to_string(), "first".to_string()),
+                ("guile".to_string(), "second".to_string()),
+            ]
+        );
+    }
+}
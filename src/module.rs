@@ -0,0 +1,253 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use guile_sys::SCM;
+
+use crate::error::{protect, GuileError};
+use crate::scm::{list_from_raw, public_ref, read_all, symbol};
+use crate::string;
+use crate::{FromScm, GuileVM, Scm, ToScm};
+
+/* The absolute path of the file a module was loaded from, or #f. */
+const MODULE_FILE: &str = "
+(lambda (module)
+  (let ((file (module-filename module)))
+    (and file
+         (if (absolute-file-name? file) file (%search-load-path file)))))
+";
+
+/* Loading a module's source again re-runs its `define-module`, which
+ * finds the existing module, so every definition is rebound in the same
+ * variables other modules have imported. */
+const RELOAD: &str = "
+(lambda (file)
+  (save-module-excursion
+   (lambda ()
+     (primitive-load file))))
+";
+
+/* When each file was last (re)loaded by `reload`/`reload_if_changed`. */
+static LOADED: Mutex<Option<HashMap<PathBuf, SystemTime>>> = Mutex::new(None);
+
+unsafe fn eval_lambda(code: &str) -> SCM {
+    guile_sys::scm_primitive_eval(guile_sys::scm_car(read_all(code)))
+}
+
+/* Load `path` and remember its modification time. */
+fn reload_file(vm: &GuileVM, path: &Path) -> Result<(), GuileError> {
+    let modified = fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    let file = path.to_scm(vm).as_raw();
+    unsafe { protect(|| guile_sys::scm_call_1(eval_lambda(RELOAD), file))? };
+    let key = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut loaded = LOADED.lock().unwrap();
+    let loaded = loaded.get_or_insert_with(HashMap::new);
+    match modified {
+        Some(time) => loaded.insert(key, time),
+        None => loaded.remove(&key),
+    };
+    Ok(())
+}
+
+/// A Scheme module.
+#[derive(Clone, Copy)]
+pub struct ScmModule<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> ScmModule<'vm> {
+    /// Find the module `name`, given as space-separated components like
+    /// `"ice-9 format"`, loading it from the load path if needed. Fails if
+    /// there's no such module.
+    pub fn resolve(vm: &'vm GuileVM, name: &str) -> Result<ScmModule<'vm>, GuileError> {
+        unsafe {
+            let raw = protect(|| {
+                let name = list_from_raw(name.split_whitespace().map(|c| symbol(c)));
+                guile_sys::scm_maybe_resolve_module(name)
+            })?;
+            if guile_sys::scm_is_false(raw) {
+                return Err(GuileError::Throw {
+                    key: String::from("misc-error"),
+                    message: format!("no code for module ({})", name),
+                });
+            }
+            Ok(ScmModule {
+                scm: Scm::from_raw(vm, raw),
+            })
+        }
+    }
+
+    /// Returns `None` if `scm` is not a module.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmModule<'vm>> {
+        if guile_sys::scm_is_true(unsafe {
+            guile_sys::scm_call_1(public_ref("guile", "module?"), scm.as_raw())
+        }) {
+            Some(ScmModule { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    /// The module's name as space-separated components.
+    pub fn name(&self) -> String {
+        let mut components = Vec::new();
+        unsafe {
+            let mut name =
+                guile_sys::scm_call_1(public_ref("guile", "module-name"), self.scm.as_raw());
+            while guile_sys::scm_is_pair(name) != 0 {
+                let component = guile_sys::scm_symbol_to_string(guile_sys::scm_car(name));
+                components.push(string::to_string(component));
+                name = guile_sys::scm_cdr(name);
+            }
+        }
+        components.join(" ")
+    }
+
+    /// The source file the module was loaded from, if any.
+    pub fn filename(&self) -> Option<PathBuf> {
+        let module = self.scm.as_raw();
+        let file = unsafe { protect(|| guile_sys::scm_call_1(eval_lambda(MODULE_FILE), module)) };
+        file.ok()
+            .and_then(|file| PathBuf::from_scm(self.scm.with_raw(file)))
+    }
+
+    /// Re-evaluate the module's source file, rebinding its definitions
+    /// in place so importers see the new ones.
+    pub fn reload(&self) -> Result<(), GuileError> {
+        let file = self.filename().ok_or_else(|| GuileError::Throw {
+            key: String::from("misc-error"),
+            message: format!("module ({}) has no source file", self.name()),
+        })?;
+        let vm = unsafe { GuileVM::assume_guile_mode() };
+        reload_file(&vm, &file)
+    }
+}
+
+impl GuileVM {
+    /// Evaluate `code` in `module`, leaving the current module as it is.
+    pub fn eval_in<'vm>(
+        &'vm self,
+        module: &ScmModule<'vm>,
+        code: &str,
+    ) -> Result<Scm<'vm>, GuileError> {
+        let module = module.as_scm().as_raw();
+        unsafe {
+            let code = string::from_str(code);
+            let result = protect(|| guile_sys::scm_eval_string_in_module(code, module))?;
+            Ok(Scm::from_raw(self, result))
+        }
+    }
+
+    /// Reload the module source at `path` unless it's unmodified since it
+    /// was last loaded by this or `ScmModule::reload`. Returns whether it
+    /// was reloaded. Meant to be called from a file watcher.
+    pub fn reload_if_changed<P: AsRef<Path>>(&self, path: P) -> Result<bool, GuileError> {
+        let path = path.as_ref();
+        let key = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let modified = fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        let unchanged = LOADED
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|loaded| loaded.get(&key))
+            .is_some_and(|&time| Some(time) == modified);
+        if unchanged {
+            return Ok(false);
+        }
+        reload_file(self, path)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language};
+    use std::fs::File;
+    use std::time::Duration;
+
+    fn value(vm: &GuileVM) -> i64 {
+        let value = vm
+            .eval_language(Language::Scheme, "(@ (guile-rs test reload) value)")
+            .unwrap();
+        unsafe { guile_sys::scm_to_int64(value.as_raw()) }
+    }
+
+    fn write_module(path: &Path, value: i64, modified: SystemTime) {
+        fs::write(
+            path,
+            format!(
+                "(define-module (guile-rs test reload) #:export (value))
+                 (define value {})",
+                value
+            ),
+        )
+        .unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    #[test]
+    fn eval_in() {
+        init(|vm| {
+            let module = ScmModule::resolve(&vm, "ice-9 sandbox").unwrap();
+            vm.eval_in(&module, "(define eval-in-test 42)").unwrap();
+            let value = vm
+                .eval_language(Language::Scheme, "(@@ (ice-9 sandbox) eval-in-test)")
+                .unwrap();
+            assert_eq!(unsafe { guile_sys::scm_to_int64(value.as_raw()) }, 42);
+            assert!(vm.eval_language(Language::Scheme, "eval-in-test").is_err());
+        });
+    }
+
+    #[test]
+    fn reload() {
+        let path = std::env::temp_dir().join(format!("guile-rs-reload-{}.scm", std::process::id()));
+        let start = SystemTime::now();
+        write_module(&path, 1, start);
+        init(|vm| {
+            let load = format!("(primitive-load {:?})", path.to_str().unwrap());
+            vm.eval_language(Language::Scheme, &load).unwrap();
+            assert_eq!(value(&vm), 1);
+
+            let module = ScmModule::resolve(&vm, "guile-rs test reload").unwrap();
+            assert_eq!(module.name(), "guile-rs test reload");
+            write_module(&path, 2, start + Duration::from_secs(1));
+            module.reload().unwrap();
+            assert_eq!(value(&vm), 2);
+
+            assert!(!vm.reload_if_changed(&path).unwrap());
+            write_module(&path, 3, start + Duration::from_secs(2));
+            assert!(vm.reload_if_changed(&path).unwrap());
+            assert_eq!(value(&vm), 3);
+        });
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::ops::Deref;
+
+use ::ndarray::{ArrayBase, ArrayD, ArrayViewD, Axis, Data, Dimension, IxDyn, ShapeBuilder};
+use guile_sys::SCM_UNDEFINED;
+
+use crate::array::ArrayHandle;
+use crate::scm::{list_from_raw, symbol};
+use crate::{GuileVM, Scm, ScmArray, UniformElement};
+
+/// An `ndarray` view of a Scheme array's elements, released when
+/// dropped.
+pub struct NdArrayView<'a, T> {
+    _handle: ArrayHandle,
+    view: ArrayViewD<'a, T>,
+}
+
+impl<'a, T> Deref for NdArrayView<'a, T> {
+    type Target = ArrayViewD<'a, T>;
+
+    fn deref(&self) -> &ArrayViewD<'a, T> {
+        &self.view
+    }
+}
+
+impl<'vm> ScmArray<'vm> {
+    /// View the elements in place as an `ndarray`, whatever their
+    /// layout: strided and transposed arrays keep their strides.
+    /// Returns `None` unless the array holds `T`s.
+    pub fn ndarray_view<T: UniformElement>(&self) -> Option<NdArrayView<'_, T>> {
+        let mut handle = unsafe { ArrayHandle::new(self.as_scm().as_raw()) };
+        if handle.element_type() != T::ELEMENT_TYPE {
+            return None;
+        }
+        let dims = handle.dims().to_vec();
+        let shape: Vec<usize> = dims
+            .iter()
+            .map(|dim| (dim.ubnd - dim.lbnd + 1).max(0) as usize)
+            .collect();
+        let strides: Vec<usize> = dims.iter().map(|dim| dim.inc.unsigned_abs()).collect();
+        let mut elements =
+            unsafe { guile_sys::scm_array_handle_uniform_elements(handle.as_mut_ptr()) }
+                as *const T;
+        /* ndarray wants the lowest address and non-negative strides, so
+         * start from the far end of each reversed axis and flip it back
+         * afterwards. */
+        for (dim, &len) in dims.iter().zip(&shape) {
+            if dim.inc < 0 && len > 0 {
+                elements = unsafe { elements.offset(dim.inc * (len as isize - 1)) };
+            }
+        }
+        let mut view =
+            unsafe { ArrayViewD::from_shape_ptr(IxDyn(&shape).strides(IxDyn(&strides)), elements) };
+        for (axis, dim) in dims.iter().enumerate() {
+            if dim.inc < 0 {
+                view.invert_axis(Axis(axis));
+            }
+        }
+        Some(NdArrayView {
+            _handle: handle,
+            view,
+        })
+    }
+
+    /// Copy the elements into an owned `ndarray`. Returns `None` unless
+    /// the array holds `T`s.
+    pub fn to_ndarray<T: UniformElement>(&self) -> Option<ArrayD<T>> {
+        Some(self.ndarray_view()?.to_owned())
+    }
+
+    /// Create a Scheme array of `T`s with the same shape and elements as
+    /// `array`, zero-based in every dimension.
+    pub fn from_ndarray<T, S, D>(vm: &'vm GuileVM, array: &ArrayBase<S, D>) -> ScmArray<'vm>
+    where
+        T: UniformElement,
+        S: Data<Elem = T>,
+        D: Dimension,
+    {
+        let mut scm_array = unsafe {
+            let bounds = list_from_raw(
+                array
+                    .shape()
+                    .iter()
+                    .map(|&len| guile_sys::scm_from_uint64(len as u64)),
+            );
+            let raw = guile_sys::scm_make_typed_array(symbol(T::TYPE), SCM_UNDEFINED, bounds);
+            ScmArray::from_scm(Scm::from_raw(vm, raw))
+                .expect("make-typed-array returned a non-array")
+        };
+        /* A fresh array is contiguous and row-major, which is also the
+         * order `iter` walks `array` in. Nothing else has seen it yet. */
+        if let Some(mut elements) = unsafe { scm_array.as_mut_slice::<T>() } {
+            for (dst, src) in elements.iter_mut().zip(array.iter()) {
+                *dst = *src;
+            }
+        }
+        scm_array
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Language;
+    use ::ndarray::{arr1, arr2, Array2};
+
+    #[test]
+    fn views_follow_strides() {
+        crate::init(|vm| {
+            let scm = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(transpose-array (list->typed-array 'f64 2 '((1 2 3) (4 5 6))) 1 0)",
+                )
+                .unwrap();
+            let transposed = ScmArray::from_scm(scm).unwrap();
+            let view = transposed.ndarray_view::<f64>().unwrap();
+            assert_eq!(view.shape(), &[3, 2]);
+            assert_eq!(view[[2, 0]], 3.0);
+            assert!(transposed.ndarray_view::<f32>().is_none());
+
+            let reversed = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(make-shared-array #f32(1 2 3) (lambda (i) (list (- 2 i))) 3)",
+                )
+                .unwrap();
+            let reversed = ScmArray::from_scm(reversed).unwrap();
+            assert_eq!(
+                reversed.to_ndarray::<f32>().unwrap(),
+                arr1(&[3.0f32, 2.0, 1.0]).into_dyn()
+            );
+        });
+    }
+
+    #[test]
+    fn from_ndarray_round_trip() {
+        crate::init(|vm| {
+            let matrix: Array2<f64> = arr2(&[[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]]);
+            let scm = ScmArray::from_ndarray(&vm, &matrix.t());
+            assert_eq!(scm.shape(), vec![2, 3]);
+            assert_eq!(
+                format!("{:?}", scm.as_scm()),
+                "#2f64((1.0 3.0 5.0) (2.0 4.0 6.0))"
+            );
+            let back = scm.to_ndarray::<f64>().unwrap();
+            assert_eq!(back, matrix.t().into_dyn());
+        });
+    }
+}
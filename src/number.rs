@@ -0,0 +1,153 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::cmp::Ordering;
+
+use guile_sys::SCM;
+
+use crate::error::{protect, GuileError};
+use crate::Scm;
+
+unsafe fn compare(
+    x: SCM,
+    y: SCM,
+    pred: unsafe extern "C" fn(SCM, SCM) -> SCM,
+) -> Result<bool, GuileError> {
+    protect(|| pred(x, y)).map(guile_sys::scm_is_true)
+}
+
+/* These throw wrong-type-arg for non-numbers (and, except for `num_eq`,
+ * for non-real numbers), which comes back as an error. */
+impl<'vm> Scm<'vm> {
+    /// `<`
+    pub fn num_lt(&self, other: Scm) -> Result<bool, GuileError> {
+        unsafe { compare(self.as_raw(), other.as_raw(), guile_sys::scm_less_p) }
+    }
+
+    /// `<=`
+    pub fn num_le(&self, other: Scm) -> Result<bool, GuileError> {
+        unsafe { compare(self.as_raw(), other.as_raw(), guile_sys::scm_leq_p) }
+    }
+
+    /// `>`
+    pub fn num_gt(&self, other: Scm) -> Result<bool, GuileError> {
+        unsafe { compare(self.as_raw(), other.as_raw(), guile_sys::scm_gr_p) }
+    }
+
+    /// `>=`
+    pub fn num_ge(&self, other: Scm) -> Result<bool, GuileError> {
+        unsafe { compare(self.as_raw(), other.as_raw(), guile_sys::scm_geq_p) }
+    }
+
+    /// `=`: numeric equality, so `2` and `2.0` are equal.
+    pub fn num_eq(&self, other: Scm) -> Result<bool, GuileError> {
+        unsafe { compare(self.as_raw(), other.as_raw(), guile_sys::scm_num_eq_p) }
+    }
+}
+
+/// A Scheme number, compared without converting to a Rust type. Like
+/// `f64`, it's only partially ordered: NaNs and non-real numbers compare
+/// as `None`.
+#[derive(Clone, Copy, Debug)]
+pub struct ScmNumber<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> ScmNumber<'vm> {
+    /// Returns `None` if `scm` is not a number.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmNumber<'vm>> {
+        if unsafe { guile_sys::scm_is_number(scm.as_raw()) } != 0 {
+            Some(ScmNumber { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    pub fn is_exact(&self) -> bool {
+        unsafe { guile_sys::scm_is_exact(self.scm.as_raw()) != 0 }
+    }
+
+    fn is_ordered(&self) -> bool {
+        let raw = self.scm.as_raw();
+        unsafe {
+            guile_sys::scm_is_real(raw) != 0 && !guile_sys::scm_is_true(guile_sys::scm_nan_p(raw))
+        }
+    }
+}
+
+impl<'a, 'b> PartialEq<ScmNumber<'b>> for ScmNumber<'a> {
+    fn eq(&self, other: &ScmNumber<'b>) -> bool {
+        self.scm.num_eq(other.scm).unwrap_or(false)
+    }
+}
+
+impl<'a, 'b> PartialOrd<ScmNumber<'b>> for ScmNumber<'a> {
+    fn partial_cmp(&self, other: &ScmNumber<'b>) -> Option<Ordering> {
+        if !self.is_ordered() || !other.is_ordered() {
+            return if self == other {
+                Some(Ordering::Equal)
+            } else {
+                None
+            };
+        }
+        if self.scm.num_lt(other.scm).ok()? {
+            Some(Ordering::Less)
+        } else if self.scm.num_gt(other.scm).ok()? {
+            Some(Ordering::Greater)
+        } else {
+            Some(Ordering::Equal)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language, ToScm};
+
+    #[test]
+    fn compare_without_converting() {
+        init(|vm| {
+            let eval = |code| {
+                ScmNumber::from_scm(vm.eval_language(Language::Scheme, code).unwrap()).unwrap()
+            };
+            let big = eval("(+ (expt 2 64) 1)");
+            let bigger = eval("(+ (expt 2 64) 2)");
+            assert!(big < bigger);
+            assert_eq!(eval("2"), eval("2.0"));
+            assert!(eval("1/3") < eval("0.34"));
+            assert_eq!(eval("+nan.0").partial_cmp(&eval("1")), None);
+            assert_eq!(eval("+i").partial_cmp(&eval("1")), None);
+            assert_eq!(eval("+i").partial_cmp(&eval("+i")), Some(Ordering::Equal));
+
+            let one = 1i32.to_scm(&vm);
+            assert_eq!(one.num_le(2i32.to_scm(&vm)), Ok(true));
+            assert_eq!(one.num_ge(2i32.to_scm(&vm)), Ok(false));
+            assert!(one.num_lt("2".to_scm(&vm)).is_err());
+            assert!(ScmNumber::from_scm("2".to_scm(&vm)).is_none());
+
+            let mut numbers = [eval("3"), eval("1/2"), eval("-1.5")];
+            numbers.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            assert_eq!(vm.display(numbers[0].as_scm()), "-1.5");
+            assert_eq!(vm.display(numbers[1].as_scm()), "1/2");
+        });
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use guile_sys::SCM;
+
+use crate::error::{protect, GuileError};
+use crate::{GuileVM, Scm};
+
+impl GuileVM {
+    pub fn cons<'vm>(&'vm self, car: Scm<'vm>, cdr: Scm<'vm>) -> Scm<'vm> {
+        unsafe { Scm::from_raw(self, guile_sys::scm_cons(car.as_raw(), cdr.as_raw())) }
+    }
+}
+
+/* Each of these checks for a pair the way the Scheme procedures do, so a
+ * non-pair comes back as a wrong-type-arg error instead of a throw. */
+impl<'vm> Scm<'vm> {
+    fn pair_op(&self, op: unsafe extern "C" fn(SCM) -> SCM) -> Result<Scm<'vm>, GuileError> {
+        let raw = self.as_raw();
+        unsafe { protect(|| op(raw)).map(|r| self.with_raw(r)) }
+    }
+
+    pub fn car(&self) -> Result<Scm<'vm>, GuileError> {
+        self.pair_op(guile_sys::scm_car)
+    }
+
+    pub fn cdr(&self) -> Result<Scm<'vm>, GuileError> {
+        self.pair_op(guile_sys::scm_cdr)
+    }
+
+    pub fn caar(&self) -> Result<Scm<'vm>, GuileError> {
+        self.car()?.car()
+    }
+
+    pub fn cadr(&self) -> Result<Scm<'vm>, GuileError> {
+        self.cdr()?.car()
+    }
+
+    pub fn cdar(&self) -> Result<Scm<'vm>, GuileError> {
+        self.car()?.cdr()
+    }
+
+    pub fn cddr(&self) -> Result<Scm<'vm>, GuileError> {
+        self.cdr()?.cdr()
+    }
+
+    pub fn caddr(&self) -> Result<Scm<'vm>, GuileError> {
+        self.cddr()?.car()
+    }
+
+    pub fn set_car(&self, value: Scm) -> Result<(), GuileError> {
+        let (pair, value) = (self.as_raw(), value.as_raw());
+        unsafe { protect(|| guile_sys::scm_set_car_x(pair, value)) }.map(drop)
+    }
+
+    pub fn set_cdr(&self, value: Scm) -> Result<(), GuileError> {
+        let (pair, value) = (self.as_raw(), value.as_raw());
+        unsafe { protect(|| guile_sys::scm_set_cdr_x(pair, value)) }.map(drop)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{init, Scm};
+
+    #[test]
+    fn surgery() {
+        init(|vm| {
+            let list = Scm::new(&vm, &vec![1i32, 2, 3]);
+            assert_eq!(list.car().unwrap().get::<i32>(), Some(1));
+            assert_eq!(list.cadr().unwrap().get::<i32>(), Some(2));
+            assert_eq!(list.caddr().unwrap().get::<i32>(), Some(3));
+            assert!(list.cddr().unwrap().cdr().unwrap().is_null());
+
+            list.set_car(Scm::new(&vm, "one")).unwrap();
+            list.cdr().unwrap().set_cdr(Scm::eol(&vm)).unwrap();
+            assert_eq!(format!("{:?}", list), "(\"one\" 2)");
+
+            let pair = vm.cons(Scm::bool(&vm, true), Scm::bool(&vm, false));
+            assert_eq!(format!("{}", pair), "(#t . #f)");
+            assert_eq!(pair.caar().unwrap_err().key(), "wrong-type-arg");
+            assert!(Scm::eol(&vm).car().is_err());
+        });
+    }
+}
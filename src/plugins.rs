@@ -0,0 +1,324 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::ffi::CString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use guile_sys::SCM;
+
+use crate::error::{protect, GuileError};
+use crate::scm::{list_from_raw, read_all, symbol};
+use crate::string;
+use crate::{GuileVM, ToScm};
+
+/* Load `file` into a fresh module named `module-name`, which uses
+ * (guile) like a user module does. */
+const LOAD: &str = "
+(lambda (module-name file compiled?)
+  (let ((module (resolve-module module-name #:ensure #t)))
+    (beautify-user-module! module)
+    (save-module-excursion
+     (lambda ()
+       (set-current-module module)
+       (if compiled? (load-compiled file) (primitive-load file))))
+    module))
+";
+
+/* (plugin-name plugin-version (export ...)), with #f for missing
+ * metadata. */
+const METADATA: &str = "
+(lambda (module)
+  (define (ref name)
+    (let ((var (module-local-variable module name)))
+      (and var (variable-bound? var) (variable-ref var))))
+  (list (ref 'plugin-name)
+        (ref 'plugin-version)
+        (module-map (lambda (name var) (symbol->string name))
+                    (module-public-interface module))))
+";
+
+/* Call the thunk `name` in `module`, if the plugin defined one. */
+const HOOK: &str = "
+(lambda (module name)
+  (let ((var (module-local-variable module name)))
+    (when (and var (variable-bound? var))
+      ((variable-ref var)))))
+";
+
+const MODULE_PREFIX: &str = "guile-rs plugins";
+
+unsafe fn eval_lambda(code: &str) -> SCM {
+    guile_sys::scm_primitive_eval(guile_sys::scm_car(read_all(code)))
+}
+
+unsafe fn optional_string(x: SCM) -> Option<String> {
+    if guile_sys::scm_is_true(guile_sys::scm_string_p(x)) {
+        Some(string::to_string(x))
+    } else {
+        None
+    }
+}
+
+/// A loaded plugin.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Plugin {
+    /// The plugin's `plugin-name`, or its file name without extension.
+    pub name: String,
+    /// The plugin's `plugin-version`, if it defines one.
+    pub version: Option<String>,
+    pub path: PathBuf,
+    /// The module the plugin was loaded into, e.g.
+    /// `"guile-rs plugins foo"`.
+    pub module: String,
+    /// The names the plugin exported from its module.
+    pub exports: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum PluginError {
+    /// A plugin directory couldn't be read.
+    Io { path: PathBuf, error: io::Error },
+    /// Loading a plugin or running one of its hooks raised an error.
+    Guile { path: PathBuf, error: GuileError },
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PluginError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            PluginError::Guile { path, error } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl Error for PluginError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PluginError::Io { error, .. } => Some(error),
+            PluginError::Guile { error, .. } => Some(error),
+        }
+    }
+}
+
+/// Discovers and loads Scheme plugins from a set of directories.
+///
+/// Every `foo.scm` (or compiled `foo.go`, which wins if both exist) is
+/// loaded into its own module, `(guile-rs plugins foo)`. A plugin may
+/// define `plugin-name` and `plugin-version` strings, and
+/// `plugin-init`/`plugin-shutdown` thunks which are called after it's
+/// loaded and by `shutdown`.
+#[derive(Debug, Default)]
+pub struct PluginManager {
+    directories: Vec<PathBuf>,
+    plugins: Vec<Plugin>,
+}
+
+impl PluginManager {
+    pub fn new() -> PluginManager {
+        PluginManager::default()
+    }
+
+    pub fn add_directory<P: Into<PathBuf>>(&mut self, dir: P) {
+        self.directories.push(dir.into());
+    }
+
+    pub fn plugins(&self) -> &[Plugin] {
+        &self.plugins
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Plugin> {
+        self.plugins.iter().find(|plugin| plugin.name == name)
+    }
+
+    /// The plugin files in the registered directories, by plugin.
+    pub fn discover(&self) -> Result<Vec<PathBuf>, PluginError> {
+        let mut found = Vec::new();
+        for dir in &self.directories {
+            let entries = fs::read_dir(dir).map_err(|error| PluginError::Io {
+                path: dir.clone(),
+                error,
+            })?;
+            let mut by_stem = BTreeMap::new();
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let (Some(stem), Some(ext)) = (path.file_stem(), path.extension()) else {
+                    continue;
+                };
+                if ext == "go" || (ext == "scm" && !by_stem.contains_key(stem)) {
+                    by_stem.insert(stem.to_os_string(), path.clone());
+                }
+            }
+            found.extend(by_stem.into_values());
+        }
+        Ok(found)
+    }
+
+    /// Load every discovered plugin that isn't loaded yet, returning the
+    /// errors for those that failed. A failing plugin doesn't stop the
+    /// others from loading.
+    pub fn load_all(&mut self, vm: &GuileVM) -> Vec<PluginError> {
+        let mut paths = match self.discover() {
+            Ok(paths) => paths,
+            Err(error) => return vec![error],
+        };
+        paths.retain(|path| !self.plugins.iter().any(|plugin| &plugin.path == path));
+        paths
+            .into_iter()
+            .filter_map(|path| self.load(vm, &path).err())
+            .collect()
+    }
+
+    /// Load the plugin at `path` and run its `plugin-init`.
+    pub fn load(&mut self, vm: &GuileVM, path: &Path) -> Result<&Plugin, PluginError> {
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let module = format!("{} {}", MODULE_PREFIX, stem);
+        let compiled = path.extension().is_some_and(|ext| ext == "go");
+        let file = path.to_scm(vm).as_raw();
+        let metadata = unsafe {
+            let module_name = list_from_raw(module.split(' ').map(|c| symbol(c)));
+            protect(|| {
+                let loaded = guile_sys::scm_call_3(
+                    eval_lambda(LOAD),
+                    module_name,
+                    file,
+                    guile_sys::scm_from_bool(compiled),
+                );
+                guile_sys::scm_call_2(eval_lambda(HOOK), loaded, symbol("plugin-init"));
+                guile_sys::scm_call_1(eval_lambda(METADATA), loaded)
+            })
+        }
+        .map_err(|error| PluginError::Guile {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let plugin = unsafe {
+            let mut exports = Vec::new();
+            let mut names = guile_sys::scm_caddr(metadata);
+            while guile_sys::scm_is_pair(names) != 0 {
+                exports.push(string::to_string(guile_sys::scm_car(names)));
+                names = guile_sys::scm_cdr(names);
+            }
+            exports.sort();
+            Plugin {
+                name: optional_string(guile_sys::scm_car(metadata)).unwrap_or(stem),
+                version: optional_string(guile_sys::scm_cadr(metadata)),
+                path: path.to_path_buf(),
+                module,
+                exports,
+            }
+        };
+        self.plugins.push(plugin);
+        Ok(self.plugins.last().unwrap())
+    }
+
+    /// Run every plugin's `plugin-shutdown` and forget about them,
+    /// returning the errors raised by the hooks.
+    pub fn shutdown(&mut self, _vm: &GuileVM) -> Vec<PluginError> {
+        self.plugins
+            .drain(..)
+            .filter_map(|plugin| {
+                let module = CString::new(plugin.module.as_str()).unwrap();
+                unsafe {
+                    protect(|| {
+                        guile_sys::scm_call_2(
+                            eval_lambda(HOOK),
+                            guile_sys::scm_c_resolve_module(module.as_ptr()),
+                            symbol("plugin-shutdown"),
+                        )
+                    })
+                }
+                .err()
+                .map(|error| PluginError::Guile {
+                    path: plugin.path,
+                    error,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language, ScmString};
+
+    fn plugin_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("guile-rs-plugins-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("greeter.scm"),
+            "(define plugin-name \"Greeter\")
+             (define plugin-version \"1.2\")
+             (define (greet who) (string-append \"hello \" who))
+             (export greet)
+             (define initialized #f)
+             (define (plugin-init) (set! initialized #t))",
+        )
+        .unwrap();
+        fs::write(dir.join("broken.scm"), "(this is not defined)").unwrap();
+        fs::write(dir.join("README"), "not a plugin").unwrap();
+        dir
+    }
+
+    #[test]
+    fn load_plugins() {
+        let dir = plugin_dir();
+        init(|vm| {
+            let mut manager = PluginManager::new();
+            manager.add_directory(&dir);
+            assert_eq!(manager.discover().unwrap().len(), 2);
+
+            let errors = manager.load_all(&vm);
+            assert_eq!(errors.len(), 1);
+
+            let greeter = manager.get("Greeter").unwrap();
+            assert_eq!(greeter.version.as_deref(), Some("1.2"));
+            assert_eq!(greeter.exports, vec!["greet"]);
+            assert_eq!(greeter.module, "guile-rs plugins greeter");
+
+            let greeting = vm
+                .eval_language(
+                    Language::Scheme,
+                    "((@ (guile-rs plugins greeter) greet) \"world\")",
+                )
+                .unwrap();
+            assert_eq!(
+                ScmString::from_scm(greeting).unwrap().to_string(),
+                "hello world"
+            );
+            let initialized = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(@@ (guile-rs plugins greeter) initialized)",
+                )
+                .unwrap();
+            assert!(initialized.is_true());
+
+            assert!(manager.shutdown(&vm).is_empty());
+            assert!(manager.plugins().is_empty());
+        });
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::os::raw::c_void;
+
+use guile_sys::{scm_t_pointer_finalizer, SCM_UNDEFINED};
+
+use crate::error::{protect, GuileError};
+use crate::{GuileVM, Scm};
+
+/// A `(system foreign)` pointer object.
+#[derive(Clone, Copy)]
+pub struct ScmPointer<'vm> {
+    scm: Scm<'vm>,
+}
+
+unsafe extern "C" fn drop_box<T>(ptr: *mut c_void) {
+    drop(Box::from_raw(ptr as *mut T));
+}
+
+impl<'vm> ScmPointer<'vm> {
+    /// Wrap `ptr`. `finalizer`, if given, is called with `ptr` once the
+    /// pointer object is garbage collected.
+    ///
+    /// # Safety
+    ///
+    /// Scheme code can read and write through the pointer, so it must
+    /// stay valid for as long as the pointer object is reachable.
+    pub unsafe fn new(
+        vm: &'vm GuileVM,
+        ptr: *mut c_void,
+        finalizer: scm_t_pointer_finalizer,
+    ) -> ScmPointer<'vm> {
+        ScmPointer {
+            scm: Scm::from_raw(vm, guile_sys::scm_from_pointer(ptr, finalizer)),
+        }
+    }
+
+    /// Hand ownership of `value` to Scheme; it's dropped when the pointer
+    /// object is collected. Finalizers run on Guile's finalizer thread,
+    /// hence `T: Send`.
+    pub fn from_box<T: Send + 'static>(vm: &'vm GuileVM, value: Box<T>) -> ScmPointer<'vm> {
+        unsafe { ScmPointer::new(vm, Box::into_raw(value) as *mut c_void, Some(drop_box::<T>)) }
+    }
+
+    /// `%null-pointer`.
+    pub fn null(vm: &'vm GuileVM) -> ScmPointer<'vm> {
+        unsafe { ScmPointer::new(vm, std::ptr::null_mut(), None) }
+    }
+
+    /// The address of the first byte of `bytevector`, plus `offset`. The
+    /// bytevector is kept alive as long as the pointer object is.
+    pub fn from_bytevector(
+        bytevector: Scm<'vm>,
+        offset: usize,
+    ) -> Result<ScmPointer<'vm>, GuileError> {
+        unsafe {
+            let offset = guile_sys::scm_from_uint64(offset as u64);
+            let raw =
+                protect(|| guile_sys::scm_bytevector_to_pointer(bytevector.as_raw(), offset))?;
+            Ok(ScmPointer {
+                scm: bytevector.with_raw(raw),
+            })
+        }
+    }
+
+    /// Returns `None` if `scm` is not a pointer object.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmPointer<'vm>> {
+        if guile_sys::scm_is_true(unsafe { guile_sys::scm_pointer_p(scm.as_raw()) }) {
+            Some(ScmPointer { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    pub fn as_ptr(&self) -> *mut c_void {
+        unsafe { guile_sys::scm_to_pointer(self.scm.as_raw()) }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.as_ptr().is_null()
+    }
+
+    /// A bytevector of `len` bytes sharing memory with the pointee, as
+    /// `pointer->bytevector`. Fails on a null pointer.
+    ///
+    /// # Safety
+    ///
+    /// The `len` bytes must be valid for reads and writes for as long as
+    /// the bytevector is reachable.
+    pub unsafe fn to_bytevector(&self, len: usize) -> Result<Scm<'vm>, GuileError> {
+        let pointer = self.scm.as_raw();
+        let len = guile_sys::scm_from_uint64(len as u64);
+        // pointer->bytevector takes (pointer len [offset [uvec-type]]),
+        // whatever the parameter names in the header say.
+        let raw = protect(|| {
+            guile_sys::scm_pointer_to_bytevector(pointer, len, SCM_UNDEFINED, SCM_UNDEFINED)
+        })?;
+        Ok(self.scm.with_raw(raw))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::init;
+
+    #[test]
+    fn bytevector_view() {
+        init(|vm| {
+            let mut bytes = [1u8, 2, 3, 4];
+            unsafe {
+                let ptr = ScmPointer::new(&vm, bytes.as_mut_ptr() as *mut c_void, None);
+                assert!(ScmPointer::from_scm(ptr.as_scm()).is_some());
+                let bv = ptr.to_bytevector(4).unwrap();
+                guile_sys::scm_c_bytevector_set_x(bv.as_raw(), 0, 9);
+                assert_eq!(guile_sys::scm_c_bytevector_length(bv.as_raw()), 4);
+
+                let back = ScmPointer::from_bytevector(bv, 2).unwrap();
+                assert_eq!(back.as_ptr() as *mut u8, bytes.as_mut_ptr().add(2));
+            }
+            assert_eq!(bytes, [9, 2, 3, 4]);
+
+            let null = ScmPointer::null(&vm);
+            assert!(null.is_null());
+            assert!(unsafe { null.to_bytevector(1) }.is_err());
+        });
+    }
+
+    #[test]
+    fn boxed() {
+        init(|vm| {
+            let ptr = ScmPointer::from_box(&vm, Box::new(42u32));
+            assert_eq!(unsafe { *(ptr.as_ptr() as *const u32) }, 42);
+        });
+    }
+}
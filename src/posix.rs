@@ -0,0 +1,298 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use guile_sys::{SCM, SCM_BOOL_T};
+
+use crate::error::{protect, GuileError};
+use crate::string;
+use crate::{FromScm, GuileVM, Scm, ToScm};
+
+/// The result of `stat`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stat {
+    pub dev: u64,
+    pub ino: u64,
+    pub mode: u32,
+    pub nlink: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub rdev: u64,
+    pub size: u64,
+    pub atime: i64,
+    pub mtime: i64,
+    pub ctime: i64,
+    pub kind: FileKind,
+}
+
+/// `stat:type`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    Regular,
+    Directory,
+    Symlink,
+    BlockSpecial,
+    CharSpecial,
+    Fifo,
+    Socket,
+    Unknown,
+}
+
+/// The result of `uname`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Uname {
+    pub sysname: String,
+    pub nodename: String,
+    pub release: String,
+    pub version: String,
+    pub machine: String,
+}
+
+/// The result of `waitpid`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WaitStatus {
+    pub pid: i32,
+    pub status: i32,
+    /// `status:exit-val`, if the process exited normally.
+    pub exit_code: Option<i32>,
+    /// `status:term-sig`, if the process was killed by a signal.
+    pub signal: Option<i32>,
+}
+
+/* Indices into the vector returned by `stat`. */
+const STAT_DEV: usize = 0;
+const STAT_INO: usize = 1;
+const STAT_MODE: usize = 2;
+const STAT_NLINK: usize = 3;
+const STAT_UID: usize = 4;
+const STAT_GID: usize = 5;
+const STAT_RDEV: usize = 6;
+const STAT_SIZE: usize = 7;
+const STAT_ATIME: usize = 8;
+const STAT_MTIME: usize = 9;
+const STAT_CTIME: usize = 10;
+const STAT_TYPE: usize = 13;
+
+unsafe fn vector_u64(v: SCM, idx: usize) -> u64 {
+    guile_sys::scm_to_uint64(guile_sys::scm_c_vector_ref(v, idx))
+}
+
+unsafe fn vector_i64(v: SCM, idx: usize) -> i64 {
+    guile_sys::scm_to_int64(guile_sys::scm_c_vector_ref(v, idx))
+}
+
+unsafe fn vector_string(v: SCM, idx: usize) -> String {
+    string::to_string(guile_sys::scm_c_vector_ref(v, idx))
+}
+
+unsafe fn optional_i32(x: SCM) -> Option<i32> {
+    if guile_sys::scm_is_true(x) {
+        Some(guile_sys::scm_to_int32(x))
+    } else {
+        None
+    }
+}
+
+impl GuileVM {
+    /// Scheme's `stat`.
+    pub fn stat<P: AsRef<Path>>(&self, path: P) -> io::Result<Stat> {
+        let path = path.as_ref().to_scm(self).as_raw();
+        unsafe {
+            let v = protect(|| guile_sys::scm_stat(path, SCM_BOOL_T))?;
+            let kind = guile_sys::scm_symbol_to_string(guile_sys::scm_c_vector_ref(v, STAT_TYPE));
+            let kind = match string::to_string(kind).as_str() {
+                "regular" => FileKind::Regular,
+                "directory" => FileKind::Directory,
+                "symlink" => FileKind::Symlink,
+                "block-special" => FileKind::BlockSpecial,
+                "char-special" => FileKind::CharSpecial,
+                "fifo" => FileKind::Fifo,
+                "socket" => FileKind::Socket,
+                _ => FileKind::Unknown,
+            };
+            Ok(Stat {
+                dev: vector_u64(v, STAT_DEV),
+                ino: vector_u64(v, STAT_INO),
+                mode: vector_u64(v, STAT_MODE) as u32,
+                nlink: vector_u64(v, STAT_NLINK),
+                uid: vector_u64(v, STAT_UID) as u32,
+                gid: vector_u64(v, STAT_GID) as u32,
+                rdev: vector_u64(v, STAT_RDEV),
+                size: vector_u64(v, STAT_SIZE),
+                atime: vector_i64(v, STAT_ATIME),
+                mtime: vector_i64(v, STAT_MTIME),
+                ctime: vector_i64(v, STAT_CTIME),
+                kind,
+            })
+        }
+    }
+
+    /// All entries of a directory, via `opendir`/`readdir`. Like
+    /// `readdir`, this includes `.` and `..`.
+    pub fn read_dir<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<OsString>> {
+        let path = path.as_ref().to_scm(self).as_raw();
+        unsafe {
+            let dir = protect(|| guile_sys::scm_opendir(path))?;
+            let mut entries = Vec::new();
+            let result = loop {
+                let entry = match protect(|| guile_sys::scm_readdir(dir)) {
+                    Ok(entry) => entry,
+                    Err(error) => break Err(error.into()),
+                };
+                if guile_sys::scm_is_true(guile_sys::scm_eof_object_p(entry)) {
+                    break Ok(());
+                }
+                match OsString::from_scm(Scm::from_raw(self, entry)) {
+                    Some(entry) => entries.push(entry),
+                    None => {
+                        break Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "undecodable file name",
+                        ))
+                    }
+                }
+            };
+            protect(|| guile_sys::scm_closedir(dir))?;
+            result.map(|()| entries)
+        }
+    }
+
+    /// Scheme's `pipe`, returning the `(read, write)` ports.
+    pub fn pipe(&self) -> io::Result<(Scm<'_>, Scm<'_>)> {
+        unsafe {
+            let ports = protect(|| guile_sys::scm_pipe())?;
+            Ok((
+                Scm::from_raw(self, guile_sys::scm_car(ports)),
+                Scm::from_raw(self, guile_sys::scm_cdr(ports)),
+            ))
+        }
+    }
+
+    /// Scheme's `waitpid`. `options` are the `WNOHANG`/`WUNTRACED` flags.
+    pub fn waitpid(&self, pid: i32, options: i32) -> io::Result<WaitStatus> {
+        unsafe {
+            let result = protect(|| {
+                guile_sys::scm_waitpid(
+                    guile_sys::scm_from_int32(pid),
+                    guile_sys::scm_from_int32(options),
+                )
+            })?;
+            let status = guile_sys::scm_cdr(result);
+            Ok(WaitStatus {
+                pid: guile_sys::scm_to_int32(guile_sys::scm_car(result)),
+                status: guile_sys::scm_to_int32(status),
+                exit_code: optional_i32(guile_sys::scm_status_exit_val(status)),
+                signal: optional_i32(guile_sys::scm_status_term_sig(status)),
+            })
+        }
+    }
+
+    /// Scheme's `uname`.
+    pub fn uname(&self) -> io::Result<Uname> {
+        unsafe {
+            let v = protect(|| guile_sys::scm_uname())?;
+            Ok(Uname {
+                sysname: vector_string(v, 0),
+                nodename: vector_string(v, 1),
+                release: vector_string(v, 2),
+                version: vector_string(v, 3),
+                machine: vector_string(v, 4),
+            })
+        }
+    }
+
+    /// Sleep for `duration` without holding up the rest of Guile: GC can
+    /// run meanwhile, and asyncs queued for this thread are run as they
+    /// arrive. Fails if one of them throws.
+    pub fn sleep(&self, duration: Duration) -> Result<(), GuileError> {
+        let deadline = Instant::now() + duration;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(());
+            }
+            let micros = (deadline - now).as_micros().min(libc::c_ulong::MAX as u128);
+            unsafe {
+                protect(|| {
+                    guile_sys::scm_std_usleep(micros as libc::c_ulong);
+                    guile_sys::scm_async_tick();
+                })?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, LocaleCategory};
+
+    #[test]
+    fn stat() {
+        init(|vm| {
+            let stat = vm.stat(env!("CARGO_MANIFEST_DIR")).unwrap();
+            assert_eq!(stat.kind, FileKind::Directory);
+
+            let error = vm.stat("/does/not/exist").unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        });
+    }
+
+    #[test]
+    fn read_dir() {
+        init(|vm| {
+            let entries = vm.read_dir(env!("CARGO_MANIFEST_DIR")).unwrap();
+            assert!(entries.contains(&OsString::from("Cargo.toml")));
+        });
+    }
+
+    #[test]
+    fn read_dir_non_ascii() {
+        let dir = std::env::temp_dir().join(format!("guile-rs-readdir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("café"), "").unwrap();
+        init(|vm| {
+            /* Guile decodes the names with the locale, which has to be
+             * able to spell them. */
+            if vm.setlocale(LocaleCategory::CType, "C.UTF-8").is_err() {
+                return;
+            }
+            let entries = vm.read_dir(&dir).unwrap();
+            assert!(entries.contains(&OsString::from("café")));
+        });
+    }
+
+    #[test]
+    fn sleep() {
+        init(|vm| {
+            let start = Instant::now();
+            vm.sleep(Duration::from_millis(20)).unwrap();
+            assert!(start.elapsed() >= Duration::from_millis(20));
+        });
+    }
+
+    #[test]
+    fn uname() {
+        init(|vm| {
+            assert!(!vm.uname().unwrap().sysname.is_empty());
+        });
+    }
+}
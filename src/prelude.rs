@@ -0,0 +1,37 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+//! The types and traits most embedding code needs:
+//!
+//! ```ignore
+//! use guile::prelude::*;
+//! ```
+
+pub use crate::{guile_fn, init, GuileVM, OwnedSexp};
+
+pub use crate::{CallbackArgs, FromKeywordArgs, FromScm, HookArgs, ToScm, TryFromScm};
+
+pub use crate::{
+    ProtectedScm, Scm, ScmAlist, ScmArray, ScmAtomicBox, ScmBitvector, ScmBytevector, ScmChar,
+    ScmHashTable, ScmKeyword, ScmList, ScmModule, ScmNumber, ScmProcedure, ScmPromise, ScmString,
+    ScmSymbol, ScmVector, TypedProc,
+};
+
+pub use crate::{CallbackError, ConversionError, ErrorMap, GuileError, PluginError};
+
+#[cfg(feature = "serde")]
+pub use crate::{config::ConfigError, from_scm, DeserializeError};
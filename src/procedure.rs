@@ -0,0 +1,657 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::cell::Cell;
+use std::ffi::CString;
+use std::marker::PhantomData;
+
+use guile_sys::{SCM, SCM_EOL, SCM_UNDEFINED};
+use libc::c_char;
+
+use crate::convert::to_raw;
+use crate::error::{protect, GuileError};
+use crate::scm::{public_ref, read_all, symbol};
+use crate::string;
+use crate::{CallbackArgs, FromScm, GuileVM, Scm, ScmAlist, ToScm};
+
+/* Keeps a value alive while it's only referenced from Rust memory. */
+struct Rooted {
+    raw: SCM,
+}
+
+impl Rooted {
+    unsafe fn new(raw: SCM) -> Rooted {
+        guile_sys::scm_gc_protect_object(raw);
+        Rooted { raw }
+    }
+}
+
+impl Drop for Rooted {
+    fn drop(&mut self) {
+        unsafe { guile_sys::scm_gc_unprotect_object(self.raw) };
+    }
+}
+
+/// A Scheme procedure.
+#[derive(Clone, Copy)]
+pub struct ScmProcedure<'vm> {
+    scm: Scm<'vm>,
+}
+
+fn convert_result<'vm, R: FromScm<'vm>>(value: Scm<'vm>) -> Result<R, GuileError> {
+    R::from_scm(value).ok_or_else(|| {
+        let vm = unsafe { GuileVM::assume_guile_mode() };
+        GuileError::Throw {
+            key: String::from("wrong-type-arg"),
+            message: format!("procedure returned {}, of the wrong type", vm.write(value)),
+        }
+    })
+}
+
+impl<'vm> ScmProcedure<'vm> {
+    /// Returns `None` if `scm` is not a procedure.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmProcedure<'vm>> {
+        if guile_sys::scm_is_true(unsafe { guile_sys::scm_procedure_p(scm.as_raw()) }) {
+            Some(ScmProcedure { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    /// Apply the procedure to `args`, catching any error it throws.
+    pub fn call(&self, args: &[Scm<'vm>]) -> Result<Scm<'vm>, GuileError> {
+        self.scm.call_with_slice(args)
+    }
+
+    /// Convert each item of `args` and apply the procedure to them, for
+    /// calls whose arity is only known at run time.
+    pub fn apply<I>(&self, args: I) -> Result<Scm<'vm>, GuileError>
+    where
+        I: IntoIterator,
+        I::Item: ToScm,
+    {
+        /* Converted arguments must stay where the collector can see them:
+         * up to SMALL_ARITY in an array on the stack, past that consed
+         * onto a (reversed) Scheme list, rather than in a Vec. */
+        const SMALL_ARITY: usize = 8;
+        let vm = unsafe { GuileVM::assume_guile_mode() };
+        let mut small = [SCM_UNDEFINED; SMALL_ARITY];
+        let mut len = 0;
+        let mut spilled = SCM_EOL;
+        for arg in args {
+            let raw = arg.to_scm(&vm).as_raw();
+            if len < SMALL_ARITY {
+                small[len] = raw;
+            } else {
+                if len == SMALL_ARITY {
+                    spilled = small.iter().fold(SCM_EOL, |tail, &arg| unsafe {
+                        guile_sys::scm_cons(arg, tail)
+                    });
+                }
+                spilled = unsafe { guile_sys::scm_cons(raw, spilled) };
+            }
+            len += 1;
+        }
+        let proc_ = self.scm.as_raw();
+        unsafe {
+            let result = protect(|| {
+                if len <= SMALL_ARITY {
+                    guile_sys::scm_call_n(proc_, small.as_mut_ptr(), len)
+                } else {
+                    guile_sys::scm_apply_0(proc_, guile_sys::scm_reverse_x(spilled, SCM_EOL))
+                }
+            })?;
+            Ok(self.scm.with_raw(result))
+        }
+    }
+
+    /// Turn the procedure into a Rust closure taking an argument tuple,
+    /// for APIs that expect one. The closure keeps the procedure from
+    /// being collected, and enters guile mode itself when called.
+    pub fn into_fn<A, R>(self) -> Box<dyn Fn(A) -> Result<R, GuileError> + 'vm>
+    where
+        A: CallbackArgs + 'vm,
+        R: for<'a> FromScm<'a> + 'vm,
+    {
+        let rooted = unsafe { Rooted::new(self.scm.as_raw()) };
+        Box::new(move |args: A| {
+            let args = Cell::new(Some(args));
+            let result = Cell::new(None);
+            crate::init(|vm: GuileVM| {
+                let procedure = ScmProcedure {
+                    scm: unsafe { Scm::from_raw(&vm, rooted.raw) },
+                };
+                let value = args
+                    .take()
+                    .unwrap()
+                    .apply(&vm, procedure)
+                    .and_then(|value| {
+                        R::from_scm(value).ok_or_else(|| GuileError::Throw {
+                            key: String::from("wrong-type-arg"),
+                            message: format!(
+                                "procedure returned {}, of the wrong type",
+                                vm.write(value)
+                            ),
+                        })
+                    });
+                result.set(Some(value));
+            });
+            result.into_inner().unwrap()
+        })
+    }
+
+    /// The bytecode listing `disassemble-program` prints. Fails for
+    /// procedures that aren't compiled to bytecode, such as primitives.
+    pub fn disassemble(&self) -> Result<String, GuileError> {
+        let program = self.scm.as_raw();
+        unsafe {
+            let listing = protect(|| {
+                let port = guile_sys::scm_open_output_string();
+                guile_sys::scm_call_2(
+                    public_ref("system vm disassembler", "disassemble-program"),
+                    program,
+                    port,
+                );
+                guile_sys::scm_get_output_string(port)
+            })?;
+            Ok(string::to_string(listing))
+        }
+    }
+
+    /// The procedure property `key`, such as `name`. `None` if it isn't
+    /// set, or is set to `#f`.
+    pub fn property(&self, key: &str) -> Option<Scm<'vm>> {
+        let raw = unsafe { guile_sys::scm_procedure_property(self.scm.as_raw(), symbol(key)) };
+        if guile_sys::scm_is_true(raw) {
+            Some(self.scm.with_raw(raw))
+        } else {
+            None
+        }
+    }
+
+    /// Set the procedure property `key`, as `set-procedure-property!`
+    /// does. Works for primitives defined from Rust, too.
+    pub fn set_property(&self, key: &str, value: impl ToScm) {
+        unsafe {
+            guile_sys::scm_set_procedure_property_x(self.scm.as_raw(), symbol(key), to_raw(&value));
+        }
+    }
+
+    /// The docstring `procedure-documentation` returns.
+    pub fn documentation(&self) -> Option<String> {
+        let doc = unsafe { guile_sys::scm_procedure_documentation(self.scm.as_raw()) };
+        if guile_sys::scm_is_true(doc) {
+            Some(unsafe { string::to_string(doc) })
+        } else {
+            None
+        }
+    }
+
+    /// Set the docstring shown by `procedure-documentation` and the
+    /// REPL's `,describe`.
+    pub fn set_documentation(&self, doc: &str) {
+        self.set_property("documentation", doc);
+    }
+
+    /// The name it was defined with; `None` for anonymous procedures.
+    pub fn name(&self) -> Option<String> {
+        let name = unsafe { guile_sys::scm_procedure_name(self.scm.as_raw()) };
+        if guile_sys::scm_is_true(unsafe { guile_sys::scm_symbol_p(name) }) {
+            Some(unsafe { string::to_string(guile_sys::scm_symbol_to_string(name)) })
+        } else {
+            None
+        }
+    }
+
+    /// What `procedure-minimum-arity` reports, or `None` if it's unknown.
+    pub fn arity(&self) -> Option<Arity> {
+        let proc_ = self.scm.as_raw();
+        unsafe {
+            let arity = protect(|| {
+                guile_sys::scm_call_1(public_ref("guile", "procedure-minimum-arity"), proc_)
+            })
+            .ok()?;
+            Arity::from_raw(arity)
+        }
+    }
+
+    /// The arity of each clause of a `case-lambda`, from
+    /// `program-arities`; just `arity()` for other procedures.
+    pub fn arities(&self) -> Vec<Arity> {
+        let proc_ = self.scm.as_raw();
+        let mut arities = Vec::new();
+        unsafe {
+            if let Ok(mut list) = protect(|| {
+                let program_arities =
+                    guile_sys::scm_primitive_eval(guile_sys::scm_car(read_all(PROGRAM_ARITIES)));
+                guile_sys::scm_call_1(program_arities, proc_)
+            }) {
+                while guile_sys::scm_is_pair(list) != 0 {
+                    arities.extend(Arity::from_raw(guile_sys::scm_car(list)));
+                    list = guile_sys::scm_cdr(list);
+                }
+            }
+        }
+        if arities.is_empty() {
+            arities.extend(self.arity());
+        }
+        arities
+    }
+
+    /// The `source` procedure property, as `procedure-source` returns
+    /// it. Guile 3 doesn't keep source for compiled or evaluated code, so
+    /// this is `None` unless something set the property.
+    pub fn source(&self) -> Option<Scm<'vm>> {
+        let source = unsafe { guile_sys::scm_procedure_source(self.scm.as_raw()) };
+        if guile_sys::scm_is_true(source) {
+            Some(self.scm.with_raw(source))
+        } else {
+            None
+        }
+    }
+
+    /// All of the procedure's properties.
+    pub fn properties(&self) -> ScmAlist<'vm> {
+        let properties = unsafe { guile_sys::scm_procedure_properties(self.scm.as_raw()) };
+        ScmAlist::from_scm(self.scm.with_raw(properties)).unwrap()
+    }
+}
+
+/* Each clause as (required optional rest?), like
+ * `procedure-minimum-arity`; #f for procedures that aren't programs. */
+const PROGRAM_ARITIES: &str = "
+(lambda (proc)
+  (let ((arities (and ((@ (system vm program) program?) proc)
+                      ((@ (system vm program) program-arities) proc))))
+    (and arities
+         (map (lambda (arity)
+                (list ((@ (system vm program) arity:nreq) arity)
+                      ((@ (system vm program) arity:nopt) arity)
+                      ((@ (system vm program) arity:rest?) arity)))
+              arities))))
+";
+
+/// How many arguments a procedure takes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Arity {
+    pub required: usize,
+    pub optional: usize,
+    /// Whether any number of further arguments are accepted.
+    pub rest: bool,
+}
+
+impl Arity {
+    /// `arity` is `(required optional rest?)`; `None` if it's `#f`.
+    unsafe fn from_raw(arity: SCM) -> Option<Arity> {
+        if guile_sys::scm_is_true(arity) {
+            Some(Arity {
+                required: guile_sys::scm_to_uint64(guile_sys::scm_car(arity)) as usize,
+                optional: guile_sys::scm_to_uint64(guile_sys::scm_cadr(arity)) as usize,
+                rest: guile_sys::scm_is_true(guile_sys::scm_caddr(arity)),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Whether a call with `count` arguments is allowed.
+    pub fn accepts(&self, count: usize) -> bool {
+        count >= self.required && (self.rest || count <= self.required + self.optional)
+    }
+
+    /* E.g. "at least 1 argument" or "between 1 and 3 arguments". */
+    fn describe(&self) -> String {
+        let plural = |count: usize| if count == 1 { "argument" } else { "arguments" };
+        if self.rest {
+            format!("at least {} {}", self.required, plural(self.required))
+        } else if self.optional > 0 {
+            let max = self.required + self.optional;
+            format!("between {} and {} arguments", self.required, max)
+        } else {
+            format!("{} {}", self.required, plural(self.required))
+        }
+    }
+}
+
+impl GuileVM {
+    fn procedure_ref(
+        &self,
+        module: &str,
+        name: &str,
+        lookup: unsafe extern "C" fn(*const c_char, *const c_char) -> SCM,
+    ) -> Result<ScmProcedure<'_>, GuileError> {
+        let module_name = CString::new(module).expect("module name contains a NUL byte");
+        let c_name = CString::new(name).expect("binding name contains a NUL byte");
+        let value = unsafe {
+            let raw = protect(|| lookup(module_name.as_ptr(), c_name.as_ptr()))?;
+            Scm::from_raw(self, raw)
+        };
+        ScmProcedure::from_scm(value).ok_or_else(|| GuileError::Throw {
+            key: String::from("wrong-type-arg"),
+            message: format!(
+                "({} {}) is {}, not a procedure",
+                module,
+                name,
+                self.write(value)
+            ),
+        })
+    }
+
+    /// The procedure `name` exported from `module`, given as
+    /// space-separated components: `public_ref("srfi srfi-1", "fold")` is
+    /// `(@ (srfi srfi-1) fold)`. Loads the module if needed; fails if
+    /// there's no such module or export, or it isn't a procedure.
+    pub fn public_ref(&self, module: &str, name: &str) -> Result<ScmProcedure<'_>, GuileError> {
+        self.procedure_ref(module, name, guile_sys::scm_c_public_ref)
+    }
+
+    /// As `public_ref`, but for any top-level binding in `module`,
+    /// exported or not, like `(@@ module name)`.
+    pub fn private_ref(&self, module: &str, name: &str) -> Result<ScmProcedure<'_>, GuileError> {
+        self.procedure_ref(module, name, guile_sys::scm_c_private_ref)
+    }
+}
+
+/* `scm_call_N` for fixed arities, so short calls don't build an
+ * argument array. */
+macro_rules! fixed_calls {
+    ($($name:ident $call:ident ($($arg:ident),*);)*) => {$(
+        /// Call this value with the given arguments, as `call_with_slice`.
+        #[allow(clippy::too_many_arguments)]
+        pub fn $name(&self, $($arg: Scm<'vm>),*) -> Result<Scm<'vm>, GuileError> {
+            let proc_ = self.checked_procedure()?;
+            $(let $arg = $arg.as_raw();)*
+            unsafe {
+                let result = protect(|| guile_sys::$call(proc_, $($arg),*))?;
+                Ok(self.with_raw(result))
+            }
+        }
+    )*};
+}
+
+impl<'vm> Scm<'vm> {
+    fn checked_procedure(&self) -> Result<SCM, GuileError> {
+        if guile_sys::scm_is_true(unsafe { guile_sys::scm_procedure_p(self.as_raw()) }) {
+            Ok(self.as_raw())
+        } else {
+            let vm = unsafe { GuileVM::assume_guile_mode() };
+            Err(GuileError::Throw {
+                key: String::from("wrong-type-arg"),
+                message: format!("not a procedure: {}", vm.write(*self)),
+            })
+        }
+    }
+
+    /// Call this value with `args`, catching any error it throws. Fails
+    /// with `wrong-type-arg` without calling anything if it isn't a
+    /// procedure.
+    pub fn call_with_slice(&self, args: &[Scm<'vm>]) -> Result<Scm<'vm>, GuileError> {
+        let proc_ = self.checked_procedure()?;
+        let mut raw: Vec<SCM> = args.iter().map(|arg| arg.as_raw()).collect();
+        unsafe {
+            let result = protect(|| guile_sys::scm_call_n(proc_, raw.as_mut_ptr(), raw.len()))?;
+            Ok(self.with_raw(result))
+        }
+    }
+
+    fixed_calls! {
+        call0 scm_call_0 ();
+        call1 scm_call_1 (a);
+        call2 scm_call_2 (a, b);
+        call3 scm_call_3 (a, b, c);
+        call4 scm_call_4 (a, b, c, d);
+        call5 scm_call_5 (a, b, c, d, e);
+        call6 scm_call_6 (a, b, c, d, e, f);
+        call7 scm_call_7 (a, b, c, d, e, f, g);
+        call8 scm_call_8 (a, b, c, d, e, f, g, h);
+        call9 scm_call_9 (a, b, c, d, e, f, g, h, i);
+    }
+}
+
+/// A procedure checked to accept `A::LEN` arguments, called with an
+/// argument tuple `A` and returning an `R`.
+pub struct TypedProc<'vm, A, R> {
+    procedure: ScmProcedure<'vm>,
+    _signature: PhantomData<fn(A) -> R>,
+}
+
+impl<'vm, A, R> TypedProc<'vm, A, R>
+where
+    A: CallbackArgs,
+    R: FromScm<'vm>,
+{
+    /// Fails with `wrong-number-of-args` if `procedure` can't be called
+    /// with `A::LEN` arguments.
+    pub fn new(procedure: ScmProcedure<'vm>) -> Result<TypedProc<'vm, A, R>, GuileError> {
+        if let Some(arity) = procedure.arity() {
+            if !arity.accepts(A::LEN) {
+                return Err(GuileError::Throw {
+                    key: String::from("wrong-number-of-args"),
+                    message: format!("procedure takes {}, not {}", arity.describe(), A::LEN),
+                });
+            }
+        }
+        Ok(TypedProc {
+            procedure,
+            _signature: PhantomData,
+        })
+    }
+
+    pub fn procedure(&self) -> ScmProcedure<'vm> {
+        self.procedure
+    }
+
+    pub fn call(&self, args: A) -> Result<R, GuileError> {
+        let vm = unsafe { GuileVM::assume_guile_mode() };
+        let procedure = ScmProcedure {
+            scm: unsafe { Scm::from_raw(&vm, self.procedure.scm.as_raw()) },
+        };
+        let value = args.apply(&vm, procedure)?;
+        convert_result(self.procedure.scm.with_raw(value.as_raw()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language, ScmString, ScmSymbol};
+
+    #[test]
+    fn call() {
+        init(|vm| {
+            let f = vm.eval_language(Language::Scheme, "string-upcase").unwrap();
+            let f = ScmProcedure::from_scm(f).unwrap();
+            let result = f.call(&[ScmString::new(&vm, "abc").as_scm()]).unwrap();
+            assert_eq!(ScmString::from_scm(result).unwrap().to_string(), "ABC");
+
+            assert!(f.call(&[]).is_err());
+            assert!(ScmProcedure::from_scm(result).is_none());
+        });
+    }
+
+    #[test]
+    fn scm_calls() {
+        init(|vm| {
+            let list = vm.eval_language(Language::Scheme, "list").unwrap();
+            let n = |x: i32| x.to_scm(&vm);
+            assert_eq!(vm.write(list.call0().unwrap()), "()");
+            assert_eq!(vm.write(list.call2(n(1), n(2)).unwrap()), "(1 2)");
+            let nine = list
+                .call9(n(1), n(2), n(3), n(4), n(5), n(6), n(7), n(8), n(9))
+                .unwrap();
+            assert_eq!(vm.write(nine), "(1 2 3 4 5 6 7 8 9)");
+            let ten: Vec<Scm> = (1..=10).map(n).collect();
+            assert_eq!(
+                vm.write(list.call_with_slice(&ten).unwrap()),
+                "(1 2 3 4 5 6 7 8 9 10)"
+            );
+
+            let car = vm.eval_language(Language::Scheme, "car").unwrap();
+            assert!(car.call1(n(1)).is_err());
+            let error = n(1).call1(n(2)).unwrap_err();
+            assert_eq!(error.key(), "wrong-type-arg");
+        });
+    }
+
+    #[test]
+    fn apply() {
+        init(|vm| {
+            let list = vm.eval_language(Language::Scheme, "list").unwrap();
+            let list = ScmProcedure::from_scm(list).unwrap();
+            assert_eq!(vm.write(list.apply(Vec::<i32>::new()).unwrap()), "()");
+            assert_eq!(vm.write(list.apply(["a", "b"]).unwrap()), "(\"a\" \"b\")");
+            for len in [8, 9, 20] {
+                let expected: Vec<String> = (0..len).map(|n| n.to_string()).collect();
+                let result = list.apply((0..len).map(|n| n as i64)).unwrap();
+                assert_eq!(vm.write(result), format!("({})", expected.join(" ")));
+            }
+        });
+    }
+
+    #[test]
+    fn module_refs() {
+        init(|vm| {
+            let fold = vm.public_ref("srfi srfi-1", "fold").unwrap();
+            let plus = vm.public_ref("guile", "+").unwrap();
+            let sum = fold
+                .call(&[plus.as_scm(), 0.to_scm(&vm), vec![1, 2, 3].to_scm(&vm)])
+                .unwrap();
+            assert_eq!(sum.get::<i32>(), Some(6));
+
+            assert!(vm.public_ref("srfi srfi-1", "no-such-binding").is_err());
+            assert!(vm.public_ref("no such module", "fold").is_err());
+            let Err(error) = vm.public_ref("guile", "%load-path") else {
+                panic!("%load-path isn't a procedure");
+            };
+            assert_eq!(error.key(), "wrong-type-arg");
+
+            vm.eval_language(
+                Language::Scheme,
+                "(define-module (guile-rs test private)) (define (hidden) 1)",
+            )
+            .unwrap();
+            assert!(vm.public_ref("guile-rs test private", "hidden").is_err());
+            let hidden = vm.private_ref("guile-rs test private", "hidden").unwrap();
+            assert_eq!(hidden.call(&[]).unwrap().get::<i32>(), Some(1));
+        });
+    }
+
+    #[test]
+    fn into_fn() {
+        init(|vm| {
+            let f = vm
+                .eval_language(Language::Scheme, "(lambda (a b) (* a b))")
+                .unwrap();
+            let multiply = ScmProcedure::from_scm(f)
+                .unwrap()
+                .into_fn::<(i32, i32), i64>();
+            unsafe { guile_sys::scm_gc() };
+            assert_eq!(multiply((6, 7)), Ok(42));
+            assert!(multiply((6, i32::MAX)).is_ok());
+
+            let f = vm
+                .eval_language(Language::Scheme, "symbol->string")
+                .unwrap();
+            let to_number = ScmProcedure::from_scm(f).unwrap().into_fn::<(i32,), i32>();
+            assert!(to_number((1,)).is_err());
+        });
+    }
+
+    #[test]
+    fn typed_proc() {
+        init(|vm| {
+            let f = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(lambda* (s #:optional (n 1)) (string-length s))",
+                )
+                .unwrap();
+            let f = ScmProcedure::from_scm(f).unwrap();
+            let length = TypedProc::<(String,), usize>::new(f).unwrap();
+            assert_eq!(length.call(("abc".to_string(),)), Ok(3));
+            assert!(TypedProc::<(String, i32), usize>::new(f).is_ok());
+            assert!(TypedProc::<(), usize>::new(f).is_err());
+            let error = TypedProc::<(String, i32, i32), usize>::new(f)
+                .err()
+                .unwrap();
+            assert_eq!(
+                error.to_string(),
+                "procedure takes between 1 and 2 arguments, not 3"
+            );
+            assert!(TypedProc::<(String,), String>::new(f)
+                .unwrap()
+                .call(("abc".to_string(),))
+                .is_err());
+        });
+    }
+
+    #[test]
+    fn disassemble() {
+        init(|vm| {
+            let thunk = vm.compile("(+ 1 2)").unwrap();
+            assert!(!thunk.disassemble().unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn introspection() {
+        init(|vm| {
+            let eval = |code| {
+                let value = vm.eval_language(Language::Scheme, code).unwrap();
+                ScmProcedure::from_scm(value).unwrap()
+            };
+            let f = eval("(define* (greet name #:optional greeting . rest) name) greet");
+            assert_eq!(f.name().as_deref(), Some("greet"));
+            let arity = f.arity().unwrap();
+            assert_eq!(
+                arity,
+                Arity {
+                    required: 1,
+                    optional: 1,
+                    rest: true
+                }
+            );
+            assert!(!arity.accepts(0));
+            assert!(arity.accepts(5));
+            assert_eq!(eval("(lambda (x) x)").name(), None);
+            assert_eq!(eval("car").name().as_deref(), Some("car"));
+
+            let clauses = eval("(case-lambda ((x) x) ((x y . z) y))").arities();
+            assert_eq!(clauses.len(), 2);
+            assert_eq!((clauses[0].required, clauses[0].rest), (1, false));
+            assert_eq!((clauses[1].required, clauses[1].rest), (2, true));
+            assert_eq!(eval("cons").arities().len(), 1);
+
+            let f = eval("(let ((f (lambda (x) x))) (set-procedure-property! f 'tag 'x) f)");
+            assert!(f
+                .properties()
+                .assq(&ScmSymbol::new(&vm, "tag").as_scm())
+                .is_some());
+
+            assert!(f.source().is_none());
+            let expr = vm
+                .eval_language(Language::Scheme, "'(lambda (x) x)")
+                .unwrap();
+            f.set_property("source", expr);
+            assert!(f.source().unwrap().is_equal(expr));
+        });
+    }
+}
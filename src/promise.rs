@@ -0,0 +1,132 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::sync::Mutex;
+
+use crate::closure::make_procedure;
+use crate::error::{protect, GuileError};
+use crate::{GuileVM, Scm, ToScm};
+
+/// A Scheme promise, as made by `delay`: a value computed the first time
+/// it's forced and remembered after that.
+#[derive(Clone, Copy)]
+pub struct ScmPromise<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> ScmPromise<'vm> {
+    /// Returns `None` if `scm` is not a promise.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmPromise<'vm>> {
+        if guile_sys::scm_is_true(unsafe { guile_sys::scm_promise_p(scm.as_raw()) }) {
+            Some(ScmPromise { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    /// The promise's value, computing it if this is the first time.
+    /// Fails if computing it throws. A promise made by Scheme's `delay`
+    /// runs its body again on the next `force`; one made by
+    /// `GuileVM::delay` has used up its closure and keeps failing.
+    pub fn force(&self) -> Result<Scm<'vm>, GuileError> {
+        let promise = self.scm.as_raw();
+        unsafe {
+            let value = protect(|| guile_sys::scm_force(promise))?;
+            Ok(self.scm.with_raw(value))
+        }
+    }
+}
+
+impl GuileVM {
+    /// A promise whose value is `f()`, called the first time the promise
+    /// is forced from either Rust or Scheme. `f` runs at most once, so if
+    /// it panics, forcing the promise again fails too.
+    pub fn delay<F, T>(&self, f: F) -> ScmPromise<'_>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: ToScm,
+    {
+        let f = Mutex::new(Some(f));
+        unsafe {
+            let thunk = make_procedure(
+                "rust-delay",
+                Box::new(move |_args| {
+                    let vm = GuileVM::assume_guile_mode();
+                    let f = f.lock().unwrap().take().ok_or_else(|| GuileError::Throw {
+                        key: "misc-error".into(),
+                        message: "delayed closure failed the first time it was forced".into(),
+                    })?;
+                    Ok(f().to_scm(&vm).as_raw())
+                }),
+            );
+            ScmPromise {
+                scm: Scm::from_raw(self, guile_sys::scm_make_promise(thunk)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language, ScmProcedure};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn forced_once() {
+        init(|vm| {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let counter = calls.clone();
+            let promise = vm.delay(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                42i32
+            });
+            assert_eq!(calls.load(Ordering::SeqCst), 0);
+            assert_eq!(promise.force().unwrap().get::<i32>(), Some(42));
+
+            let force = vm.eval_language(Language::Scheme, "force").unwrap();
+            let forced = ScmProcedure::from_scm(force)
+                .unwrap()
+                .call(&[promise.as_scm()])
+                .unwrap();
+            assert_eq!(forced.get::<i32>(), Some(42));
+            assert_eq!(calls.load(Ordering::SeqCst), 1);
+        });
+    }
+
+    #[test]
+    fn from_scheme() {
+        init(|vm| {
+            let scm = vm
+                .eval_language(Language::Scheme, "(delay (error \"not yet\"))")
+                .unwrap();
+            let promise = ScmPromise::from_scm(scm).unwrap();
+            assert!(promise.force().is_err());
+            assert!(ScmPromise::from_scm(1i32.to_scm(&vm)).is_none());
+
+            let panicky = vm.delay(|| -> i32 { panic!("no value") });
+            assert!(panicky.force().is_err());
+            let again = panicky.force().unwrap_err();
+            assert!(again.to_string().contains("failed the first time"));
+        });
+    }
+}
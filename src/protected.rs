@@ -0,0 +1,104 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use guile_sys::SCM;
+
+use crate::{GuileVM, Scm};
+
+/// A Scheme value protected from the GC until dropped, so it can be kept
+/// in Rust memory, sent to other threads, and used again in a later
+/// `init` call.
+pub struct ProtectedScm {
+    raw: SCM,
+}
+
+/* The value is only touched in guile mode, and protection is global to
+ * the process, so the handle can move between threads. */
+unsafe impl Send for ProtectedScm {}
+unsafe impl Sync for ProtectedScm {}
+
+impl ProtectedScm {
+    pub fn new(value: Scm) -> ProtectedScm {
+        let raw = value.as_raw();
+        unsafe { guile_sys::scm_gc_protect_object(raw) };
+        ProtectedScm { raw }
+    }
+
+    /// The value, usable for as long as `vm` is.
+    pub fn get<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe { Scm::from_raw(vm, self.raw) }
+    }
+}
+
+impl Clone for ProtectedScm {
+    fn clone(&self) -> ProtectedScm {
+        let raw = self.raw;
+        crate::init(|_| unsafe {
+            guile_sys::scm_gc_protect_object(raw);
+        });
+        ProtectedScm { raw }
+    }
+}
+
+impl Drop for ProtectedScm {
+    /// Enters guile mode if the thread isn't in it already.
+    fn drop(&mut self) {
+        let raw = self.raw;
+        crate::init(|_| unsafe {
+            guile_sys::scm_gc_unprotect_object(raw);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::{init, FromScm, ToScm};
+
+    #[test]
+    fn survives_across_init() {
+        let handle = RefCell::new(None);
+        init(|vm| {
+            let value = vec!["kept", "alive"].to_scm(&vm);
+            *handle.borrow_mut() = Some(ProtectedScm::new(value));
+        });
+        let handle = handle.into_inner().unwrap();
+        let handle = std::thread::spawn(move || {
+            init(|_| unsafe {
+                guile_sys::scm_gc();
+            });
+            handle
+        })
+        .join()
+        .unwrap();
+
+        let copy = handle.clone();
+        drop(handle);
+        init(|_| unsafe {
+            guile_sys::scm_gc();
+        });
+        init(|vm| {
+            let value = copy.get(&vm);
+            assert_eq!(
+                Vec::<String>::from_scm(value),
+                Some(vec!["kept".into(), "alive".into()])
+            );
+        });
+    }
+}
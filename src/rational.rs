@@ -0,0 +1,135 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use num_bigint::{BigInt, BigUint, Sign};
+use num_rational::BigRational;
+
+use guile_sys::{SCM, SCM_UNDEFINED};
+
+use crate::string;
+use crate::{FromScm, GuileVM, Scm, ToScm};
+
+/* Bignums cross over as decimal strings: libguile doesn't expose GMP
+ * limbs, and this keeps us independent of num-bigint's digit size. */
+
+unsafe fn bigint_to_raw(n: &BigInt) -> SCM {
+    guile_sys::scm_string_to_number(string::from_str(&n.to_string()), SCM_UNDEFINED)
+}
+
+/// `raw` must be an exact integer.
+unsafe fn raw_to_bigint(raw: SCM) -> Option<BigInt> {
+    string::to_string(guile_sys::scm_number_to_string(raw, SCM_UNDEFINED))
+        .parse()
+        .ok()
+}
+
+impl ToScm for BigInt {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe { Scm::from_raw(vm, bigint_to_raw(self)) }
+    }
+}
+
+/// Only exact integers convert.
+impl<'vm> FromScm<'vm> for BigInt {
+    fn from_scm(scm: Scm<'vm>) -> Option<BigInt> {
+        unsafe {
+            if guile_sys::scm_is_exact_integer(scm.as_raw()) != 0 {
+                raw_to_bigint(scm.as_raw())
+            } else {
+                None
+            }
+        }
+    }
+}
+
+impl ToScm for BigUint {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        BigInt::from(self.clone()).to_scm(vm)
+    }
+}
+
+/// Only non-negative exact integers convert.
+impl<'vm> FromScm<'vm> for BigUint {
+    fn from_scm(scm: Scm<'vm>) -> Option<BigUint> {
+        BigInt::from_scm(scm)?.to_biguint()
+    }
+}
+
+/// Panics on a zero denominator, which only `BigRational::new_raw` can
+/// produce; Guile would otherwise throw from `/`.
+impl ToScm for BigRational {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        assert!(
+            self.denom().sign() != Sign::NoSign,
+            "BigRational with a zero denominator"
+        );
+        unsafe {
+            let numer = bigint_to_raw(self.numer());
+            let denom = bigint_to_raw(self.denom());
+            Scm::from_raw(vm, guile_sys::scm_divide(numer, denom))
+        }
+    }
+}
+
+/// Only exact rationals (including integers) convert; an inexact number
+/// like `0.5` gives `None` rather than silently becoming `1/2`.
+impl<'vm> FromScm<'vm> for BigRational {
+    fn from_scm(scm: Scm<'vm>) -> Option<BigRational> {
+        let raw = scm.as_raw();
+        unsafe {
+            if guile_sys::scm_is_rational(raw) == 0 || guile_sys::scm_is_exact(raw) == 0 {
+                return None;
+            }
+            let numer = raw_to_bigint(guile_sys::scm_numerator(raw))?;
+            let denom = raw_to_bigint(guile_sys::scm_denominator(raw))?;
+            Some(BigRational::new_raw(numer, denom))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language};
+
+    #[test]
+    fn exact_ratios() {
+        init(|vm| {
+            let third = vm.eval_language(Language::Scheme, "(/ -1 3)").unwrap();
+            let r = BigRational::from_scm(third).unwrap();
+            assert_eq!(r, BigRational::new(BigInt::from(-1), BigInt::from(3)));
+            assert_eq!(vm.write(r.to_scm(&vm)), "-1/3");
+
+            let big = vm.eval_language(Language::Scheme, "(expt 10 40)").unwrap();
+            let n = BigInt::from_scm(big).unwrap();
+            assert_eq!(n.to_string(), format!("1{}", "0".repeat(40)));
+            assert_eq!(
+                BigRational::from_scm(big),
+                Some(BigRational::from_integer(n))
+            );
+
+            let inexact = vm.eval_language(Language::Scheme, "0.5").unwrap();
+            assert_eq!(BigRational::from_scm(inexact), None);
+            assert_eq!(BigInt::from_scm(third), None);
+
+            let negative = BigInt::from(-5).to_scm(&vm);
+            assert_eq!(BigUint::from_scm(negative), None);
+            let n = BigUint::from_scm(big).unwrap();
+            assert_eq!(BigUint::from_scm(n.to_scm(&vm)), Some(n));
+        });
+    }
+}
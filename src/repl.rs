@@ -0,0 +1,421 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::ptr;
+
+use guile_sys::{SCM, SCM_BOOL_F};
+use libc::c_void;
+
+use crate::closure::make_procedure;
+use crate::error::{protect, GuileError};
+use crate::scm::read_all;
+use crate::string;
+use crate::{GuileVM, Scm, ScmModule};
+
+thread_local! {
+    /// The `Repl` whose `run` is active on this thread, if any.
+    static RUNNING: Cell<*mut c_void> = const { Cell::new(ptr::null_mut()) };
+}
+
+/* Puts a value back in `RUNNING` when dropped, even by a panic. */
+struct Restore(*mut c_void);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        RUNNING.with(|running| running.set(self.0));
+    }
+}
+
+/* An input port whose characters come from calling `next-line`, which
+ * returns a line without its newline, or #f at the end of input. */
+const MAKE_LINE_PORT: &str = "
+(lambda (next-line)
+  (let ((pending \"\") (pos 0))
+    (make-soft-port
+     (vector (lambda (c) #f)
+             (lambda (s) #f)
+             (lambda () #f)
+             (lambda ()
+               (when (= pos (string-length pending))
+                 (let ((line (next-line)))
+                   (set! pending (if line (string-append line \"\\n\") \"\"))
+                   (set! pos 0)))
+               (if (= pos (string-length pending))
+                   (eof-object)
+                   (let ((c (string-ref pending pos)))
+                     (set! pos (+ pos 1))
+                     c)))
+             (lambda () #f))
+     \"r\")))
+";
+
+/* The prompt is left to the line source, and errors are reported
+ * instead of entering a nested debugger REPL. */
+const RUN_REPL: &str = "
+(lambda (port)
+  (let ((repl ((@ (system repl common) make-repl) 'scheme)))
+    ((@ (system repl common) repl-option-set!) repl 'prompt \"\")
+    ((@ (system repl common) repl-option-set!) repl 'on-error 'report)
+    (parameterize ((current-input-port port))
+      ((@ (system repl repl) run-repl) repl))))
+";
+
+/* Names bound in the current module or the modules it uses. */
+const COMPLETIONS: &str = "
+(lambda (prefix)
+  (let ((names '()))
+    (define (add! name var)
+      (let ((name (symbol->string name)))
+        (when (string-prefix? prefix name)
+          (set! names (cons name names)))))
+    (module-for-each add! (current-module))
+    (for-each (lambda (module) (module-for-each add! module))
+              (module-uses (current-module)))
+    names))
+";
+
+/* (start form end), with character offsets around the datum read. */
+const READ_FORM: &str = "
+(lambda (port)
+  (let* ((start (seek port 0 SEEK_CUR))
+         (form (read port)))
+    (list start form (seek port 0 SEEK_CUR))))
+";
+
+/* The written representations of the values `form` returns, leaving out
+ * unspecified ones like the REPL does. */
+const EVAL_FORM: &str = "
+(lambda (form port)
+  (parameterize ((current-output-port port)
+                 (current-error-port port))
+    (call-with-values (lambda () (primitive-eval form))
+      (lambda vals
+        (map object->string
+             (filter (lambda (val) (not (unspecified? val))) vals))))))
+";
+
+unsafe fn eval_lambda(code: &str) -> SCM {
+    guile_sys::scm_primitive_eval(guile_sys::scm_car(read_all(code)))
+}
+
+type ReadLine<'a> = Box<dyn FnMut(&str, &Completer) -> Option<String> + 'a>;
+type Prompt<'a> = Box<dyn FnMut(&str) -> String + 'a>;
+type Completion<'a> = Box<dyn Fn(&str) -> Vec<String> + 'a>;
+type Command<'a> = Box<dyn FnMut(&str) -> bool + 'a>;
+
+/// Completes identifiers for a line source, from the bindings visible in
+/// the REPL's module and the host's completion callback.
+pub struct Completer<'r> {
+    pub(crate) extra: Option<&'r Completion<'r>>,
+}
+
+impl<'r> Completer<'r> {
+    /// Every known name starting with `prefix`, sorted.
+    pub fn complete(&self, prefix: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        unsafe {
+            let prefix_scm = string::from_str(prefix);
+            if let Ok(mut list) =
+                protect(|| guile_sys::scm_call_1(eval_lambda(COMPLETIONS), prefix_scm))
+            {
+                while guile_sys::scm_is_pair(list) != 0 {
+                    names.push(string::to_string(guile_sys::scm_car(list)));
+                    list = guile_sys::scm_cdr(list);
+                }
+            }
+        }
+        if let Some(extra) = self.extra {
+            names.extend(extra(prefix));
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+/// A Scheme REPL reading from a host-supplied line source, for
+/// applications that draw their own terminal UI.
+///
+/// Evaluation and printing are done by `(system repl repl)`, with output
+/// going to the current output port.
+pub struct Repl<'a> {
+    read_line: ReadLine<'a>,
+    prompt: Option<Prompt<'a>>,
+    completion: Option<Completion<'a>>,
+    commands: HashMap<String, Command<'a>>,
+}
+
+impl<'a> Repl<'a> {
+    /// Read lines by calling `read_line` with the prompt to show, until it
+    /// returns `None`.
+    pub fn new<F>(read_line: F) -> Repl<'a>
+    where
+        F: FnMut(&str, &Completer) -> Option<String> + 'a,
+    {
+        Repl {
+            read_line: Box::new(read_line),
+            prompt: None,
+            completion: None,
+            commands: HashMap::new(),
+        }
+    }
+
+    /// Build the prompt from the current module's name, instead of
+    /// Guile's `scheme@(guile-user)> `.
+    pub fn prompt<F: FnMut(&str) -> String + 'a>(mut self, prompt: F) -> Repl<'a> {
+        self.prompt = Some(Box::new(prompt));
+        self
+    }
+
+    /// Add the names `completion` returns for a prefix to the ones the
+    /// `Completer` finds itself.
+    pub fn completion<F: Fn(&str) -> Vec<String> + 'a>(mut self, completion: F) -> Repl<'a> {
+        self.completion = Some(Box::new(completion));
+        self
+    }
+
+    /// Intercept the meta-command `,name`, calling `handler` with the rest
+    /// of the line. If it returns `false`, the line is passed on to the
+    /// REPL after all.
+    pub fn command<F: FnMut(&str) -> bool + 'a>(mut self, name: &str, handler: F) -> Repl<'a> {
+        self.commands.insert(name.to_string(), Box::new(handler));
+        self
+    }
+
+    fn next_line(&mut self, vm: &GuileVM) -> Option<String> {
+        loop {
+            let module = unsafe { Scm::from_raw(vm, guile_sys::scm_current_module()) };
+            let module = ScmModule::from_scm(module)
+                .map(|m| m.name())
+                .unwrap_or_default();
+            let prompt = match &mut self.prompt {
+                Some(prompt) => prompt(&module),
+                None => format!("scheme@({})> ", module),
+            };
+            let completer = Completer {
+                extra: self.completion.as_ref(),
+            };
+            let line = (self.read_line)(&prompt, &completer)?;
+            if let Some(command) = line.trim_start().strip_prefix(',') {
+                let (name, args) = command
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((command, ""));
+                if let Some(handler) = self.commands.get_mut(name) {
+                    if handler(args.trim()) {
+                        continue;
+                    }
+                }
+            }
+            return Some(line);
+        }
+    }
+
+    /// Run the REPL until the line source runs out.
+    pub fn run(&mut self, _vm: &GuileVM) -> Result<(), GuileError> {
+        /* The port can outlive this call if Scheme code holds on to it,
+         * and the callback has to be `Send`, so it doesn't capture
+         * `self`: it finds it in `RUNNING`, which only holds it on this
+         * thread for the duration of the call. */
+        let this = self as *mut Repl as *mut c_void;
+        let _restore = Restore(RUNNING.with(|running| running.replace(this)));
+        let id = this as usize;
+        let result = unsafe {
+            let next_line = make_procedure(
+                "repl-next-line",
+                Box::new(move |_args| {
+                    /* Take it out while `read_line` runs, so reading the
+                     * port again from inside it sees the end of input
+                     * instead of a second `&mut`. */
+                    let this = RUNNING.with(|running| running.replace(ptr::null_mut()));
+                    let _restore = Restore(this);
+                    if this as usize != id {
+                        return Ok(SCM_BOOL_F);
+                    }
+                    let repl = &mut *(this as *mut Repl);
+                    Ok(match repl.next_line(&GuileVM::assume_guile_mode()) {
+                        Some(line) => string::from_str(&line),
+                        None => SCM_BOOL_F,
+                    })
+                }),
+            );
+            protect(|| {
+                let port = guile_sys::scm_call_1(eval_lambda(MAKE_LINE_PORT), next_line);
+                guile_sys::scm_call_1(eval_lambda(RUN_REPL), port)
+            })
+        };
+        result.map(|_| ())
+    }
+}
+
+/// One form read and evaluated by `GuileVM::run_session`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Interaction {
+    /// The source text of the form.
+    pub input: String,
+    /// The written representations of the values it returned.
+    pub values: Vec<String>,
+    /// What it wrote to the current output and error ports.
+    pub output: String,
+    /// The error it raised, if any. Reading errors end the session.
+    pub error: Option<GuileError>,
+}
+
+unsafe fn string_list(mut list: SCM) -> Vec<String> {
+    let mut strings = Vec::new();
+    while guile_sys::scm_is_pair(list) != 0 {
+        strings.push(string::to_string(guile_sys::scm_car(list)));
+        list = guile_sys::scm_cdr(list);
+    }
+    strings
+}
+
+impl GuileVM {
+    /// Read and evaluate every form in `input` like the REPL would,
+    /// writing a transcript to `output` and returning each interaction.
+    pub fn run_session<R: Read, W: Write>(
+        &self,
+        mut input: R,
+        mut output: W,
+    ) -> io::Result<Vec<Interaction>> {
+        let mut source = String::new();
+        input.read_to_string(&mut source)?;
+        let mut interactions = Vec::new();
+        let mut value_count = 0;
+        unsafe {
+            let port = guile_sys::scm_open_input_string(string::from_str(&source));
+            loop {
+                let read = protect(|| guile_sys::scm_call_1(eval_lambda(READ_FORM), port));
+                let (form, input) = match read {
+                    Ok(read) => {
+                        let form = guile_sys::scm_cadr(read);
+                        if guile_sys::scm_is_true(guile_sys::scm_eof_object_p(form)) {
+                            break;
+                        }
+                        let start = guile_sys::scm_to_uint64(guile_sys::scm_car(read)) as usize;
+                        let end = guile_sys::scm_to_uint64(guile_sys::scm_caddr(read)) as usize;
+                        /* String ports hold UTF-8, so these are byte
+                         * offsets into `source`. */
+                        let input = &source[start..end];
+                        (form, input.trim().to_string())
+                    }
+                    Err(error) => {
+                        writeln!(output, "{}", error)?;
+                        interactions.push(Interaction {
+                            input: String::new(),
+                            values: Vec::new(),
+                            output: String::new(),
+                            error: Some(error),
+                        });
+                        break;
+                    }
+                };
+
+                let out = guile_sys::scm_open_output_string();
+                let result = protect(|| guile_sys::scm_call_2(eval_lambda(EVAL_FORM), form, out));
+                let text = string::to_string(guile_sys::scm_get_output_string(out));
+                let (values, error) = match result {
+                    Ok(values) => (string_list(values), None),
+                    Err(error) => (Vec::new(), Some(error)),
+                };
+
+                writeln!(output, "> {}", input)?;
+                write!(output, "{}", text)?;
+                for value in &values {
+                    value_count += 1;
+                    writeln!(output, "${} = {}", value_count, value)?;
+                }
+                if let Some(error) = &error {
+                    writeln!(output, "{}", error)?;
+                }
+                interactions.push(Interaction {
+                    input,
+                    values,
+                    output: text,
+                    error,
+                });
+            }
+        }
+        Ok(interactions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language};
+    use std::cell::RefCell;
+
+    #[test]
+    fn run_lines() {
+        init(|vm| {
+            let mut lines = vec![
+                "(define repl-x 2)",
+                ",double 21",
+                "(define repl-y (+ repl-x 1))",
+            ]
+            .into_iter();
+            let prompts = RefCell::new(Vec::new());
+            let doubled = RefCell::new(Vec::new());
+            let completions = RefCell::new(Vec::new());
+            Repl::new(|prompt, completer| {
+                prompts.borrow_mut().push(prompt.to_string());
+                *completions.borrow_mut() = completer.complete("repl-");
+                lines.next().map(String::from)
+            })
+            .prompt(|module| format!("{}> ", module))
+            .completion(|prefix| vec![format!("{}host", prefix)])
+            .command("double", |args| {
+                doubled.borrow_mut().push(args.parse::<i32>().unwrap() * 2);
+                true
+            })
+            .run(&vm)
+            .unwrap();
+
+            assert_eq!(prompts.borrow()[0], "guile-user> ");
+            assert_eq!(*doubled.borrow(), vec![42]);
+            assert_eq!(*completions.borrow(), vec!["repl-host", "repl-x", "repl-y"]);
+            let y = vm.eval_language(Language::Scheme, "repl-y").unwrap();
+            assert_eq!(unsafe { guile_sys::scm_to_int64(y.as_raw()) }, 3);
+        });
+    }
+
+    #[test]
+    fn session() {
+        init(|vm| {
+            let input = "(display \"hi\") (values 1 \"λ\")\n(car '())";
+            let mut transcript = Vec::new();
+            let interactions = vm.run_session(input.as_bytes(), &mut transcript).unwrap();
+            assert_eq!(interactions.len(), 3);
+            assert_eq!(interactions[0].input, "(display \"hi\")");
+            assert_eq!(interactions[0].output, "hi");
+            assert!(interactions[0].values.is_empty());
+            assert_eq!(interactions[1].input, "(values 1 \"λ\")");
+            assert_eq!(interactions[1].values, vec!["1", "\"λ\""]);
+            assert_eq!(interactions[2].input, "(car '())");
+            assert_eq!(
+                interactions[2].error.as_ref().unwrap().key(),
+                "wrong-type-arg"
+            );
+
+            let transcript = String::from_utf8(transcript).unwrap();
+            assert!(transcript.contains("$2 = \"λ\""));
+        });
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use guile_sys::{SCM, SCM_EOL};
+
+use crate::error::{protect, GuileError};
+use crate::scm::{keyword, list_from_raw, public_ref, read_all, symbol};
+use crate::{GuileVM, Limits, Scm};
+
+/// A set of bindings a sandbox may use. The named sets are the ones
+/// exported by `(ice-9 sandbox)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Bindings {
+    /// `all-pure-bindings`: everything that can't mutate shared state.
+    AllPure,
+    /// `all-pure-and-impure-bindings`: also allows mutating data the
+    /// sandboxed code allocated itself.
+    AllPureAndImpure,
+    Alist,
+    Bitvector,
+    Char,
+    Core,
+    Error,
+    Hash,
+    Iteration,
+    Keyword,
+    List,
+    Number,
+    Pair,
+    Predicate,
+    Procedure,
+    String,
+    Symbol,
+    Vector,
+    /// Specific `names` from the module `module`, given as
+    /// space-separated components, e.g. `"ice-9 format"`.
+    Custom {
+        module: String,
+        names: Vec<String>,
+    },
+}
+
+impl Bindings {
+    fn variable(&self) -> Option<&'static str> {
+        Some(match self {
+            Bindings::AllPure => "all-pure-bindings",
+            Bindings::AllPureAndImpure => "all-pure-and-impure-bindings",
+            Bindings::Alist => "alist-bindings",
+            Bindings::Bitvector => "bitvector-bindings",
+            Bindings::Char => "char-bindings",
+            Bindings::Core => "core-bindings",
+            Bindings::Error => "error-bindings",
+            Bindings::Hash => "hash-bindings",
+            Bindings::Iteration => "iteration-bindings",
+            Bindings::Keyword => "keyword-bindings",
+            Bindings::List => "list-bindings",
+            Bindings::Number => "number-bindings",
+            Bindings::Pair => "pair-bindings",
+            Bindings::Predicate => "predicate-bindings",
+            Bindings::Procedure => "procedure-bindings",
+            Bindings::String => "string-bindings",
+            Bindings::Symbol => "symbol-bindings",
+            Bindings::Vector => "vector-bindings",
+            Bindings::Custom { .. } => return None,
+        })
+    }
+
+    /* A binding set is a list of `((module name ...) binding ...)`. */
+    unsafe fn to_raw(&self) -> SCM {
+        match self {
+            Bindings::Custom { module, names } => {
+                let module = list_from_raw(module.split_whitespace().map(|c| symbol(c)));
+                let names = list_from_raw(names.iter().map(|name| symbol(name)));
+                guile_sys::scm_list_1(guile_sys::scm_cons(module, names))
+            }
+            named => public_ref("ice-9 sandbox", named.variable().unwrap()),
+        }
+    }
+}
+
+/// Options for `GuileVM::eval_sandboxed`.
+#[derive(Clone, Debug, Default)]
+pub struct SandboxOptions {
+    bindings: Vec<Bindings>,
+    limits: Limits,
+}
+
+impl SandboxOptions {
+    /// Options allowing `Bindings::AllPure` unless other bindings are
+    /// allowed explicitly.
+    pub fn new() -> SandboxOptions {
+        SandboxOptions::default()
+    }
+
+    /// Make `bindings` available to the sandboxed code.
+    pub fn allow(mut self, bindings: Bindings) -> SandboxOptions {
+        self.bindings.push(bindings);
+        self
+    }
+
+    /// Override the sandbox's default limits of 0.1 seconds and 10 MB of
+    /// allocation. Limits left unset keep their default.
+    pub fn limits(mut self, limits: Limits) -> SandboxOptions {
+        self.limits = limits;
+        self
+    }
+
+    unsafe fn bindings(&self) -> SCM {
+        if self.bindings.is_empty() {
+            return Bindings::AllPure.to_raw();
+        }
+        let sets = self
+            .bindings
+            .iter()
+            .fold(SCM_EOL, |sets, b| guile_sys::scm_cons(b.to_raw(), sets));
+        guile_sys::scm_append(sets)
+    }
+}
+
+impl GuileVM {
+    /// Evaluate `code` with `eval-in-sandbox`, in a fresh module that
+    /// only has the allowed bindings.
+    pub fn eval_sandboxed(
+        &self,
+        code: &str,
+        options: &SandboxOptions,
+    ) -> Result<Scm<'_>, GuileError> {
+        unsafe {
+            let result = protect(|| {
+                let exp = guile_sys::scm_cons(guile_sys::scm_sym_begin, read_all(code));
+                let mut args = guile_sys::scm_list_2(keyword("bindings"), options.bindings());
+                if let Some(time) = options.limits.time_limit() {
+                    let time = guile_sys::scm_from_double(time.as_secs_f64());
+                    args = guile_sys::scm_cons2(keyword("time-limit"), time, args);
+                }
+                if let Some(bytes) = options.limits.allocation_limit() {
+                    let bytes = guile_sys::scm_from_uint64(bytes);
+                    args = guile_sys::scm_cons2(keyword("allocation-limit"), bytes, args);
+                }
+                guile_sys::scm_apply_1(public_ref("ice-9 sandbox", "eval-in-sandbox"), exp, args)
+            })?;
+            Ok(Scm::from_raw(self, result))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::init;
+    use std::time::Duration;
+
+    #[test]
+    fn pure_code() {
+        init(|vm| {
+            let result = vm
+                .eval_sandboxed("(define x 20) (+ x 22)", &SandboxOptions::new())
+                .unwrap();
+            assert_eq!(unsafe { guile_sys::scm_to_int64(result.as_raw()) }, 42);
+        });
+    }
+
+    #[test]
+    fn forbidden_binding() {
+        init(|vm| {
+            let options = SandboxOptions::new()
+                .allow(Bindings::Core)
+                .allow(Bindings::Number);
+            assert!(vm.eval_sandboxed("(+ 1 2)", &options).is_ok());
+            assert!(vm
+                .eval_sandboxed("(open-input-file \"/etc/passwd\")", &options)
+                .is_err());
+            assert!(vm
+                .eval_sandboxed("(string-length \"abc\")", &options)
+                .is_err());
+        });
+    }
+
+    #[test]
+    fn limits() {
+        init(|vm| {
+            let options =
+                SandboxOptions::new().limits(Limits::new().time(Duration::from_millis(50)));
+            let result = vm.eval_sandboxed("(let loop () (loop))", &options);
+            assert!(matches!(result, Err(GuileError::TimeLimitExceeded)));
+        });
+    }
+
+    #[test]
+    fn custom_bindings() {
+        init(|vm| {
+            let options = SandboxOptions::new()
+                .allow(Bindings::Core)
+                .allow(Bindings::Custom {
+                    module: "guile".to_string(),
+                    names: vec!["string-length".to_string()],
+                });
+            assert!(vm
+                .eval_sandboxed("(string-length \"abc\")", &options)
+                .is_ok());
+        });
+    }
+}
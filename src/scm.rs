@@ -0,0 +1,468 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::ffi::CString;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use guile_sys::{
+    SCM, SCM_BOOL_F, SCM_BOOL_T, SCM_EOF_VAL, SCM_EOL, SCM_UNDEFINED, SCM_UNSPECIFIED,
+};
+use libc::{c_char, c_ulong};
+
+use crate::display;
+use crate::string;
+
+use crate::{FromScm, GuileVM, ToScm};
+
+/// A Scheme value, only usable while the `GuileVM` it came from is.
+///
+/// Like a raw `SCM`, this is only kept alive by the GC while it's on the
+/// stack; values stored in Rust heap memory (a `Vec`, a `Box`) are
+/// invisible to it.
+#[derive(Clone, Copy)]
+pub struct Scm<'vm> {
+    raw: SCM,
+    _vm: PhantomData<&'vm GuileVM>,
+}
+
+impl<'vm> Scm<'vm> {
+    /* Immediates aren't heap objects, so they're valid in any VM without
+     * needing to be rooted; taking the VM only ties them to `'vm`. */
+
+    /// `#t` or `#f`.
+    pub fn bool(_vm: &'vm GuileVM, value: bool) -> Scm<'vm> {
+        Scm::immediate(if value { SCM_BOOL_T } else { SCM_BOOL_F })
+    }
+
+    /// The empty list, `'()`.
+    pub fn eol(_vm: &'vm GuileVM) -> Scm<'vm> {
+        Scm::immediate(SCM_EOL)
+    }
+
+    /// The end-of-file object.
+    pub fn eof(_vm: &'vm GuileVM) -> Scm<'vm> {
+        Scm::immediate(SCM_EOF_VAL)
+    }
+
+    pub fn unspecified(_vm: &'vm GuileVM) -> Scm<'vm> {
+        Scm::immediate(SCM_UNSPECIFIED)
+    }
+
+    /// The marker for a missing optional argument; never a value
+    /// Scheme code can see.
+    pub fn undefined(_vm: &'vm GuileVM) -> Scm<'vm> {
+        Scm::immediate(SCM_UNDEFINED)
+    }
+
+    /// For immediates, or values the caller has already tied to `'vm`;
+    /// nothing checks that a VM exists.
+    pub(crate) const fn immediate(raw: SCM) -> Scm<'vm> {
+        Scm {
+            raw,
+            _vm: PhantomData,
+        }
+    }
+
+    /// Wrap a raw `SCM`.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be a valid Scheme object that stays reachable by the
+    /// GC for `'vm` (e.g. it lives on the stack of a guile-mode thread).
+    pub unsafe fn from_raw(_vm: &'vm GuileVM, raw: SCM) -> Scm<'vm> {
+        Scm {
+            raw,
+            _vm: PhantomData,
+        }
+    }
+
+    /// Convert a Rust value, e.g. `Scm::new(&vm, "text")`.
+    pub fn new<T: ToScm + ?Sized>(vm: &'vm GuileVM, value: &T) -> Scm<'vm> {
+        value.to_scm(vm)
+    }
+
+    /// Convert back to a Rust value; `None` on a type mismatch.
+    pub fn get<T: FromScm<'vm>>(&self) -> Option<T> {
+        T::from_scm(*self)
+    }
+
+    pub fn as_raw(&self) -> SCM {
+        self.raw
+    }
+
+    pub fn is_true(&self) -> bool {
+        guile_sys::scm_is_true(self.raw)
+    }
+
+    pub fn is_false(&self) -> bool {
+        guile_sys::scm_is_false(self.raw)
+    }
+
+    /// Whether this is the empty list.
+    pub fn is_null(&self) -> bool {
+        guile_sys::scm_is_null(self.raw)
+    }
+
+    pub fn is_bool(&self) -> bool {
+        unsafe { guile_sys::scm_is_bool(self.raw) != 0 }
+    }
+
+    pub fn is_pair(&self) -> bool {
+        unsafe { guile_sys::scm_is_pair(self.raw) != 0 }
+    }
+
+    /// Whether this is a proper list; `false` for circular ones.
+    pub fn is_list(&self) -> bool {
+        guile_sys::scm_is_true(unsafe { guile_sys::scm_list_p(self.raw) })
+    }
+
+    pub fn is_number(&self) -> bool {
+        unsafe { guile_sys::scm_is_number(self.raw) != 0 }
+    }
+
+    pub fn is_real(&self) -> bool {
+        unsafe { guile_sys::scm_is_real(self.raw) != 0 }
+    }
+
+    /// Whether this is an integer, exact or not (`3.0` counts).
+    pub fn is_integer(&self) -> bool {
+        unsafe { guile_sys::scm_is_integer(self.raw) != 0 }
+    }
+
+    pub fn is_exact_integer(&self) -> bool {
+        unsafe { guile_sys::scm_is_exact_integer(self.raw) != 0 }
+    }
+
+    pub fn is_char(&self) -> bool {
+        guile_sys::SCM_CHARP(self.raw)
+    }
+
+    pub fn is_string(&self) -> bool {
+        guile_sys::scm_is_true(unsafe { guile_sys::scm_string_p(self.raw) })
+    }
+
+    pub fn is_symbol(&self) -> bool {
+        guile_sys::scm_is_true(unsafe { guile_sys::scm_symbol_p(self.raw) })
+    }
+
+    pub fn is_keyword(&self) -> bool {
+        unsafe { guile_sys::scm_is_keyword(self.raw) != 0 }
+    }
+
+    pub fn is_procedure(&self) -> bool {
+        guile_sys::scm_is_true(unsafe { guile_sys::scm_procedure_p(self.raw) })
+    }
+
+    pub fn is_vector(&self) -> bool {
+        unsafe { guile_sys::scm_is_vector(self.raw) != 0 }
+    }
+
+    pub fn is_bytevector(&self) -> bool {
+        unsafe { guile_sys::scm_is_bytevector(self.raw) != 0 }
+    }
+
+    pub fn is_hash_table(&self) -> bool {
+        guile_sys::scm_is_true(unsafe { guile_sys::scm_hash_table_p(self.raw) })
+    }
+
+    pub fn is_eof(&self) -> bool {
+        guile_sys::scm_is_true(unsafe { guile_sys::scm_eof_object_p(self.raw) })
+    }
+
+    /// A rough name for the kind of value this is, like `"string"` or
+    /// `"procedure"`, for error messages; `"object"` for anything not
+    /// covered by the predicates above.
+    pub fn type_name(&self) -> &'static str {
+        if self.is_bool() {
+            "boolean"
+        } else if self.is_exact_integer() {
+            "exact integer"
+        } else if self.is_real() {
+            "real"
+        } else if self.is_number() {
+            "number"
+        } else if self.is_char() {
+            "character"
+        } else if self.is_string() {
+            "string"
+        } else if self.is_symbol() {
+            "symbol"
+        } else if self.is_keyword() {
+            "keyword"
+        } else if self.is_null() {
+            "empty list"
+        } else if self.is_pair() {
+            "pair"
+        } else if self.is_vector() {
+            "vector"
+        } else if self.is_bytevector() {
+            "bytevector"
+        } else if self.is_hash_table() {
+            "hash table"
+        } else if self.is_procedure() {
+            "procedure"
+        } else if self.is_eof() {
+            "eof object"
+        } else {
+            "object"
+        }
+    }
+
+    /// `class-of`: the GOOPS class, which also covers records and
+    /// user-defined classes.
+    pub fn class_of(&self) -> Scm<'vm> {
+        self.with_raw(unsafe { guile_sys::scm_class_of(self.raw) })
+    }
+
+    /// The name of `class_of`, e.g. `<string>` or `<my-record>`.
+    pub fn class_name(&self) -> String {
+        unsafe {
+            let name = guile_sys::scm_class_name(guile_sys::scm_class_of(self.raw));
+            string::to_string(guile_sys::scm_symbol_to_string(name))
+        }
+    }
+
+    /// `eq?`: whether both are the same object.
+    pub fn is_eq(&self, other: Scm<'_>) -> bool {
+        guile_sys::scm_is_eq(self.raw, other.raw)
+    }
+
+    /// `eqv?`: `eq?`, but also equal numbers of the same exactness and
+    /// equal characters.
+    pub fn is_eqv(&self, other: Scm<'_>) -> bool {
+        guile_sys::scm_is_true(unsafe { guile_sys::scm_eqv_p(self.raw, other.raw) })
+    }
+
+    /// `equal?`: structural equality of pairs, vectors, strings and the
+    /// like.
+    pub fn is_equal(&self, other: Scm<'_>) -> bool {
+        guile_sys::scm_is_true(unsafe { guile_sys::scm_equal_p(self.raw, other.raw) })
+    }
+
+    /// Wrap another raw `SCM` with the same lifetime as `self`, e.g. the
+    /// result of a libguile call on it.
+    pub(crate) fn with_raw(&self, raw: SCM) -> Scm<'vm> {
+        Scm {
+            raw,
+            _vm: PhantomData,
+        }
+    }
+}
+
+/// Compares with `equal?`.
+impl<'a, 'b> PartialEq<Scm<'b>> for Scm<'a> {
+    fn eq(&self, other: &Scm<'b>) -> bool {
+        self.is_equal(*other)
+    }
+}
+
+/// As `display` prints the value.
+impl<'a> fmt::Display for Scm<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&unsafe { display::print(self.raw, guile_sys::scm_display) })
+    }
+}
+
+/// As `write` prints the value.
+impl<'a> fmt::Debug for Scm<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&unsafe { display::print(self.raw, guile_sys::scm_write) })
+    }
+}
+
+/// `equal?` is reflexive, even for `+nan.0`.
+impl<'a> Eq for Scm<'a> {}
+
+/// Consistent with `equal?`, so `Scm`s can key a `HashMap`. Like any
+/// `Scm` in Rust heap memory, the keys aren't seen by the GC: keep them
+/// reachable from Scheme, or protected, for as long as the map lives.
+impl<'a> Hash for Scm<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        unsafe { guile_sys::scm_ihash(self.raw, c_ulong::MAX) }.hash(state);
+    }
+}
+
+/// Build a proper list from raw elements. Each element is consed on as
+/// soon as it's produced, so nothing is left in Rust memory the GC can't
+/// see.
+pub(crate) unsafe fn list_from_raw<I>(items: I) -> SCM
+where
+    I: IntoIterator<Item = SCM>,
+{
+    let reversed = items
+        .into_iter()
+        .fold(SCM_EOL, |tail, item| guile_sys::scm_cons(item, tail));
+    guile_sys::scm_reverse_x(reversed, SCM_EOL)
+}
+
+/// `scm_c_public_ref`, e.g. `public_ref("ice-9 sandbox", "eval-in-sandbox")`.
+/// Throws if the module or binding doesn't exist.
+pub(crate) unsafe fn public_ref(module: &str, name: &str) -> SCM {
+    let module = CString::new(module).unwrap();
+    let name = CString::new(name).unwrap();
+    guile_sys::scm_c_public_ref(module.as_ptr(), name.as_ptr())
+}
+
+pub(crate) unsafe fn symbol(name: &str) -> SCM {
+    guile_sys::scm_from_utf8_symboln(name.as_ptr() as *const c_char, name.len())
+}
+
+pub(crate) unsafe fn keyword(name: &str) -> SCM {
+    guile_sys::scm_symbol_to_keyword(symbol(name))
+}
+
+/// Read every datum in `code`, returning them as a list. Throws on
+/// syntax errors.
+pub(crate) unsafe fn read_all(code: &str) -> SCM {
+    let port = guile_sys::scm_open_input_string(string::from_str(code));
+    let mut forms = SCM_EOL;
+    loop {
+        let form = guile_sys::scm_read(port);
+        if guile_sys::scm_is_true(guile_sys::scm_eof_object_p(form)) {
+            return guile_sys::scm_reverse_x(forms, SCM_EOL);
+        }
+        forms = guile_sys::scm_cons(form, forms);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language};
+
+    #[test]
+    fn new_and_get() {
+        init(|vm| {
+            let n = Scm::new(&vm, &42i32);
+            assert_eq!(n.get::<i32>(), Some(42));
+            assert_eq!(n.get::<String>(), None);
+            assert!(n.is_eq(n));
+            assert!(n.is_true());
+
+            let s = Scm::new(&vm, "text");
+            assert_eq!(s.get::<String>().as_deref(), Some("text"));
+            assert!(!s.is_null());
+            assert!(Scm::new(&vm, &false).is_false());
+            assert!(Scm::eol(&vm).is_null());
+        });
+    }
+
+    #[test]
+    fn constants() {
+        init(|vm| {
+            assert!(Scm::bool(&vm, true).is_true());
+            assert!(Scm::bool(&vm, false).is_false());
+            assert_eq!(Scm::bool(&vm, true).get::<bool>(), Some(true));
+            assert!(Scm::new(&vm, &Vec::<i32>::new()).is_eq(Scm::eol(&vm)));
+            assert!(Scm::new(&vm, &()).is_eq(Scm::unspecified(&vm)));
+            assert!(unsafe {
+                guile_sys::scm_is_true(guile_sys::scm_eof_object_p(Scm::eof(&vm).as_raw()))
+            });
+            assert!(!Scm::undefined(&vm).is_eq(Scm::unspecified(&vm)));
+        });
+    }
+
+    #[test]
+    fn equality() {
+        init(|vm| {
+            let a = Scm::new(&vm, &vec![1i32, 2]);
+            let b = Scm::new(&vm, &vec![1i32, 2]);
+            assert!(!a.is_eq(b));
+            assert!(!a.is_eqv(b));
+            assert!(a.is_equal(b));
+            assert!(a == b);
+            assert!(a != Scm::new(&vm, &vec![2i32, 1]));
+
+            let big = Scm::new(&vm, &u128::MAX);
+            assert!(big.is_eqv(Scm::new(&vm, &u128::MAX)));
+            assert!(Scm::new(&vm, &2i32) != Scm::new(&vm, &2.0f64));
+            assert!(Scm::new(&vm, "x") == Scm::new(&vm, "x"));
+        });
+    }
+
+    #[test]
+    fn hash_map_keys() {
+        use std::collections::HashMap;
+
+        init(|vm| {
+            let key = Scm::new(&vm, &vec!["a", "b"]);
+            let mut map = HashMap::new();
+            map.insert(key, 1);
+            map.insert(Scm::new(&vm, &f64::NAN), 2);
+
+            assert_eq!(map.get(&Scm::new(&vm, &vec!["a", "b"])), Some(&1));
+            assert_eq!(map.get(&Scm::new(&vm, &f64::NAN)), Some(&2));
+            assert_eq!(map.get(&Scm::new(&vm, &vec!["b", "a"])), None);
+            assert!(key.is_true());
+        });
+    }
+
+    #[test]
+    fn formatting() {
+        init(|vm| {
+            let value = Scm::new(&vm, &vec!["a b", "c"]);
+            assert_eq!(format!("{}", value), "(a b c)");
+            assert_eq!(format!("{:?}", value), "(\"a b\" \"c\")");
+            assert_eq!(format!("{}", Scm::eol(&vm)), "()");
+        });
+    }
+
+    #[test]
+    fn predicates() {
+        init(|vm| {
+            let list = Scm::new(&vm, &vec![1i32, 2]);
+            assert!(list.is_pair() && list.is_list() && !list.is_vector());
+            assert!(Scm::eol(&vm).is_list() && !Scm::eol(&vm).is_pair());
+            assert!(Scm::new(&vm, &2.0f64).is_integer());
+            assert!(!Scm::new(&vm, &2.0f64).is_exact_integer());
+            assert!(Scm::new(&vm, &2.5f64).is_real() && Scm::new(&vm, &2.5f64).is_number());
+            assert!(Scm::new(&vm, "s").is_string() && !Scm::new(&vm, "s").is_symbol());
+            assert!(Scm::new(&vm, &'c').is_char());
+            assert!(Scm::bool(&vm, false).is_bool() && Scm::eof(&vm).is_eof());
+            assert!(!Scm::new(&vm, &0i32).is_bool());
+            assert!(unsafe { Scm::from_raw(&vm, symbol("s")) }.is_symbol());
+            assert!(unsafe { Scm::from_raw(&vm, keyword("k")) }.is_keyword());
+            let car = unsafe { Scm::from_raw(&vm, public_ref("guile", "car")) };
+            assert!(car.is_procedure() && !list.is_procedure());
+        });
+    }
+
+    #[test]
+    fn reflection() {
+        init(|vm| {
+            assert_eq!(Scm::new(&vm, "s").type_name(), "string");
+            assert_eq!(Scm::new(&vm, &1i32).type_name(), "exact integer");
+            assert_eq!(Scm::eol(&vm).type_name(), "empty list");
+            assert_eq!(Scm::new(&vm, "s").class_name(), "<string>");
+            assert_eq!(Scm::new(&vm, &1i32).class_name(), "<integer>");
+
+            let record = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(use-modules (srfi srfi-9))
+                     (define-record-type <point> (make-point) point?)
+                     (make-point)",
+                )
+                .unwrap();
+            assert_eq!(record.type_name(), "object");
+            assert_eq!(record.class_name(), "<point>");
+            assert!(record.class_of().is_eq(record.class_of()));
+        });
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use guile_sys::{SCM_EOF_VAL, SCM_UNDEFINED, SCM_UNSPECIFIED};
+
+use crate::convert::wrong_type;
+use crate::scm::{keyword, list_from_raw, symbol};
+use crate::string;
+use crate::{ConversionError, FromScm, GuileVM, Scm, ScmKeyword, ScmString, ScmSymbol, ToScm};
+
+/// A deep copy of Scheme data that doesn't depend on a `GuileVM`, so it
+/// can be sent across threads, stored, and turned back into Scheme data
+/// later with `to_scm`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OwnedSexp {
+    Bool(bool),
+    /// An exact integer that fits in an `i128`.
+    Integer(i128),
+    /// An inexact real.
+    Real(f64),
+    /// Any other number (bignums, exact ratios, complex numbers), as
+    /// `number->string` prints it.
+    Number(String),
+    Char(char),
+    String(String),
+    Symbol(String),
+    /// A keyword, without the `#:` prefix.
+    Keyword(String),
+    /// A proper list; the empty list is `List(vec![])`.
+    List(Vec<OwnedSexp>),
+    /// An improper list: the elements, then the final cdr.
+    DottedList(Vec<OwnedSexp>, Box<OwnedSexp>),
+    Vector(Vec<OwnedSexp>),
+    Bytevector(Vec<u8>),
+    Unspecified,
+    Eof,
+}
+
+impl<'vm> Scm<'vm> {
+    /// Copy this value out of the VM. Fails with `WrongType` on values
+    /// that aren't plain data, like procedures or ports. The value must
+    /// not be circular.
+    pub fn to_owned_sexp(&self) -> Result<OwnedSexp, ConversionError> {
+        let raw = self.as_raw();
+        unsafe {
+            if let Some(b) = bool::from_scm(*self) {
+                return Ok(OwnedSexp::Bool(b));
+            }
+            if guile_sys::scm_is_number(raw) != 0 {
+                return Ok(if let Some(n) = i128::from_scm(*self) {
+                    OwnedSexp::Integer(n)
+                } else if guile_sys::scm_is_real(raw) != 0 && guile_sys::scm_is_inexact(raw) != 0 {
+                    OwnedSexp::Real(guile_sys::scm_to_double(raw))
+                } else {
+                    OwnedSexp::Number(string::to_string(guile_sys::scm_number_to_string(
+                        raw,
+                        SCM_UNDEFINED,
+                    )))
+                });
+            }
+            if let Some(c) = char::from_scm(*self) {
+                return Ok(OwnedSexp::Char(c));
+            }
+            if let Some(s) = ScmString::from_scm(*self) {
+                return Ok(OwnedSexp::String(s.to_string()));
+            }
+            if let Some(s) = ScmSymbol::from_scm(*self) {
+                return Ok(OwnedSexp::Symbol(s.name()));
+            }
+            if let Some(k) = ScmKeyword::from_scm(*self) {
+                return Ok(OwnedSexp::Keyword(k.name()));
+            }
+            if guile_sys::scm_is_null(raw) || guile_sys::scm_is_pair(raw) != 0 {
+                let mut items = Vec::new();
+                let mut rest = raw;
+                while guile_sys::scm_is_pair(rest) != 0 {
+                    items.push(self.with_raw(guile_sys::scm_car(rest)).to_owned_sexp()?);
+                    rest = guile_sys::scm_cdr(rest);
+                }
+                return Ok(if guile_sys::scm_is_null(rest) {
+                    OwnedSexp::List(items)
+                } else {
+                    OwnedSexp::DottedList(items, Box::new(self.with_raw(rest).to_owned_sexp()?))
+                });
+            }
+            if guile_sys::scm_is_vector(raw) != 0 {
+                let items = (0..guile_sys::scm_c_vector_length(raw))
+                    .map(|idx| {
+                        self.with_raw(guile_sys::scm_c_vector_ref(raw, idx))
+                            .to_owned_sexp()
+                    })
+                    .collect::<Result<_, _>>()?;
+                return Ok(OwnedSexp::Vector(items));
+            }
+            if guile_sys::scm_is_bytevector(raw) != 0 {
+                let bytes = (0..guile_sys::scm_c_bytevector_length(raw))
+                    .map(|idx| guile_sys::scm_c_bytevector_ref(raw, idx))
+                    .collect();
+                return Ok(OwnedSexp::Bytevector(bytes));
+            }
+            if guile_sys::scm_is_eq(raw, SCM_UNSPECIFIED) {
+                return Ok(OwnedSexp::Unspecified);
+            }
+            if guile_sys::scm_is_eq(raw, SCM_EOF_VAL) {
+                return Ok(OwnedSexp::Eof);
+            }
+        }
+        Err(wrong_type("data", *self))
+    }
+}
+
+impl ToScm for OwnedSexp {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe {
+            let raw = match self {
+                OwnedSexp::Bool(b) => guile_sys::scm_from_bool(*b),
+                OwnedSexp::Integer(n) => n.to_scm(vm).as_raw(),
+                OwnedSexp::Real(x) => guile_sys::scm_from_double(*x),
+                OwnedSexp::Number(s) => {
+                    guile_sys::scm_string_to_number(string::from_str(s), SCM_UNDEFINED)
+                }
+                OwnedSexp::Char(c) => guile_sys::SCM_MAKE_CHAR(*c as u32),
+                OwnedSexp::String(s) => string::from_str(s),
+                OwnedSexp::Symbol(s) => symbol(s),
+                OwnedSexp::Keyword(s) => keyword(s),
+                OwnedSexp::List(items) => {
+                    list_from_raw(items.iter().map(|i| i.to_scm(vm).as_raw()))
+                }
+                OwnedSexp::DottedList(items, tail) => items
+                    .iter()
+                    .rev()
+                    .fold(tail.to_scm(vm).as_raw(), |rest, item| {
+                        guile_sys::scm_cons(item.to_scm(vm).as_raw(), rest)
+                    }),
+                OwnedSexp::Vector(items) => {
+                    let vector = guile_sys::scm_c_make_vector(items.len(), SCM_UNSPECIFIED);
+                    for (idx, item) in items.iter().enumerate() {
+                        guile_sys::scm_c_vector_set_x(vector, idx, item.to_scm(vm).as_raw());
+                    }
+                    vector
+                }
+                OwnedSexp::Bytevector(bytes) => {
+                    let bv = guile_sys::scm_c_make_bytevector(bytes.len());
+                    for (idx, &byte) in bytes.iter().enumerate() {
+                        guile_sys::scm_c_bytevector_set_x(bv, idx, byte);
+                    }
+                    bv
+                }
+                OwnedSexp::Unspecified => SCM_UNSPECIFIED,
+                OwnedSexp::Eof => SCM_EOF_VAL,
+            };
+            Scm::from_raw(vm, raw)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::{init, Language};
+
+    #[test]
+    fn across_threads() {
+        let code = "`(1 2.5 1/3 ,(expt 2 200) #\\a \"s\" sym #:kw (a . b) #(x) #vu8(1 2) #t)";
+        let sexp = RefCell::new(None);
+        init(|vm| {
+            let value = vm.eval_language(Language::Scheme, code).unwrap();
+            *sexp.borrow_mut() = Some(value.to_owned_sexp().unwrap());
+
+            let procedure = vm.eval_language(Language::Scheme, "car").unwrap();
+            assert!(procedure.to_owned_sexp().is_err());
+        });
+        let sexp = sexp.into_inner().unwrap();
+        let sexp = std::thread::spawn(move || sexp).join().unwrap();
+
+        let OwnedSexp::List(items) = &sexp else {
+            panic!("not a list: {:?}", sexp);
+        };
+        assert_eq!(items[0], OwnedSexp::Integer(1));
+        assert_eq!(items[1], OwnedSexp::Real(2.5));
+        assert_eq!(items[2], OwnedSexp::Number("1/3".into()));
+        assert_eq!(items[7], OwnedSexp::Keyword("kw".into()));
+        assert_eq!(
+            items[8],
+            OwnedSexp::DottedList(
+                vec![OwnedSexp::Symbol("a".into())],
+                Box::new(OwnedSexp::Symbol("b".into()))
+            )
+        );
+        assert_eq!(items[10], OwnedSexp::Bytevector(vec![1, 2]));
+
+        init(|vm| {
+            let expected = vm.eval_language(Language::Scheme, code).unwrap();
+            assert!(sexp.to_scm(&vm) == expected);
+        });
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::cmp::Reverse;
+use std::time::Duration;
+
+use guile_sys::SCM;
+
+use crate::error::{protect, GuileError};
+use crate::scm::{public_ref, read_all};
+use crate::string;
+use crate::GuileVM;
+
+/// Profile data for one procedure, from `statprof-fold-call-data`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProcedureSamples {
+    pub name: String,
+    /// Samples taken while this procedure was running.
+    pub self_samples: u64,
+    /// Samples taken while this procedure was anywhere on the stack.
+    pub cumulative_samples: u64,
+    /// Number of calls, if call counting was enabled.
+    pub calls: Option<u64>,
+    pub percent_time: f64,
+    pub self_seconds: f64,
+    pub cumulative_seconds: f64,
+}
+
+/// The results of a statprof run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileReport {
+    pub sample_count: u64,
+    pub accumulated_time: Duration,
+    pub procedures: Vec<ProcedureSamples>,
+}
+
+const CALL_DATA: &str = "
+(lambda ()
+  (define (call-data->vector data)
+    (let ((stats ((@ (statprof) statprof-call-data->stats) data)))
+      (vector
+       (call-with-output-string
+        (lambda (port)
+          (display ((@ (statprof) statprof-stats-proc-name) stats) port)))
+       ((@ (statprof) statprof-call-data-self-samples) data)
+       ((@ (statprof) statprof-call-data-cum-samples) data)
+       ((@ (statprof) statprof-stats-calls) stats)
+       (exact->inexact ((@ (statprof) statprof-stats-%-time-in-proc) stats))
+       (exact->inexact ((@ (statprof) statprof-stats-self-secs-in-proc) stats))
+       (exact->inexact ((@ (statprof) statprof-stats-cum-secs-in-proc) stats)))))
+  ((@ (statprof) statprof-fold-call-data)
+   (lambda (data prior) (cons (call-data->vector data) prior))
+   '()))
+";
+
+unsafe fn statprof(name: &str) -> SCM {
+    public_ref("statprof", name)
+}
+
+unsafe fn procedure_samples(v: SCM) -> ProcedureSamples {
+    let field = |idx| guile_sys::scm_c_vector_ref(v, idx);
+    let calls = field(3);
+    ProcedureSamples {
+        name: string::to_string(field(0)),
+        self_samples: guile_sys::scm_to_uint64(field(1)),
+        cumulative_samples: guile_sys::scm_to_uint64(field(2)),
+        calls: if guile_sys::scm_is_true(calls) {
+            Some(guile_sys::scm_to_uint64(calls))
+        } else {
+            None
+        },
+        percent_time: guile_sys::scm_to_double(field(4)),
+        self_seconds: guile_sys::scm_to_double(field(5)),
+        cumulative_seconds: guile_sys::scm_to_double(field(6)),
+    }
+}
+
+impl GuileVM {
+    /// Reset statprof and start sampling every `interval`. With
+    /// `count_calls`, calls are counted too, at some cost in speed.
+    pub fn statprof_start(&self, interval: Duration, count_calls: bool) -> Result<(), GuileError> {
+        unsafe {
+            protect(|| {
+                guile_sys::scm_call_3(
+                    statprof("statprof-reset"),
+                    guile_sys::scm_from_uint64(interval.as_secs()),
+                    guile_sys::scm_from_uint32(interval.subsec_micros()),
+                    guile_sys::scm_from_bool(count_calls),
+                );
+                guile_sys::scm_call_0(statprof("statprof-start"))
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn statprof_stop(&self) -> Result<(), GuileError> {
+        unsafe {
+            protect(|| guile_sys::scm_call_0(statprof("statprof-stop")))?;
+        }
+        Ok(())
+    }
+
+    /// The data collected by the last statprof run.
+    pub fn statprof_report(&self) -> Result<ProfileReport, GuileError> {
+        unsafe {
+            let (sample_count, time, mut data) = protect(|| {
+                let call_data =
+                    guile_sys::scm_primitive_eval(guile_sys::scm_car(read_all(CALL_DATA)));
+                (
+                    guile_sys::scm_call_0(statprof("statprof-sample-count")),
+                    guile_sys::scm_call_0(statprof("statprof-accumulated-time")),
+                    guile_sys::scm_call_0(call_data),
+                )
+            })?;
+            let mut procedures = Vec::new();
+            while !guile_sys::scm_is_null(data) {
+                procedures.push(procedure_samples(guile_sys::scm_car(data)));
+                data = guile_sys::scm_cdr(data);
+            }
+            procedures.sort_by_key(|p| Reverse(p.self_samples));
+            Ok(ProfileReport {
+                sample_count: guile_sys::scm_to_uint64(sample_count),
+                accumulated_time: Duration::from_secs_f64(guile_sys::scm_to_double(
+                    guile_sys::scm_exact_to_inexact(time),
+                )),
+                procedures,
+            })
+        }
+    }
+
+    /// The report `statprof-display` would print.
+    pub fn statprof_display(&self) -> Result<String, GuileError> {
+        unsafe {
+            let report = protect(|| {
+                let port = guile_sys::scm_open_output_string();
+                guile_sys::scm_call_1(statprof("statprof-display"), port);
+                guile_sys::scm_get_output_string(port)
+            })?;
+            Ok(string::to_string(report))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language};
+
+    #[test]
+    fn profile() {
+        init(|vm| {
+            vm.eval_language(
+                Language::Scheme,
+                "(define (fib n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))",
+            )
+            .unwrap();
+            vm.statprof_start(Duration::from_millis(1), true).unwrap();
+            vm.eval_language(Language::Scheme, "(fib 27)").unwrap();
+            vm.statprof_stop().unwrap();
+
+            let report = vm.statprof_report().unwrap();
+            assert!(report.sample_count > 0);
+            assert!(report.procedures.iter().any(|p| p.name.contains("fib")));
+            assert!(vm.statprof_display().unwrap().contains("fib"));
+        });
+    }
+}
@@ -0,0 +1,525 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::slice;
+
+use guile_sys::{scm_t_string_failed_conversion_handler, SCM, SCM_UNDEFINED};
+use libc::{c_char, c_void};
+
+use crate::error::{protect, GuileError};
+use crate::{GuileVM, Scm};
+
+pub(crate) unsafe fn from_str(s: &str) -> SCM {
+    guile_sys::scm_from_utf8_stringn(s.as_ptr() as *const c_char, s.len())
+}
+
+/// `raw` must be a Scheme string.
+pub(crate) unsafe fn to_string(raw: SCM) -> String {
+    let mut len = 0;
+    let buf = guile_sys::scm_to_utf8_stringn(raw, &mut len);
+    let s = String::from_utf8_lossy(slice::from_raw_parts(buf as *const u8, len)).into_owned();
+    libc::free(buf as *mut c_void);
+    s
+}
+
+/// Byte encodings for converting strings to and from Scheme.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding<'a> {
+    Utf8,
+    Latin1,
+    /// The encoding of the current locale (`LC_CTYPE`).
+    Locale,
+    /// Any other encoding iconv knows by this name.
+    Named(&'a str),
+}
+
+impl<'a> Encoding<'a> {
+    fn name(&self) -> CString {
+        match *self {
+            Encoding::Utf8 => CString::new("UTF-8").unwrap(),
+            Encoding::Latin1 => CString::new("ISO-8859-1").unwrap(),
+            Encoding::Locale => unsafe { CStr::from_ptr(libc::nl_langinfo(libc::CODESET)) }.into(),
+            Encoding::Named(name) => CString::new(name).expect("encoding name contains a NUL byte"),
+        }
+    }
+}
+
+/// What to do with bytes or characters that the target encoding can't
+/// represent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvalidSequence {
+    /// Raise a `decoding-error`/`encoding-error`.
+    Error,
+    /// Substitute `?`.
+    Replace,
+    /// Substitute a `\xNN`/`\uNNNN` escape when encoding, `?` when decoding.
+    Escape,
+}
+
+impl InvalidSequence {
+    fn handler(self) -> scm_t_string_failed_conversion_handler {
+        match self {
+            InvalidSequence::Error => {
+                guile_sys::scm_t_string_failed_conversion_handler_SCM_FAILED_CONVERSION_ERROR
+            }
+            InvalidSequence::Replace => {
+                guile_sys::scm_t_string_failed_conversion_handler_SCM_FAILED_CONVERSION_QUESTION_MARK
+            }
+            InvalidSequence::Escape => {
+                guile_sys::scm_t_string_failed_conversion_handler_SCM_FAILED_CONVERSION_ESCAPE_SEQUENCE
+            }
+        }
+    }
+}
+
+fn index_from_scm(found: SCM) -> Option<usize> {
+    if guile_sys::scm_is_true(found) {
+        Some(unsafe { guile_sys::scm_to_uint64(found) } as usize)
+    } else {
+        None
+    }
+}
+
+/// A Scheme string.
+///
+/// Indices are in characters, as in Scheme, not in UTF-8 bytes.
+#[derive(Clone, Copy)]
+pub struct ScmString<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> ScmString<'vm> {
+    pub fn new(vm: &'vm GuileVM, s: &str) -> ScmString<'vm> {
+        ScmString {
+            scm: unsafe { Scm::from_raw(vm, from_str(s)) },
+        }
+    }
+
+    /// Decode `bytes` from `encoding`, e.g. data from a legacy file that
+    /// isn't valid UTF-8.
+    pub fn decode(
+        vm: &'vm GuileVM,
+        bytes: &[u8],
+        encoding: Encoding,
+        invalid: InvalidSequence,
+    ) -> Result<ScmString<'vm>, GuileError> {
+        let name = encoding.name();
+        let raw = unsafe {
+            protect(|| {
+                guile_sys::scm_from_stringn(
+                    bytes.as_ptr() as *const c_char,
+                    bytes.len(),
+                    name.as_ptr(),
+                    invalid.handler(),
+                )
+            })?
+        };
+        Ok(ScmString {
+            scm: unsafe { Scm::from_raw(vm, raw) },
+        })
+    }
+
+    /// `scm_from_latin1_stringn`. Every byte is a valid Latin-1
+    /// character, so this can't fail.
+    pub fn from_latin1(vm: &'vm GuileVM, bytes: &[u8]) -> ScmString<'vm> {
+        let raw = unsafe {
+            guile_sys::scm_from_latin1_stringn(bytes.as_ptr() as *const c_char, bytes.len())
+        };
+        ScmString {
+            scm: unsafe { Scm::from_raw(vm, raw) },
+        }
+    }
+
+    pub fn from_locale(
+        vm: &'vm GuileVM,
+        bytes: &[u8],
+        invalid: InvalidSequence,
+    ) -> Result<ScmString<'vm>, GuileError> {
+        ScmString::decode(vm, bytes, Encoding::Locale, invalid)
+    }
+
+    /// Encode the string as `encoding`.
+    pub fn encode(
+        &self,
+        encoding: Encoding,
+        invalid: InvalidSequence,
+    ) -> Result<Vec<u8>, GuileError> {
+        let name = encoding.name();
+        let raw = self.scm.as_raw();
+        let mut len = 0;
+        unsafe {
+            let buf = protect(|| {
+                guile_sys::scm_to_stringn(raw, &mut len, name.as_ptr(), invalid.handler())
+            })?;
+            let bytes = slice::from_raw_parts(buf as *const u8, len).to_vec();
+            libc::free(buf as *mut c_void);
+            Ok(bytes)
+        }
+    }
+
+    pub fn to_latin1(&self, invalid: InvalidSequence) -> Result<Vec<u8>, GuileError> {
+        self.encode(Encoding::Latin1, invalid)
+    }
+
+    pub fn to_locale(&self, invalid: InvalidSequence) -> Result<Vec<u8>, GuileError> {
+        self.encode(Encoding::Locale, invalid)
+    }
+
+    /// Returns `None` if `scm` is not a string.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmString<'vm>> {
+        if guile_sys::scm_is_true(unsafe { guile_sys::scm_string_p(scm.as_raw()) }) {
+            Some(ScmString { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    fn wrap(&self, raw: SCM) -> ScmString<'vm> {
+        ScmString {
+            scm: self.scm.with_raw(raw),
+        }
+    }
+
+    /// Length in characters.
+    pub fn len(&self) -> usize {
+        unsafe { guile_sys::scm_c_string_length(self.scm.as_raw()) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `None` if `idx` is out of bounds.
+    pub fn get(&self, idx: usize) -> Option<char> {
+        if idx < self.len() {
+            let c = unsafe { guile_sys::scm_c_string_ref(self.scm.as_raw(), idx) };
+            char::from_u32(guile_sys::SCM_CHAR(c))
+        } else {
+            None
+        }
+    }
+
+    /// `string-set!`. Fails if the string is read-only, e.g. a literal.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn set(&self, idx: usize, c: char) -> Result<(), GuileError> {
+        assert!(
+            idx < self.len(),
+            "string index {} out of bounds (length {})",
+            idx,
+            self.len()
+        );
+        let raw = self.scm.as_raw();
+        unsafe {
+            protect(|| {
+                guile_sys::scm_c_string_set_x(raw, idx, guile_sys::SCM_MAKE_CHAR(c as u32))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// The characters in `start..end`, as a new string.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start > end` or `end` is out of bounds.
+    pub fn substring(&self, start: usize, end: usize) -> ScmString<'vm> {
+        assert!(
+            start <= end && end <= self.len(),
+            "substring {}..{} out of bounds (length {})",
+            start,
+            end,
+            self.len()
+        );
+        self.wrap(unsafe { guile_sys::scm_c_substring(self.scm.as_raw(), start, end) })
+    }
+
+    /// The characters in `start..end`, sharing storage with this string
+    /// until either is modified, so slicing doesn't copy.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start > end` or `end` is out of bounds.
+    pub fn shared_substring(&self, start: usize, end: usize) -> ScmString<'vm> {
+        assert!(
+            start <= end && end <= self.len(),
+            "substring {}..{} out of bounds (length {})",
+            start,
+            end,
+            self.len()
+        );
+        self.wrap(unsafe { guile_sys::scm_c_substring_shared(self.scm.as_raw(), start, end) })
+    }
+
+    /// The characters, read in place from Guile's storage rather than
+    /// converted to UTF-8.
+    ///
+    /// # Safety
+    ///
+    /// The string, and any string sharing its storage, must not be
+    /// modified while the result is alive, by `set` or by Scheme code:
+    /// Guile may then copy or widen the buffer, leaving the slices
+    /// dangling.
+    pub unsafe fn chars(&self) -> StringChars<'_> {
+        let raw = self.scm.as_raw();
+        let len = guile_sys::scm_i_string_length(raw);
+        if len == 0 {
+            StringChars::Narrow(&[])
+        } else if guile_sys::scm_i_is_narrow_string(raw) != 0 {
+            let chars = guile_sys::scm_i_string_chars(raw) as *const u8;
+            StringChars::Narrow(slice::from_raw_parts(chars, len))
+        } else {
+            let chars = guile_sys::scm_i_string_wide_chars(raw) as *const u32;
+            StringChars::Wide(slice::from_raw_parts(chars, len))
+        }
+    }
+
+    /// Index of the first occurrence of `c`.
+    pub fn index(&self, c: char) -> Option<usize> {
+        let found = unsafe {
+            guile_sys::scm_string_index(
+                self.scm.as_raw(),
+                guile_sys::SCM_MAKE_CHAR(c as u32),
+                SCM_UNDEFINED,
+                SCM_UNDEFINED,
+            )
+        };
+        index_from_scm(found)
+    }
+
+    /// Index of the first occurrence of `needle`.
+    pub fn find(&self, needle: &ScmString) -> Option<usize> {
+        let found = unsafe {
+            guile_sys::scm_string_contains(
+                self.scm.as_raw(),
+                needle.scm.as_raw(),
+                SCM_UNDEFINED,
+                SCM_UNDEFINED,
+                SCM_UNDEFINED,
+                SCM_UNDEFINED,
+            )
+        };
+        index_from_scm(found)
+    }
+
+    /// A new string with `other` appended.
+    pub fn append(&self, other: &ScmString) -> ScmString<'vm> {
+        self.wrap(unsafe {
+            guile_sys::scm_string_append(guile_sys::scm_list_2(
+                self.scm.as_raw(),
+                other.scm.as_raw(),
+            ))
+        })
+    }
+
+    pub fn upcase(&self) -> ScmString<'vm> {
+        self.wrap(unsafe { guile_sys::scm_string_upcase(self.scm.as_raw()) })
+    }
+
+    pub fn downcase(&self) -> ScmString<'vm> {
+        self.wrap(unsafe { guile_sys::scm_string_downcase(self.scm.as_raw()) })
+    }
+}
+
+/// A string's characters as Guile stores them: one Latin-1 byte each if
+/// they all fit, otherwise one code point each.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StringChars<'a> {
+    Narrow(&'a [u8]),
+    Wide(&'a [u32]),
+}
+
+impl<'a> StringChars<'a> {
+    pub fn len(&self) -> usize {
+        match self {
+            StringChars::Narrow(chars) => chars.len(),
+            StringChars::Wide(chars) => chars.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `None` if `idx` is out of bounds.
+    pub fn get(&self, idx: usize) -> Option<char> {
+        match self {
+            StringChars::Narrow(chars) => chars.get(idx).map(|&c| char::from(c)),
+            StringChars::Wide(chars) => chars.get(idx).and_then(|&c| char::from_u32(c)),
+        }
+    }
+
+    pub fn iter(&self) -> StringCharsIter<'a> {
+        StringCharsIter {
+            chars: *self,
+            idx: 0,
+        }
+    }
+}
+
+impl PartialEq<str> for StringChars<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.iter().eq(other.chars())
+    }
+}
+
+impl<'a> IntoIterator for StringChars<'a> {
+    type Item = char;
+    type IntoIter = StringCharsIter<'a>;
+
+    fn into_iter(self) -> StringCharsIter<'a> {
+        self.iter()
+    }
+}
+
+pub struct StringCharsIter<'a> {
+    chars: StringChars<'a>,
+    idx: usize,
+}
+
+impl Iterator for StringCharsIter<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.get(self.idx)?;
+        self.idx += 1;
+        Some(c)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.chars.len().saturating_sub(self.idx);
+        (left, Some(left))
+    }
+}
+
+impl<'vm> fmt::Display for ScmString<'vm> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&unsafe { to_string(self.scm.as_raw()) })
+    }
+}
+
+impl GuileVM {
+    /// A new Scheme string. Embedded NULs are kept.
+    pub fn string_from_str<'vm>(&'vm self, s: &str) -> Scm<'vm> {
+        ScmString::new(self, s).as_scm()
+    }
+}
+
+impl<'vm> Scm<'vm> {
+    /// The contents of a string; `None` for any other value. Use
+    /// `to_string` for how `display` prints any value.
+    pub fn string_value(&self) -> Option<String> {
+        ScmString::from_scm(*self).map(|s| s.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn surgery() {
+        crate::init(|vm| {
+            let s = ScmString::new(&vm, "hello");
+            let t = s.append(&ScmString::new(&vm, ", world"));
+            assert_eq!(t.to_string(), "hello, world");
+            assert_eq!(t.substring(7, 12).upcase().to_string(), "WORLD");
+            assert_eq!(t.index('o'), Some(4));
+            assert_eq!(t.index('z'), None);
+            assert_eq!(t.find(&ScmString::new(&vm, "wor")), Some(7));
+
+            t.set(0, 'J').unwrap();
+            assert_eq!(t.get(0), Some('J'));
+            assert_eq!(t.get(12), None);
+        });
+    }
+
+    #[test]
+    fn latin1() {
+        crate::init(|vm| {
+            let s = ScmString::from_latin1(&vm, b"caf\xe9");
+            assert_eq!(s.to_string(), "café");
+            assert_eq!(s.to_latin1(InvalidSequence::Error).unwrap(), b"caf\xe9");
+
+            let s = ScmString::new(&vm, "λx");
+            assert!(s.to_latin1(InvalidSequence::Error).is_err());
+            assert_eq!(s.to_latin1(InvalidSequence::Replace).unwrap(), b"?x");
+        });
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        crate::init(|vm| {
+            let bytes = b"a\xffb";
+            assert!(ScmString::decode(&vm, bytes, Encoding::Utf8, InvalidSequence::Error).is_err());
+            let s =
+                ScmString::decode(&vm, bytes, Encoding::Utf8, InvalidSequence::Replace).unwrap();
+            assert_eq!(s.to_string(), "a?b");
+        });
+    }
+
+    #[test]
+    fn non_ascii() {
+        crate::init(|vm| {
+            let s = ScmString::new(&vm, "Grüße");
+            assert_eq!(s.len(), 5);
+            assert_eq!(s.get(2), Some('ü'));
+            assert_eq!(s.downcase().to_string(), "grüße");
+        });
+    }
+
+    #[test]
+    fn in_place_chars() {
+        crate::init(|vm| {
+            let s = ScmString::new(&vm, "café au lait");
+            let chars = unsafe { s.chars() };
+            assert!(matches!(chars, StringChars::Narrow(_)));
+            assert_eq!(chars.len(), 12);
+            assert_eq!(chars.get(3), Some('é'));
+            assert!(unsafe { s.shared_substring(5, 7).chars() } == *"au");
+
+            let s = ScmString::new(&vm, "λx.x");
+            let chars = unsafe { s.chars() };
+            assert!(matches!(chars, StringChars::Wide(_)));
+            assert_eq!(chars.iter().collect::<String>(), "λx.x");
+            let tail = s.shared_substring(1, 4);
+            assert_eq!(unsafe { tail.chars() }.get(0), Some('x'));
+            assert!(unsafe { ScmString::new(&vm, "").chars() }.is_empty());
+        });
+    }
+
+    #[test]
+    fn scm_strings() {
+        crate::init(|vm| {
+            let s = vm.string_from_str("a\0b");
+            assert_eq!(s.string_value().as_deref(), Some("a\0b"));
+            assert_eq!(s.to_string(), "a\0b");
+
+            let n = unsafe { Scm::from_raw(&vm, guile_sys::scm_from_int64(12)) };
+            assert_eq!(n.string_value(), None);
+            assert_eq!(n.to_string(), "12");
+        });
+    }
+}
@@ -0,0 +1,169 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use guile_sys::{SCM, SCM_UNDEFINED};
+
+use crate::error::{protect, GuileError};
+use crate::scm::list_from_raw;
+use crate::string;
+use crate::{GuileVM, Scm, ScmProcedure};
+
+/// A struct vtable, describing the fields of the structs made from it.
+#[derive(Clone, Copy)]
+pub struct ScmVtable<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> ScmVtable<'vm> {
+    /// `make-vtable` with a layout such as `"pwpw"`: two characters per
+    /// field, `p` for a Scheme value or `u` for an unboxed word, then
+    /// `w` for writable. `printer`, if given, is called with the struct
+    /// and a port to print it.
+    pub fn new(
+        vm: &'vm GuileVM,
+        layout: &str,
+        printer: Option<ScmProcedure<'vm>>,
+    ) -> Result<ScmVtable<'vm>, GuileError> {
+        let printer = printer.map_or(SCM_UNDEFINED, |p| p.as_scm().as_raw());
+        unsafe {
+            let fields = string::from_str(layout);
+            let raw = protect(|| guile_sys::scm_make_vtable(fields, printer))?;
+            Ok(ScmVtable {
+                scm: Scm::from_raw(vm, raw),
+            })
+        }
+    }
+
+    /// A vtable for `count` writable Scheme-valued fields.
+    pub fn with_fields(vm: &'vm GuileVM, count: usize) -> Result<ScmVtable<'vm>, GuileError> {
+        ScmVtable::new(vm, &"pw".repeat(count), None)
+    }
+
+    /// Returns `None` if `scm` is not a vtable.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmVtable<'vm>> {
+        if guile_sys::scm_is_true(unsafe { guile_sys::scm_struct_vtable_p(scm.as_raw()) }) {
+            Some(ScmVtable { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    pub fn field_count(&self) -> usize {
+        unsafe {
+            let layout = guile_sys::scm_struct_ref(
+                self.scm.as_raw(),
+                guile_sys::scm_from_uint64(guile_sys::scm_vtable_index_layout as u64),
+            );
+            string::to_string(guile_sys::scm_symbol_to_string(layout)).len() / 2
+        }
+    }
+}
+
+/// An instance of a struct vtable.
+#[derive(Clone, Copy)]
+pub struct ScmStruct<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> ScmStruct<'vm> {
+    /// `make-struct/no-tail`, with a value for each field.
+    pub fn new(vtable: ScmVtable<'vm>, fields: &[Scm<'vm>]) -> Result<ScmStruct<'vm>, GuileError> {
+        let vtable = vtable.as_scm();
+        unsafe {
+            let init = list_from_raw(fields.iter().map(|f| f.as_raw()));
+            let raw = protect(|| guile_sys::scm_make_struct_no_tail(vtable.as_raw(), init))?;
+            Ok(ScmStruct {
+                scm: vtable.with_raw(raw),
+            })
+        }
+    }
+
+    /// Returns `None` if `scm` is not a struct.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmStruct<'vm>> {
+        if guile_sys::scm_is_true(unsafe { guile_sys::scm_struct_p(scm.as_raw()) }) {
+            Some(ScmStruct { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    pub fn vtable(&self) -> ScmVtable<'vm> {
+        ScmVtable {
+            scm: self
+                .scm
+                .with_raw(unsafe { guile_sys::scm_struct_vtable(self.scm.as_raw()) }),
+        }
+    }
+
+    unsafe fn index(idx: usize) -> SCM {
+        guile_sys::scm_from_uint64(idx as u64)
+    }
+
+    /// Fails if `idx` is out of range. Unboxed fields are returned as
+    /// integers.
+    pub fn get(&self, idx: usize) -> Result<Scm<'vm>, GuileError> {
+        let handle = self.scm.as_raw();
+        unsafe {
+            let value = protect(|| guile_sys::scm_struct_ref(handle, ScmStruct::index(idx)))?;
+            Ok(self.scm.with_raw(value))
+        }
+    }
+
+    /// Fails if `idx` is out of range, or `value` isn't an integer for an
+    /// unboxed field.
+    pub fn set(&self, idx: usize, value: Scm<'vm>) -> Result<(), GuileError> {
+        let handle = self.scm.as_raw();
+        unsafe {
+            protect(|| guile_sys::scm_struct_set_x(handle, ScmStruct::index(idx), value.as_raw()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, FromScm, ToScm};
+
+    #[test]
+    fn fields() {
+        init(|vm| {
+            let vtable = ScmVtable::new(&vm, "pwuw", None).unwrap();
+            assert_eq!(vtable.field_count(), 2);
+            assert!(ScmVtable::new(&vm, "xx", None).is_err());
+
+            let s = ScmStruct::new(vtable, &["name".to_scm(&vm), 7.to_scm(&vm)]).unwrap();
+            assert!(ScmStruct::from_scm(s.as_scm()).is_some());
+            assert!(ScmVtable::from_scm(s.vtable().as_scm()).is_some());
+            assert_eq!(String::from_scm(s.get(0).unwrap()).as_deref(), Some("name"));
+            assert_eq!(u64::from_scm(s.get(1).unwrap()), Some(7));
+
+            s.set(1, 8.to_scm(&vm)).unwrap();
+            assert_eq!(u64::from_scm(s.get(1).unwrap()), Some(8));
+            assert!(s.set(1, "x".to_scm(&vm)).is_err());
+            assert!(s.get(2).is_err());
+        });
+    }
+}
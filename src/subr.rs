@@ -0,0 +1,449 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::ffi::CString;
+use std::panic::{self, AssertUnwindSafe};
+
+use guile_sys::{SCM, SCM_BOOL_F, SCM_UNDEFINED};
+use libc::c_void;
+
+use crate::closure::panic_message;
+use crate::error::GuileError;
+use crate::scm::symbol;
+use crate::string;
+use crate::{FromScm, GuileVM, Scm, ScmList, ScmListIter, ScmProcedure, ToScm};
+
+/// Guile's limit on the number of parameters of a primitive.
+const MAX_PARAMS: usize = 10;
+
+/// `extern "C"` functions that can be defined as Scheme primitives with
+/// `GuileVM::define_fn`. Each parameter receives one argument: optional
+/// ones that weren't passed are `SCM_UNDEFINED`, and the rest list, if
+/// any, comes last.
+///
+/// # Safety
+///
+/// `PARAMS` must be the number of parameters the function takes.
+pub unsafe trait Subr: Copy {
+    #[doc(hidden)]
+    const PARAMS: usize;
+
+    #[doc(hidden)]
+    fn as_ptr(self) -> *mut c_void;
+}
+
+macro_rules! subr {
+    ($($arg:ident),*) => {
+        unsafe impl Subr for extern "C" fn($($arg),*) -> SCM {
+            const PARAMS: usize = <[&str]>::len(&[$(stringify!($arg)),*]);
+
+            fn as_ptr(self) -> *mut c_void {
+                self as *mut c_void
+            }
+        }
+
+        unsafe impl Subr for unsafe extern "C" fn($($arg),*) -> SCM {
+            const PARAMS: usize = <[&str]>::len(&[$(stringify!($arg)),*]);
+
+            fn as_ptr(self) -> *mut c_void {
+                self as *mut c_void
+            }
+        }
+    };
+}
+
+subr!();
+subr!(SCM);
+subr!(SCM, SCM);
+subr!(SCM, SCM, SCM);
+subr!(SCM, SCM, SCM, SCM);
+subr!(SCM, SCM, SCM, SCM, SCM);
+subr!(SCM, SCM, SCM, SCM, SCM, SCM);
+subr!(SCM, SCM, SCM, SCM, SCM, SCM, SCM);
+subr!(SCM, SCM, SCM, SCM, SCM, SCM, SCM, SCM);
+subr!(SCM, SCM, SCM, SCM, SCM, SCM, SCM, SCM, SCM);
+subr!(SCM, SCM, SCM, SCM, SCM, SCM, SCM, SCM, SCM, SCM);
+
+/// A primitive generated by `#[guile_fn]`, ready for
+/// `GuileVM::define_guile_fn`.
+pub trait GuileFn {
+    /// The Scheme name.
+    const NAME: &'static str;
+    /// The function's doc comment, which becomes its docstring.
+    const DOC: Option<&'static str>;
+    const REQUIRED: usize;
+    const OPTIONAL: usize;
+    const REST: bool;
+
+    type Subr: Subr;
+    const SUBR: Self::Subr;
+}
+
+/// Values a `#[guile_fn]` function can return: anything `ToScm`, or
+/// `Result<T, GuileError>` to throw the error instead.
+pub trait SubrReturn {
+    #[doc(hidden)]
+    fn into_subr_result(self, vm: &GuileVM) -> Result<Scm<'_>, GuileError>;
+}
+
+impl<T: ToScm> SubrReturn for T {
+    fn into_subr_result(self, vm: &GuileVM) -> Result<Scm<'_>, GuileError> {
+        Ok(self.to_scm(vm))
+    }
+}
+
+impl<T: ToScm> SubrReturn for Result<T, GuileError> {
+    fn into_subr_result(self, vm: &GuileVM) -> Result<Scm<'_>, GuileError> {
+        self.map(|value| value.to_scm(vm))
+    }
+}
+
+impl GuileVM {
+    /// Define `name` in the current module as a primitive that calls
+    /// `f`, taking `required` arguments, then `optional` ones, then a
+    /// rest list if `rest` is set. Write the body of `f` with
+    /// `subr_body` so errors and panics reach Scheme as throws.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` doesn't take `required + optional + rest`
+    /// parameters, or if that is more than 10.
+    pub fn define_fn<F: Subr>(
+        &self,
+        name: &str,
+        required: usize,
+        optional: usize,
+        rest: bool,
+        f: F,
+    ) -> ScmProcedure<'_> {
+        let params = required + optional + rest as usize;
+        assert!(
+            params == F::PARAMS,
+            "primitive {} declares {} parameters but its function takes {}",
+            name,
+            params,
+            F::PARAMS
+        );
+        assert!(
+            params <= MAX_PARAMS,
+            "primitive {} takes {} parameters, more than Guile allows",
+            name,
+            params
+        );
+        let c_name = CString::new(name).unwrap();
+        unsafe {
+            let raw = guile_sys::scm_c_define_gsubr(
+                c_name.as_ptr(),
+                required as i32,
+                optional as i32,
+                rest as i32,
+                f.as_ptr(),
+            );
+            ScmProcedure::from_scm(Scm::from_raw(self, raw)).unwrap()
+        }
+    }
+
+    /// Define a function marked `#[guile_fn]` in the current module.
+    pub fn define_guile_fn<F: GuileFn>(&self) -> ScmProcedure<'_> {
+        let procedure = self.define_fn(F::NAME, F::REQUIRED, F::OPTIONAL, F::REST, F::SUBR);
+        if let Some(doc) = F::DOC {
+            procedure.set_documentation(doc);
+        }
+        procedure
+    }
+}
+
+/// The arguments of a primitive, as seen from inside `subr_body`.
+pub struct SubrArgs {
+    vm: GuileVM,
+}
+
+impl SubrArgs {
+    pub fn vm(&self) -> &GuileVM {
+        &self.vm
+    }
+
+    /// Convert the argument in 1-based position `pos`, or fail with a
+    /// `wrong-type-arg` error.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be one of the primitive's parameters.
+    pub unsafe fn get<'vm, T: FromScm<'vm>>(
+        &'vm self,
+        pos: usize,
+        raw: SCM,
+    ) -> Result<T, GuileError> {
+        let value = Scm::from_raw(&self.vm, raw);
+        T::from_scm(value).ok_or_else(|| GuileError::Throw {
+            key: String::from("wrong-type-arg"),
+            message: format!(
+                "Wrong type argument in position {}: {}",
+                pos,
+                self.vm.write(value)
+            ),
+        })
+    }
+
+    /// Like `get`, for an optional parameter: `None` if the caller left
+    /// it out.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be one of the primitive's parameters.
+    pub unsafe fn get_opt<'vm, T: FromScm<'vm>>(
+        &'vm self,
+        pos: usize,
+        raw: SCM,
+    ) -> Result<Option<T>, GuileError> {
+        if guile_sys::scm_is_eq(raw, SCM_UNDEFINED) {
+            Ok(None)
+        } else {
+            self.get(pos, raw).map(Some)
+        }
+    }
+
+    /// The arguments gathered by the rest parameter.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be the primitive's rest parameter.
+    pub unsafe fn rest<'vm>(&'vm self, raw: SCM) -> Rest<'vm> {
+        let list = ScmList::from_scm(Scm::from_raw(&self.vm, raw)).unwrap();
+        Rest {
+            list,
+            iter: list.iter(),
+        }
+    }
+}
+
+/// The arguments past a primitive's required and optional ones. As a
+/// `#[guile_fn]` parameter, it must come last.
+pub struct Rest<'vm> {
+    list: ScmList<'vm>,
+    iter: ScmListIter<'vm>,
+}
+
+impl<'vm> Rest<'vm> {
+    /// All of the arguments, including ones already iterated over.
+    pub fn as_list(&self) -> ScmList<'vm> {
+        self.list
+    }
+}
+
+impl<'vm> Iterator for Rest<'vm> {
+    type Item = Scm<'vm>;
+
+    fn next(&mut self) -> Option<Scm<'vm>> {
+        self.iter.next()
+    }
+}
+
+/// Run the body of the primitive `name`, returning its result to
+/// Scheme. An `Err` is thrown with the error's key, and a panic is
+/// thrown as `rust-panic`, since neither may unwind into libguile.
+///
+/// # Safety
+///
+/// Must be called from a function registered with `define_fn`, while
+/// Guile is calling it.
+pub unsafe fn subr_body<F>(name: &str, body: F) -> SCM
+where
+    F: for<'vm> FnOnce(&'vm SubrArgs) -> Result<Scm<'vm>, GuileError>,
+{
+    let args = SubrArgs {
+        vm: GuileVM::assume_guile_mode(),
+    };
+    /* Everything owned on the Rust side is dropped before the throw
+     * skips over this frame. */
+    let (key, throw_args) = match panic::catch_unwind(AssertUnwindSafe(|| body(&args))) {
+        Ok(Ok(result)) => return result.as_raw(),
+        Ok(Err(err)) => (
+            symbol(err.key()),
+            error_args(symbol(name), &err.to_string()),
+        ),
+        Err(payload) => (
+            symbol("rust-panic"),
+            guile_sys::scm_list_1(string::from_str(&panic_message(&*payload))),
+        ),
+    };
+    guile_sys::scm_throw(key, throw_args)
+}
+
+/* The (subr format-string format-args data) shape `print-exception`
+ * expects; the message goes through ~A so tildes in it are harmless. */
+pub(crate) unsafe fn error_args(subr: SCM, message: &str) -> SCM {
+    guile_sys::scm_list_4(
+        subr,
+        string::from_str("~A"),
+        guile_sys::scm_list_1(string::from_str(message)),
+        SCM_BOOL_F,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{guile_fn, init, Language};
+
+    extern "C" fn add(a: SCM, b: SCM, c: SCM) -> SCM {
+        unsafe {
+            subr_body("rs-add", |args| {
+                let a: i32 = args.get(1, a)?;
+                let b: i32 = args.get(2, b)?;
+                let c: i32 = args.get_opt(3, c)?.unwrap_or(0);
+                Ok((a + b + c).to_scm(args.vm()))
+            })
+        }
+    }
+
+    extern "C" fn count(rest: SCM) -> SCM {
+        unsafe {
+            subr_body("rs-count", |args| {
+                Ok(args.rest(rest).count().to_scm(args.vm()))
+            })
+        }
+    }
+
+    /// Multiply `x` by `factor`.
+    #[guile_fn]
+    fn scale_by(x: f64, factor: f64) -> f64 {
+        x * factor
+    }
+
+    #[guile_fn(name = "checked-div")]
+    fn checked_div(a: i64, b: i64) -> Result<i64, GuileError> {
+        a.checked_div(b).ok_or_else(|| GuileError::Throw {
+            key: String::from("numerical-overflow"),
+            message: String::from("division by zero"),
+        })
+    }
+
+    #[guile_fn]
+    fn join_words(first: String, sep: Option<String>, rest: Rest) -> String {
+        let sep = sep.unwrap_or_else(|| String::from(" "));
+        let mut words = vec![first];
+        words.extend(rest.filter_map(|word| word.get::<String>()));
+        words.join(&sep)
+    }
+
+    extern "C" fn boom() -> SCM {
+        unsafe { subr_body("rs-boom", |_| panic!("boom")) }
+    }
+
+    #[test]
+    fn define_and_call() {
+        init(|vm| {
+            vm.define_fn(
+                "rs-add",
+                2,
+                1,
+                false,
+                add as extern "C" fn(SCM, SCM, SCM) -> SCM,
+            );
+            vm.define_fn("rs-count", 0, 0, true, count as extern "C" fn(SCM) -> SCM);
+            let eval = |code| vm.eval_language(Language::Scheme, code);
+            assert_eq!(eval("(rs-add 1 2)").unwrap().get::<i32>(), Some(3));
+            assert_eq!(eval("(rs-add 1 2 3)").unwrap().get::<i32>(), Some(6));
+            assert_eq!(eval("(rs-count 'a 'b 'c)").unwrap().get::<i32>(), Some(3));
+
+            let err = eval("(rs-add 1 \"two\")").unwrap_err();
+            assert_eq!(err.key(), "wrong-type-arg");
+            assert!(err.to_string().contains("position 2"));
+            assert_eq!(
+                eval("(rs-add 1)").unwrap_err().key(),
+                "wrong-number-of-args"
+            );
+        });
+    }
+
+    #[test]
+    fn panics_become_throws() {
+        init(|vm| {
+            vm.define_fn("rs-boom", 0, 0, false, boom as extern "C" fn() -> SCM);
+            let err = vm.eval_language(Language::Scheme, "(rs-boom)").unwrap_err();
+            assert_eq!(err.key(), "rust-panic");
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "declares 1 parameters")]
+    fn arity_mismatch() {
+        init(|vm| {
+            vm.define_fn("rs-bad", 1, 0, false, boom as extern "C" fn() -> SCM);
+        });
+    }
+
+    #[test]
+    fn attribute_macro() {
+        init(|vm| {
+            vm.define_guile_fn::<scale_by>();
+            vm.define_guile_fn::<checked_div>();
+            let eval = |code| vm.eval_language(Language::Scheme, code);
+            assert_eq!(eval("(scale-by 1.5 2)").unwrap().get::<f64>(), Some(3.0));
+            assert_eq!(scale_by(1.5, 2.0), 3.0);
+            assert_eq!(eval("(scale-by 'x 2)").unwrap_err().key(), "wrong-type-arg");
+            assert_eq!(eval("(checked-div 7 2)").unwrap().get::<i64>(), Some(3));
+            let err = eval("(checked-div 1 0)").unwrap_err();
+            assert_eq!(err.key(), "numerical-overflow");
+            assert!(err.to_string().contains("division by zero"));
+        });
+    }
+
+    #[test]
+    fn optional_and_rest() {
+        init(|vm| {
+            let join = vm.define_guile_fn::<join_words>();
+            let eval = |code| vm.eval_language(Language::Scheme, code);
+            let words = |code| eval(code).unwrap().get::<String>().unwrap();
+            assert_eq!(words("(join-words \"a\")"), "a");
+            assert_eq!(words("(join-words \"a\" \"-\")"), "a");
+            assert_eq!(words("(join-words \"a\" \"-\" \"b\" \"c\")"), "a-b-c");
+            assert_eq!(
+                eval("(join-words \"a\" 5)").unwrap_err().key(),
+                "wrong-type-arg"
+            );
+            assert!(join.call(&[]).is_err());
+        });
+    }
+
+    #[test]
+    fn documentation() {
+        init(|vm| {
+            let scale = vm.define_guile_fn::<scale_by>();
+            assert_eq!(
+                scale.documentation().as_deref(),
+                Some("Multiply `x` by `factor`.")
+            );
+            let doc = vm
+                .eval_language(Language::Scheme, "(procedure-documentation scale-by)")
+                .unwrap();
+            assert_eq!(
+                doc.get::<String>().as_deref(),
+                Some("Multiply `x` by `factor`.")
+            );
+
+            let add = vm.define_guile_fn::<join_words>();
+            assert_eq!(add.documentation(), None);
+            add.set_documentation("Join words with a separator.");
+            add.set_property("since", "0.1");
+            let since = add.property("since").unwrap();
+            assert_eq!(since.get::<String>().as_deref(), Some("0.1"));
+            assert!(add.property("missing").is_none());
+        });
+    }
+}
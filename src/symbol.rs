@@ -0,0 +1,107 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+
+use guile_sys::SCM;
+
+use crate::scm;
+use crate::string;
+use crate::{GuileVM, Scm};
+
+thread_local! {
+    /// Symbols looked up with `ScmSymbol::cached`. Each one is protected
+    /// from the GC for good, since nothing else may refer to it.
+    static CACHE: RefCell<HashMap<&'static str, SCM>> = RefCell::new(HashMap::new());
+}
+
+/// A Scheme symbol.
+#[derive(Clone, Copy)]
+pub struct ScmSymbol<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> ScmSymbol<'vm> {
+    pub fn new(vm: &'vm GuileVM, name: &str) -> ScmSymbol<'vm> {
+        unsafe {
+            ScmSymbol {
+                scm: Scm::from_raw(vm, scm::symbol(name)),
+            }
+        }
+    }
+
+    /// Like `new`, but only interns `name` the first time it's asked for
+    /// on this thread. Meant for hot paths with a fixed set of names.
+    pub fn cached(vm: &'vm GuileVM, name: &'static str) -> ScmSymbol<'vm> {
+        let raw = CACHE.with(|cache| {
+            *cache.borrow_mut().entry(name).or_insert_with(|| unsafe {
+                let raw = scm::symbol(name);
+                guile_sys::scm_gc_protect_object(raw);
+                raw
+            })
+        });
+        ScmSymbol {
+            scm: unsafe { Scm::from_raw(vm, raw) },
+        }
+    }
+
+    /// Returns `None` if `scm` is not a symbol.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmSymbol<'vm>> {
+        if guile_sys::scm_is_true(unsafe { guile_sys::scm_symbol_p(scm.as_raw()) }) {
+            Some(ScmSymbol { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    pub fn name(&self) -> String {
+        unsafe { string::to_string(guile_sys::scm_symbol_to_string(self.scm.as_raw())) }
+    }
+}
+
+impl<'vm> fmt::Display for ScmSymbol<'vm> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::init;
+
+    #[test]
+    fn interned() {
+        init(|vm| {
+            let a = ScmSymbol::new(&vm, "hot-path");
+            let b = ScmSymbol::cached(&vm, "hot-path");
+            let c = ScmSymbol::cached(&vm, "hot-path");
+            assert!(a.as_scm().is_eq(b.as_scm()));
+            assert!(b.as_scm().is_eq(c.as_scm()));
+            assert_eq!(c.to_string(), "hot-path");
+
+            assert!(ScmSymbol::from_scm(a.as_scm()).is_some());
+            assert!(ScmSymbol::from_scm(vm.string_from_str("hot-path")).is_none());
+        });
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::fmt;
+
+use crate::{GuileError, GuileVM, ScmModule};
+
+/// A `syntax-rules` transformer built up from pattern/template pairs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyntaxRules {
+    literals: Vec<String>,
+    rules: Vec<(String, String)>,
+}
+
+impl SyntaxRules {
+    pub fn new() -> SyntaxRules {
+        SyntaxRules::default()
+    }
+
+    /// Match `literal` literally in patterns, like `else` or `=>`.
+    pub fn literal(mut self, literal: &str) -> SyntaxRules {
+        self.literals.push(literal.to_string());
+        self
+    }
+
+    /// Add a rule, e.g. `.rule("(_ a b)", "(+ a b)")`. Rules are tried
+    /// in the order they were added.
+    pub fn rule(mut self, pattern: &str, template: &str) -> SyntaxRules {
+        self.rules.push((pattern.to_string(), template.to_string()));
+        self
+    }
+}
+
+/// The `(syntax-rules ...)` form.
+impl fmt::Display for SyntaxRules {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(syntax-rules ({})", self.literals.join(" "))?;
+        for (pattern, template) in &self.rules {
+            write!(f, " ({} {})", pattern, template)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl<'vm> ScmModule<'vm> {
+    /// Define and export the macro `name` in this module. `transformer`
+    /// is Scheme source for a syntax transformer, such as a
+    /// `syntax-rules` or `syntax-case` form, or a `SyntaxRules`.
+    pub fn define_syntax<T: fmt::Display>(
+        &self,
+        name: &str,
+        transformer: T,
+    ) -> Result<(), GuileError> {
+        let vm = unsafe { GuileVM::assume_guile_mode() };
+        let code = format!("(define-syntax {} {}) (export {})", name, transformer, name);
+        vm.eval_in(self, &code)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language};
+
+    #[test]
+    fn define_syntax() {
+        init(|vm| {
+            let module = ScmModule::resolve(&vm, "guile-user").unwrap();
+            let swap = SyntaxRules::new()
+                .literal("with")
+                .rule("(_ a with b)", "(let ((tmp a)) (set! a b) (set! b tmp))");
+            assert_eq!(
+                swap.to_string(),
+                "(syntax-rules (with) ((_ a with b) (let ((tmp a)) (set! a b) (set! b tmp))))"
+            );
+            module.define_syntax("swap!", &swap).unwrap();
+            let result = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(let ((x 1) (y 2)) (swap! x with y) (list x y))",
+                )
+                .unwrap();
+            assert_eq!(vm.write(result), "(2 1)");
+
+            module
+                .define_syntax(
+                    "unless-zero",
+                    "(syntax-rules () ((_ n e) (if (zero? n) #f e)))",
+                )
+                .unwrap();
+            let result = vm
+                .eval_language(Language::Scheme, "(unless-zero 0 (car '()))")
+                .unwrap();
+            assert!(result.is_false());
+
+            assert!(module.define_syntax("broken", "(syntax-rules").is_err());
+        });
+    }
+}
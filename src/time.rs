@@ -0,0 +1,260 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use guile_sys::SCM;
+
+use crate::error::{protect, GuileError};
+use crate::scm::{public_ref, symbol};
+use crate::{FromScm, GuileVM, Scm, ToScm};
+
+/* Plain conversions use `(seconds . microseconds)` pairs, the format of
+ * `gettimeofday`; `Srfi19` converts to and from SRFI-19 time objects,
+ * which keep nanoseconds. */
+
+const MICROS: i64 = 1_000_000;
+
+/// Seconds and microseconds relative to the epoch, with the microseconds
+/// always in `0..1_000_000` as `gettimeofday` has them.
+fn split_time(time: SystemTime) -> (i64, i64) {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() as i64, i64::from(d.subsec_micros())),
+        Err(e) => {
+            let micros = -(e.duration().as_micros() as i64);
+            (micros.div_euclid(MICROS), micros.rem_euclid(MICROS))
+        }
+    }
+}
+
+fn join_time(secs: i64, nanos: i64) -> Option<SystemTime> {
+    let total = i128::from(secs) * 1_000_000_000 + i128::from(nanos);
+    let offset = Duration::from_nanos(u64::try_from(total.unsigned_abs()).ok()?);
+    if total >= 0 {
+        UNIX_EPOCH.checked_add(offset)
+    } else {
+        UNIX_EPOCH.checked_sub(offset)
+    }
+}
+
+unsafe fn pair(secs: i64, micros: i64) -> SCM {
+    guile_sys::scm_cons(
+        guile_sys::scm_from_int64(secs),
+        guile_sys::scm_from_int64(micros),
+    )
+}
+
+/// `(seconds . microseconds)`, if `scm` is such a pair.
+fn unpair(scm: Scm) -> Option<(i64, i64)> {
+    let raw = scm.as_raw();
+    unsafe {
+        if guile_sys::scm_is_pair(raw) == 0 {
+            return None;
+        }
+        let secs = i64::from_scm(scm.with_raw(guile_sys::scm_car(raw)))?;
+        let micros = i64::from_scm(scm.with_raw(guile_sys::scm_cdr(raw)))?;
+        Some((secs, micros))
+    }
+}
+
+/// A `(seconds . microseconds)` pair. Sub-microsecond precision is lost.
+impl ToScm for Duration {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe {
+            let raw = guile_sys::scm_cons(
+                guile_sys::scm_from_uint64(self.as_secs()),
+                guile_sys::scm_from_uint32(self.subsec_micros()),
+            );
+            Scm::from_raw(vm, raw)
+        }
+    }
+}
+
+/// From a `(seconds . microseconds)` pair or a non-negative real number
+/// of seconds.
+impl<'vm> FromScm<'vm> for Duration {
+    fn from_scm(scm: Scm<'vm>) -> Option<Duration> {
+        if let Some((secs, micros)) = unpair(scm) {
+            let micros = u64::try_from(i128::from(secs) * 1_000_000 + i128::from(micros)).ok()?;
+            return Some(Duration::from_micros(micros));
+        }
+        Duration::try_from_secs_f64(f64::from_scm(scm)?).ok()
+    }
+}
+
+/// A `(seconds . microseconds)` pair since the epoch, like
+/// `gettimeofday` returns.
+impl ToScm for SystemTime {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        let (secs, micros) = split_time(*self);
+        unsafe { Scm::from_raw(vm, pair(secs, micros)) }
+    }
+}
+
+/// From a `gettimeofday` pair or a whole number of seconds since the
+/// epoch, like `current-time` returns.
+impl<'vm> FromScm<'vm> for SystemTime {
+    fn from_scm(scm: Scm<'vm>) -> Option<SystemTime> {
+        let (secs, micros) = unpair(scm).or_else(|| Some((i64::from_scm(scm)?, 0)))?;
+        join_time(secs, micros.checked_mul(1000)?)
+    }
+}
+
+/// Converts a `Duration` to and from a SRFI-19 `time-duration` object,
+/// and a `SystemTime` to and from a `time-utc` one. Converting to Scheme
+/// panics if `(srfi srfi-19)` can't be loaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Srfi19<T>(pub T);
+
+unsafe fn make_time(kind: &str, secs: SCM, nanos: i64) -> Result<SCM, GuileError> {
+    protect(|| {
+        let make = public_ref("srfi srfi-19", "make-time");
+        guile_sys::scm_call_3(make, symbol(kind), guile_sys::scm_from_int64(nanos), secs)
+    })
+}
+
+/// The seconds and nanoseconds of a SRFI-19 time of type `kind`.
+fn time_parts(scm: Scm, kind: &str) -> Option<(i64, i64)> {
+    let raw = scm.as_raw();
+    unsafe {
+        let parts = protect(|| {
+            let is_time = guile_sys::scm_call_1(public_ref("srfi srfi-19", "time?"), raw);
+            if guile_sys::scm_is_false(is_time) {
+                return guile_sys::SCM_BOOL_F;
+            }
+            let kind_of = guile_sys::scm_call_1(public_ref("srfi srfi-19", "time-type"), raw);
+            if !guile_sys::scm_is_eq(kind_of, symbol(kind)) {
+                return guile_sys::SCM_BOOL_F;
+            }
+            guile_sys::scm_cons(
+                guile_sys::scm_call_1(public_ref("srfi srfi-19", "time-second"), raw),
+                guile_sys::scm_call_1(public_ref("srfi srfi-19", "time-nanosecond"), raw),
+            )
+        })
+        .ok()?;
+        let parts = scm.with_raw(parts);
+        if parts.is_false() {
+            return None;
+        }
+        let secs = i64::from_scm(parts.with_raw(guile_sys::scm_car(parts.as_raw())))?;
+        let nanos = i64::from_scm(parts.with_raw(guile_sys::scm_cdr(parts.as_raw())))?;
+        Some((secs, nanos))
+    }
+}
+
+impl ToScm for Srfi19<Duration> {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        unsafe {
+            let secs = guile_sys::scm_from_uint64(self.0.as_secs());
+            let nanos = i64::from(self.0.subsec_nanos());
+            let time = make_time("time-duration", secs, nanos).expect("SRFI-19 make-time failed");
+            Scm::from_raw(vm, time)
+        }
+    }
+}
+
+impl<'vm> FromScm<'vm> for Srfi19<Duration> {
+    fn from_scm(scm: Scm<'vm>) -> Option<Srfi19<Duration>> {
+        let (secs, nanos) = time_parts(scm, "time-duration")?;
+        Some(Srfi19(Duration::new(
+            u64::try_from(secs).ok()?,
+            u32::try_from(nanos).ok()?,
+        )))
+    }
+}
+
+impl ToScm for Srfi19<SystemTime> {
+    fn to_scm<'vm>(&self, vm: &'vm GuileVM) -> Scm<'vm> {
+        let nanos = match self.0.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_nanos() as i128,
+            Err(e) => -(e.duration().as_nanos() as i128),
+        };
+        let secs = nanos.div_euclid(1_000_000_000) as i64;
+        let nanos = nanos.rem_euclid(1_000_000_000) as i64;
+        unsafe {
+            let secs = guile_sys::scm_from_int64(secs);
+            let time = make_time("time-utc", secs, nanos).expect("SRFI-19 make-time failed");
+            Scm::from_raw(vm, time)
+        }
+    }
+}
+
+impl<'vm> FromScm<'vm> for Srfi19<SystemTime> {
+    fn from_scm(scm: Scm<'vm>) -> Option<Srfi19<SystemTime>> {
+        let (secs, nanos) = time_parts(scm, "time-utc")?;
+        join_time(secs, nanos).map(Srfi19)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{init, Language};
+
+    #[test]
+    fn pairs() {
+        init(|vm| {
+            let d = Duration::from_micros(2_500_001);
+            assert_eq!(vm.write(d.to_scm(&vm)), "(2 . 500001)");
+            assert_eq!(Duration::from_scm(d.to_scm(&vm)), Some(d));
+            assert_eq!(
+                Duration::from_scm(1.5f64.to_scm(&vm)),
+                Some(Duration::from_millis(1500))
+            );
+            assert_eq!(Duration::from_scm((-1i32).to_scm(&vm)), None);
+            assert_eq!(
+                vm.write(Duration::MAX.to_scm(&vm)),
+                "(18446744073709551615 . 999999)"
+            );
+
+            let before = UNIX_EPOCH - Duration::from_micros(1_500_000);
+            assert_eq!(vm.write(before.to_scm(&vm)), "(-2 . 500000)");
+            assert_eq!(SystemTime::from_scm(before.to_scm(&vm)), Some(before));
+
+            let now = vm
+                .eval_language(Language::Scheme, "(gettimeofday)")
+                .unwrap();
+            let now = SystemTime::from_scm(now).unwrap();
+            assert!(SystemTime::now().duration_since(now).unwrap() < Duration::from_secs(60));
+            let secs = vm
+                .eval_language(Language::Scheme, "(current-time)")
+                .unwrap();
+            assert!(SystemTime::from_scm(secs).is_some());
+        });
+    }
+
+    #[test]
+    fn srfi_19() {
+        init(|vm| {
+            let d = Duration::new(3, 7);
+            let time = Srfi19(d).to_scm(&vm);
+            assert_eq!(Srfi19::<Duration>::from_scm(time), Some(Srfi19(d)));
+            assert_eq!(Srfi19::<SystemTime>::from_scm(time), None);
+
+            let t = UNIX_EPOCH + Duration::new(1_700_000_000, 123);
+            assert_eq!(
+                Srfi19::<SystemTime>::from_scm(Srfi19(t).to_scm(&vm)),
+                Some(Srfi19(t))
+            );
+
+            let now = vm
+                .eval_language(Language::Scheme, "((@ (srfi srfi-19) current-time))")
+                .unwrap();
+            assert!(Srfi19::<SystemTime>::from_scm(now).is_some());
+        });
+    }
+}
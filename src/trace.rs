@@ -0,0 +1,210 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use guile_sys::{SCM, SCM_UNSPECIFIED};
+
+use crate::closure::make_procedure;
+use crate::error::{protect, GuileError};
+use crate::scm::{public_ref, symbol};
+use crate::string;
+use crate::{GuileVM, Scm};
+
+/// The VM hooks of `(system vm vm)`. Guile 3 has no push-continuation
+/// hook; returns are reported by `Return`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VmHook {
+    /// A procedure is about to be applied.
+    Apply,
+    /// A procedure is returning.
+    Return,
+    /// A continuation is being aborted to a prompt.
+    Abort,
+    /// The next instruction is about to run.
+    Next,
+}
+
+impl VmHook {
+    unsafe fn add(self, proc_: SCM) -> SCM {
+        match self {
+            VmHook::Apply => guile_sys::scm_vm_add_apply_hook_x(proc_),
+            VmHook::Return => guile_sys::scm_vm_add_return_hook_x(proc_),
+            VmHook::Abort => guile_sys::scm_vm_add_abort_hook_x(proc_),
+            VmHook::Next => guile_sys::scm_vm_add_next_hook_x(proc_),
+        }
+    }
+
+    unsafe fn remove(self, proc_: SCM) -> SCM {
+        match self {
+            VmHook::Apply => guile_sys::scm_vm_remove_apply_hook_x(proc_),
+            VmHook::Return => guile_sys::scm_vm_remove_return_hook_x(proc_),
+            VmHook::Abort => guile_sys::scm_vm_remove_abort_hook_x(proc_),
+            VmHook::Next => guile_sys::scm_vm_remove_next_hook_x(proc_),
+        }
+    }
+}
+
+/// The bytecode engines of `(system vm vm)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VmEngine {
+    /// The fast engine, which never runs hooks.
+    Regular,
+    /// The engine that runs VM hooks, and so supports tracing and traps.
+    Debug,
+}
+
+impl VmEngine {
+    fn name(self) -> &'static str {
+        match self {
+            VmEngine::Regular => "regular",
+            VmEngine::Debug => "debug",
+        }
+    }
+}
+
+/// The VM frame a hook fired in.
+#[derive(Clone, Copy)]
+pub struct Frame<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> Frame<'vm> {
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    /// `frame-procedure-name`, or `None` for anonymous procedures.
+    pub fn procedure_name(&self) -> Option<String> {
+        let frame = self.scm.as_raw();
+        unsafe {
+            let name = protect(|| {
+                guile_sys::scm_call_1(public_ref("system vm frame", "frame-procedure-name"), frame)
+            })
+            .ok()?;
+            if guile_sys::scm_is_true(guile_sys::scm_symbol_p(name)) {
+                Some(string::to_string(guile_sys::scm_symbol_to_string(name)))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// A handler registered with `GuileVM::add_vm_hook`. The handler's
+/// procedure stays reachable through the VM until it's removed.
+pub struct VmHookHandle {
+    hook: VmHook,
+    proc_: SCM,
+}
+
+impl GuileVM {
+    /// Call `handler` every time `hook` fires, e.g. to trace calls or
+    /// collect stacks for a flame graph.
+    ///
+    /// Hooks are only run by `VmEngine::Debug`, see `set_vm_engine`.
+    pub fn add_vm_hook<F>(&self, hook: VmHook, handler: F) -> Result<VmHookHandle, GuileError>
+    where
+        F: Fn(&Frame) + Send + 'static,
+    {
+        unsafe {
+            let proc_ = make_procedure(
+                "vm-hook",
+                Box::new(move |args| {
+                    let vm = GuileVM::assume_guile_mode();
+                    let frame = Frame {
+                        scm: Scm::from_raw(&vm, guile_sys::scm_car(args)),
+                    };
+                    handler(&frame);
+                    Ok(SCM_UNSPECIFIED)
+                }),
+            );
+            protect(|| {
+                hook.add(proc_);
+                adjust_trace_level(1)
+            })?;
+            Ok(VmHookHandle { hook, proc_ })
+        }
+    }
+
+    /// Switch the current thread to `engine`, from the next call into
+    /// the VM on.
+    pub fn set_vm_engine(&self, engine: VmEngine) -> Result<(), GuileError> {
+        unsafe {
+            protect(|| guile_sys::scm_set_vm_engine_x(symbol(engine.name())))?;
+        }
+        Ok(())
+    }
+
+    pub fn vm_engine(&self) -> VmEngine {
+        let engine = unsafe {
+            string::to_string(guile_sys::scm_symbol_to_string(guile_sys::scm_vm_engine()))
+        };
+        if engine == "debug" {
+            VmEngine::Debug
+        } else {
+            VmEngine::Regular
+        }
+    }
+
+    pub fn remove_vm_hook(&self, handle: VmHookHandle) -> Result<(), GuileError> {
+        unsafe {
+            protect(|| {
+                handle.hook.remove(handle.proc_);
+                adjust_trace_level(-1)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+unsafe fn adjust_trace_level(by: i64) -> SCM {
+    let level = guile_sys::scm_to_int64(guile_sys::scm_vm_trace_level());
+    guile_sys::scm_set_vm_trace_level_x(guile_sys::scm_from_int64(level + by))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::init;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn trace_calls() {
+        init(|vm| {
+            let calls = Arc::new(Mutex::new(Vec::new()));
+            let handler_calls = calls.clone();
+            vm.set_vm_engine(VmEngine::Debug).unwrap();
+            let thunk = vm
+                .compile("(define (traced-procedure) 42) (traced-procedure)")
+                .unwrap();
+            let handle = vm
+                .add_vm_hook(VmHook::Apply, move |frame| {
+                    if let Some(name) = frame.procedure_name() {
+                        handler_calls.lock().unwrap().push(name);
+                    }
+                })
+                .unwrap();
+            thunk.call(&[]).unwrap();
+            vm.remove_vm_hook(handle).unwrap();
+
+            assert!(calls
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|name| name == "traced-procedure"));
+        });
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::marker::PhantomData;
+
+use guile_sys::{scm_t_array_element_type, SCM};
+
+use crate::array::{ArrayHandle, ArraySlice, ArraySliceMut};
+use crate::{GuileVM, Scm};
+
+/// Element types of the SRFI-4 uniform vectors (`u8vector`,
+/// `f64vector`, ...).
+///
+/// # Safety
+///
+/// `is_vector` must only accept vectors whose elements are laid out as
+/// `Self`.
+pub unsafe trait UniformElement: Copy {
+    /// The type symbol `make-typed-array` takes, e.g. `"f64"`.
+    #[doc(hidden)]
+    const TYPE: &'static str;
+    #[doc(hidden)]
+    const ELEMENT_TYPE: scm_t_array_element_type;
+    /// # Safety
+    ///
+    /// Must be called in guile mode, with a valid `raw`.
+    #[doc(hidden)]
+    unsafe fn is_vector(raw: SCM) -> bool;
+    /// # Safety
+    ///
+    /// Must be called in guile mode.
+    #[doc(hidden)]
+    unsafe fn make_vector(len: usize) -> SCM;
+}
+
+macro_rules! uniform_elements {
+    ($($ty:ty: $name:expr, $elt:ident, $pred:ident, $make:ident;)*) => {$(
+        unsafe impl UniformElement for $ty {
+            const TYPE: &'static str = $name;
+            const ELEMENT_TYPE: scm_t_array_element_type = guile_sys::$elt;
+
+            unsafe fn is_vector(raw: SCM) -> bool {
+                guile_sys::scm_is_true(guile_sys::$pred(raw))
+            }
+
+            unsafe fn make_vector(len: usize) -> SCM {
+                let zero = guile_sys::scm_from_int64(0);
+                guile_sys::$make(guile_sys::scm_from_uint64(len as u64), zero)
+            }
+        }
+    )*};
+}
+
+uniform_elements! {
+    u8: "u8", scm_t_array_element_type_SCM_ARRAY_ELEMENT_TYPE_U8,
+        scm_u8vector_p, scm_make_u8vector;
+    i8: "s8", scm_t_array_element_type_SCM_ARRAY_ELEMENT_TYPE_S8,
+        scm_s8vector_p, scm_make_s8vector;
+    u16: "u16", scm_t_array_element_type_SCM_ARRAY_ELEMENT_TYPE_U16,
+        scm_u16vector_p, scm_make_u16vector;
+    i16: "s16", scm_t_array_element_type_SCM_ARRAY_ELEMENT_TYPE_S16,
+        scm_s16vector_p, scm_make_s16vector;
+    u32: "u32", scm_t_array_element_type_SCM_ARRAY_ELEMENT_TYPE_U32,
+        scm_u32vector_p, scm_make_u32vector;
+    i32: "s32", scm_t_array_element_type_SCM_ARRAY_ELEMENT_TYPE_S32,
+        scm_s32vector_p, scm_make_s32vector;
+    u64: "u64", scm_t_array_element_type_SCM_ARRAY_ELEMENT_TYPE_U64,
+        scm_u64vector_p, scm_make_u64vector;
+    i64: "s64", scm_t_array_element_type_SCM_ARRAY_ELEMENT_TYPE_S64,
+        scm_s64vector_p, scm_make_s64vector;
+    f32: "f32", scm_t_array_element_type_SCM_ARRAY_ELEMENT_TYPE_F32,
+        scm_f32vector_p, scm_make_f32vector;
+    f64: "f64", scm_t_array_element_type_SCM_ARRAY_ELEMENT_TYPE_F64,
+        scm_f64vector_p, scm_make_f64vector;
+}
+
+/// A SRFI-4 uniform vector of `T`s, e.g. `ScmUniformVector<f64>` for an
+/// `f64vector`.
+pub struct ScmUniformVector<'vm, T> {
+    scm: Scm<'vm>,
+    _element: PhantomData<T>,
+}
+
+/* Derived impls would require `T: Clone`. */
+impl<T> Clone for ScmUniformVector<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ScmUniformVector<'_, T> {}
+
+impl<'vm, T: UniformElement> ScmUniformVector<'vm, T> {
+    /// Create a vector of `len` zeros.
+    pub fn new(vm: &'vm GuileVM, len: usize) -> ScmUniformVector<'vm, T> {
+        ScmUniformVector {
+            scm: unsafe { Scm::from_raw(vm, T::make_vector(len)) },
+            _element: PhantomData,
+        }
+    }
+
+    pub fn from_slice(vm: &'vm GuileVM, items: &[T]) -> ScmUniformVector<'vm, T> {
+        let mut uvec = ScmUniformVector::new(vm, items.len());
+        /* Nothing else has seen the new vector yet. */
+        unsafe { uvec.as_mut_slice() }.copy_from_slice(items);
+        uvec
+    }
+
+    /// Returns `None` unless `scm` is a uniform vector of `T`s.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmUniformVector<'vm, T>> {
+        if unsafe { T::is_vector(scm.as_raw()) } {
+            Some(ScmUniformVector {
+                scm,
+                _element: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    pub fn len(&self) -> usize {
+        unsafe { guile_sys::scm_c_array_length(self.scm.as_raw()) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Borrow the elements in place.
+    pub fn as_slice(&self) -> ArraySlice<'_, T> {
+        unsafe { ArraySlice::new(ArrayHandle::new(self.scm.as_raw()), self.len()) }
+    }
+
+    /// Borrow the elements in place for writing.
+    ///
+    /// # Safety
+    ///
+    /// Copies of this vector share its storage, and the borrow checker
+    /// can't see them. While the guard is alive, nothing else may read or
+    /// write the elements: no `as_slice` or `as_mut_slice` borrow through
+    /// a copy, and no Scheme code that touches the vector.
+    pub unsafe fn as_mut_slice(&mut self) -> ArraySliceMut<'_, T> {
+        ArraySliceMut::new(ArrayHandle::new(self.scm.as_raw()), self.len())
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.as_slice().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Language, ScmProcedure};
+
+    #[test]
+    fn shared_with_scheme() {
+        crate::init(|vm| {
+            let mut samples = ScmUniformVector::from_slice(&vm, &[0.5f64, 1.5, 2.0]);
+            assert_eq!(samples.len(), 3);
+            unsafe { samples.as_mut_slice() }
+                .iter_mut()
+                .for_each(|x| *x *= 2.0);
+
+            let sum = vm
+                .eval_language(
+                    Language::Scheme,
+                    "(lambda (v) (apply + (f64vector->list v)))",
+                )
+                .unwrap();
+            let sum = ScmProcedure::from_scm(sum).unwrap();
+            assert_eq!(
+                sum.call(&[samples.as_scm()]).unwrap().get::<f64>(),
+                Some(8.0)
+            );
+            assert_eq!(format!("{:?}", samples.as_scm()), "#f64(1.0 3.0 4.0)");
+        });
+    }
+
+    #[test]
+    fn element_type_must_match() {
+        crate::init(|vm| {
+            let scm = vm.eval_language(Language::Scheme, "#s32(-1 2)").unwrap();
+            let ints = ScmUniformVector::<i32>::from_scm(scm).unwrap();
+            assert_eq!(ints.to_vec(), vec![-1, 2]);
+            assert!(ScmUniformVector::<u32>::from_scm(scm).is_none());
+            assert!(ScmUniformVector::<f32>::new(&vm, 0).as_slice().is_empty());
+        });
+    }
+}
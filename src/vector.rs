@@ -0,0 +1,298 @@
+// Copyright 2016 David Li
+
+// This file is part of guile-rs.
+
+// guile-rs is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// guile-rs is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public
+// License along with guile-rs.  If not, see
+// <http://www.gnu.org/licenses/>.
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::slice;
+
+use guile_sys::{SCM, SCM_UNSPECIFIED};
+
+use crate::array::ArrayHandle;
+use crate::{GuileVM, Scm, ToScm};
+
+/// A Scheme vector.
+#[derive(Clone, Copy)]
+pub struct ScmVector<'vm> {
+    scm: Scm<'vm>,
+}
+
+impl<'vm> ScmVector<'vm> {
+    /// Create a vector of `len` elements, all set to `fill`.
+    pub fn new(vm: &'vm GuileVM, len: usize, fill: Scm<'vm>) -> ScmVector<'vm> {
+        unsafe {
+            let raw = guile_sys::scm_c_make_vector(len, fill.as_raw());
+            ScmVector {
+                scm: Scm::from_raw(vm, raw),
+            }
+        }
+    }
+
+    pub fn from_slice<T: ToScm>(vm: &'vm GuileVM, items: &[T]) -> ScmVector<'vm> {
+        unsafe {
+            let raw = guile_sys::scm_c_make_vector(items.len(), SCM_UNSPECIFIED);
+            for (idx, item) in items.iter().enumerate() {
+                guile_sys::scm_c_vector_set_x(raw, idx, item.to_scm(vm).as_raw());
+            }
+            ScmVector {
+                scm: Scm::from_raw(vm, raw),
+            }
+        }
+    }
+
+    /// Returns `None` if `scm` is not a vector.
+    pub fn from_scm(scm: Scm<'vm>) -> Option<ScmVector<'vm>> {
+        if unsafe { guile_sys::scm_is_vector(scm.as_raw()) } != 0 {
+            Some(ScmVector { scm })
+        } else {
+            None
+        }
+    }
+
+    pub fn as_scm(&self) -> Scm<'vm> {
+        self.scm
+    }
+
+    pub fn len(&self) -> usize {
+        unsafe { guile_sys::scm_c_vector_length(self.scm.as_raw()) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `None` if `idx` is out of bounds.
+    pub fn get(&self, idx: usize) -> Option<Scm<'vm>> {
+        if idx < self.len() {
+            Some(
+                self.scm
+                    .with_raw(unsafe { guile_sys::scm_c_vector_ref(self.scm.as_raw(), idx) }),
+            )
+        } else {
+            None
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn set(&mut self, idx: usize, value: Scm<'vm>) {
+        assert!(
+            idx < self.len(),
+            "vector index {} out of bounds (length {})",
+            idx,
+            self.len()
+        );
+        unsafe { guile_sys::scm_c_vector_set_x(self.scm.as_raw(), idx, value.as_raw()) };
+    }
+
+    pub fn iter(&self) -> ScmVectorIter<'vm> {
+        ScmVectorIter {
+            vector: *self,
+            idx: 0,
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<Scm<'vm>> {
+        self.iter().collect()
+    }
+
+    /// Borrow the elements in place, without a call per element.
+    pub fn elements(&self) -> ScmVectorElements<'_> {
+        let mut handle = ArrayHandle::empty();
+        let (mut len, mut inc) = (0, 0);
+        let ptr = unsafe {
+            guile_sys::scm_vector_elements(
+                self.scm.as_raw(),
+                handle.as_mut_ptr(),
+                &mut len,
+                &mut inc,
+            )
+        };
+        /* `from_scm` only admits simple vectors, never strided slices of
+         * arrays, so the elements are contiguous. */
+        assert_eq!(inc, 1, "vector elements aren't contiguous");
+        ScmVectorElements {
+            _handle: handle,
+            elements: unsafe { slice::from_raw_parts(ptr, len) },
+        }
+    }
+
+    /// Borrow the elements in place for writing.
+    ///
+    /// # Safety
+    ///
+    /// Copies of this `ScmVector` share its storage, and the borrow checker
+    /// can't see them. While the guard is alive, nothing else may read or
+    /// write the elements: no `elements` or `elements_mut` borrow through
+    /// a copy, no `set` or `get` through a copy, and no Scheme code that
+    /// touches the vector.
+    pub unsafe fn elements_mut(&mut self) -> ScmVectorElementsMut<'_> {
+        let mut handle = ArrayHandle::empty();
+        let (mut len, mut inc) = (0, 0);
+        let ptr = guile_sys::scm_vector_writable_elements(
+            self.scm.as_raw(),
+            handle.as_mut_ptr(),
+            &mut len,
+            &mut inc,
+        );
+        assert_eq!(inc, 1, "vector elements aren't contiguous");
+        ScmVectorElementsMut {
+            _handle: handle,
+            elements: ptr,
+            len,
+            _vector: PhantomData,
+        }
+    }
+}
+
+/// The elements of an [`ScmVector`], released when dropped.
+pub struct ScmVectorElements<'a> {
+    _handle: ArrayHandle,
+    elements: &'a [SCM],
+}
+
+impl Deref for ScmVectorElements<'_> {
+    type Target = [SCM];
+
+    fn deref(&self) -> &[SCM] {
+        self.elements
+    }
+}
+
+/// The writable elements of an [`ScmVector`], released when dropped.
+pub struct ScmVectorElementsMut<'a> {
+    _handle: ArrayHandle,
+    elements: *mut SCM,
+    len: usize,
+    _vector: PhantomData<&'a mut [SCM]>,
+}
+
+impl Deref for ScmVectorElementsMut<'_> {
+    type Target = [SCM];
+
+    fn deref(&self) -> &[SCM] {
+        unsafe { slice::from_raw_parts(self.elements, self.len) }
+    }
+}
+
+impl DerefMut for ScmVectorElementsMut<'_> {
+    fn deref_mut(&mut self) -> &mut [SCM] {
+        unsafe { slice::from_raw_parts_mut(self.elements, self.len) }
+    }
+}
+
+impl<'vm> IntoIterator for ScmVector<'vm> {
+    type Item = Scm<'vm>;
+    type IntoIter = ScmVectorIter<'vm>;
+
+    fn into_iter(self) -> ScmVectorIter<'vm> {
+        self.iter()
+    }
+}
+
+impl<'vm> IntoIterator for &ScmVector<'vm> {
+    type Item = Scm<'vm>;
+    type IntoIter = ScmVectorIter<'vm>;
+
+    fn into_iter(self) -> ScmVectorIter<'vm> {
+        self.iter()
+    }
+}
+
+/// Yields elements by index, rechecking the length each step in case
+/// Scheme code swapped the vector's contents out from under us.
+pub struct ScmVectorIter<'vm> {
+    vector: ScmVector<'vm>,
+    idx: usize,
+}
+
+impl<'vm> Iterator for ScmVectorIter<'vm> {
+    type Item = Scm<'vm>;
+
+    fn next(&mut self) -> Option<Scm<'vm>> {
+        let item = self.vector.get(self.idx)?;
+        self.idx += 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.vector.len().saturating_sub(self.idx);
+        (left, Some(left))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use guile_sys::SCM_BOOL_F;
+
+    #[test]
+    fn index_and_iterate() {
+        crate::init(|vm| {
+            let mut vector = ScmVector::from_slice(&vm, &[1i32, 2, 3]);
+            assert_eq!(vector.len(), 3);
+            assert_eq!(vector.get(1).and_then(|v| v.get::<i32>()), Some(2));
+            assert!(vector.get(3).is_none());
+
+            vector.set(0, "one".to_scm(&vm));
+            assert_eq!(format!("{:?}", vector.as_scm()), "#(\"one\" 2 3)");
+            let tail: Vec<i32> = vector.iter().skip(1).filter_map(|v| v.get()).collect();
+            assert_eq!(tail, vec![2, 3]);
+            assert_eq!(vector.to_vec().len(), 3);
+        });
+    }
+
+    #[test]
+    fn element_slices() {
+        crate::init(|vm| {
+            let mut vector = ScmVector::from_slice(&vm, &[1i32, 2, 3]);
+            let sum: i32 = vector
+                .elements()
+                .iter()
+                .filter_map(|&raw| vector.as_scm().with_raw(raw).get::<i32>())
+                .sum();
+            assert_eq!(sum, 6);
+
+            let zero = 0i32.to_scm(&vm).as_raw();
+            let mut elements = unsafe { vector.elements_mut() };
+            elements[1..].fill(zero);
+            drop(elements);
+            assert_eq!(format!("{:?}", vector.as_scm()), "#(1 0 0)");
+            assert!(ScmVector::new(&vm, 0, 0i32.to_scm(&vm))
+                .elements()
+                .is_empty());
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn set_out_of_bounds() {
+        crate::init(|vm| {
+            let mut vector = ScmVector::new(&vm, 2, 0i32.to_scm(&vm));
+            vector.set(2, 1i32.to_scm(&vm));
+        });
+    }
+
+    #[test]
+    fn not_a_vector() {
+        crate::init(|vm| {
+            assert!(ScmVector::from_scm(vec![1i32].to_scm(&vm)).is_none());
+            let f = unsafe { Scm::from_raw(&vm, SCM_BOOL_F) };
+            assert!(ScmVector::from_scm(f).is_none());
+        });
+    }
+}